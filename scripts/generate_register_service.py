@@ -12,6 +12,8 @@ from elftools.elf.elffile import ELFFile
 MCUS = [
     "atmega8", "atmega16",
 
+    "attiny10", "attiny20", "attiny40",
+
     "atxmega16a4u", "atxmega32a4u", "atxmega64a4u", "atxmega128a4u"
 ]
 
@@ -24,17 +26,78 @@ USART_INDEXES = [
     "C0", "C1", "D0", "D1", "E0", "E1", "F0", "F1"
 ]
 
+USART_VECTORS = [
+    "USART{}_RXC_vect_num", "USART{}_DRE_vect_num", "USART{}_TXC_vect_num"
+]
+
+TIMERS = [
+    "TCC0", "TCC1", "TCD0", "TCD1", "TCE0", "TCE1", "TCF0", "TCF1"
+]
+
+CLASSIC_TIMER_VECTORS = [
+    "TIMER0_OVF_vect_num", "TIMER0_COMP_vect_num",
+    "TIMER1_OVF_vect_num", "TIMER1_COMPA_vect_num", "TIMER1_COMPB_vect_num",
+    "TIMER1_CAPT_vect_num",
+    "TIMER2_OVF_vect_num", "TIMER2_COMP_vect_num"
+]
+
+PORTS = [
+    "A", "B", "C", "D", "E", "F", "R"
+]
+
+EXTERNAL_INTERRUPT_VECTORS = [
+    "INT0_vect_num", "INT1_vect_num", "INT2_vect_num",
+    "PCINT0_vect_num", "PCINT1_vect_num", "PCINT2_vect_num"
+]
+
+PORT_VECTORS = [
+    "PORT{}_INT0_vect_num", "PORT{}_INT1_vect_num"
+]
+
+TIMER_VECTORS = [
+    "{}_OVF_vect_num", "{}_ERR_vect_num",
+    "{}_CCA_vect_num", "{}_CCB_vect_num", "{}_CCC_vect_num", "{}_CCD_vect_num"
+]
+
 IOREGS = [
     # GPIO
-    "SREG", "SPH", "SPL", "RAMPD", "RAMPX", "RAMPY", "RAMPZ",
+    "SREG", "SPH", "SPL", "RAMPD", "RAMPX", "RAMPY", "RAMPZ", "EIND",
 
-    "PORTA", "PORTB", "PORTC", "PORTD",
+    "PORTA", "PORTB", "PORTC", "PORTD", "PORTE", "PORTF", "PORTR",
     "PINA", "PINB", "PINC", "PIND",
     "DDRA", "DDRB", "DDRC", "DDRD",
+    "VPORT0", "VPORT1", "VPORT2", "VPORT3",
+    "PORTCFG_VPCTRLA", "PORTCFG_VPCTRLB",
+
+    # external interrupts
+    "GICR", "GIFR", "EICRA", "EIMSK", "EIFR",
+    "PCICR", "PCIFR", "PCMSK", "PCMSK0", "PCMSK1", "PCMSK2",
+
+    # PMIC
+    "PMIC_STATUS", "PMIC_INTPRI", "PMIC_CTRL",
+
+    # sleep
+    "MCUCR", "SMCR", "SLEEP_CTRL",
+
+    # NVM
+    "SPMCR", "SPMCSR", "NVM_CMD", "NVM_CTRLA", "NVM_CTRLB", "NVM_STATUS",
+
+    # reset
+    "MCUCSR", "MCUSR", "RSTFLR", "RST_STATUS", "RST_CTRL",
+
+    # classic timer/counter
+    "TCCR0", "TCNT0", "OCR0",
+    "TCCR1A", "TCCR1B", "TCNT1L", "TCNT1H", "OCR1AL", "OCR1AH",
+    "OCR1BL", "OCR1BH", "ICR1L", "ICR1H",
+    "TCCR2", "TCNT2", "OCR2", "ASSR",
+    "TIMSK", "TIFR", "SFIOR",
 ] + [
     # USART
     reg.format(index)
     for reg, index in itertools.product(USART_REGS, USART_INDEXES)
+] + [
+    # XMEGA timer/counter (base address)
+    *TIMERS
 ]
 
 REQUIRED_IOREGS = [
@@ -44,11 +107,29 @@ REQUIRED_IOREGS = [
 CONSTANTS = [
     "RAMSTART", "RAMEND",
     "MAPPED_EEPROM_START", "MAPPED_EEPROM_END",
-    "FLASHEND", "SPM_PAGESIZE",
+    "FLASHEND", "SPM_PAGESIZE", "BOOT_SECTION_START", "NRWW_START_ADDR",
     "IO_SIZE",
 
-    "__AVR_ARCH__", "__AVR_XMEGA__", "__AVR_MEGA__",
-    "__AVR_2_BYTE_PC__", "__AVR_3_BYTE_PC__"
+    "__AVR_ARCH__", "__AVR_XMEGA__", "__AVR_MEGA__", "__AVR_TINY__",
+    "__AVR_TINY_PM_BASE_ADDRESS__",
+    "__AVR_2_BYTE_PC__", "__AVR_3_BYTE_PC__",
+
+    # sleep enable and sleep mode bits of MCUCR or SMCR
+    "SE", "SM0", "SM1", "SM2",
+
+    "FUSE_WDTON", "FUSE_WDLOCK",
+
+    *CLASSIC_TIMER_VECTORS,
+    *EXTERNAL_INTERRUPT_VECTORS
+] + [
+    vector.format(port)
+    for vector, port in itertools.product(PORT_VECTORS, PORTS)
+] + [
+    vector.format(timer)
+    for vector, timer in itertools.product(TIMER_VECTORS, TIMERS)
+] + [
+    vector.format(index)
+    for vector, index in itertools.product(USART_VECTORS, USART_INDEXES)
 ]
 
 REQUIRED_CONSTANTS = [
@@ -0,0 +1,433 @@
+use instruction_set::Instruction;
+use instruction_set::RegIncDec;
+use core::DataMemoryType;
+
+
+/// AVR architecture of avr-gcc (`__AVR_ARCH__`)
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum AvrArch {
+    Avr1,
+    Avr2,
+    Avr25,
+    Avr3,
+    Avr31,
+    Avr35,
+    Avr4,
+    Avr5,
+    Avr51,
+    Avr6,
+    Xmega2,
+    Xmega3,
+    Xmega4,
+    Xmega5,
+    Xmega6,
+    Xmega7,
+    AvrTiny,
+}
+
+/// instruction groups available on an architecture
+///
+/// Follows the ISA flags of binutils.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct InstrSet {
+    /// LPM
+    pub lpm: bool,
+    /// LD/ST with X and Y, pre-decrement and post-increment, PUSH, POP, IJMP and ICALL
+    pub sram: bool,
+    /// LPM Rd, Z and LPM Rd, Z+
+    pub lpmx: bool,
+    /// JMP and CALL
+    pub jmp_call: bool,
+    /// MUL, MULS, MULSU, FMUL, FMULS and FMULSU
+    pub mul: bool,
+    pub movw: bool,
+    /// ELPM
+    pub elpm: bool,
+    /// ELPM Rd, Z and ELPM Rd, Z+
+    pub elpmx: bool,
+    pub spm: bool,
+    /// SPM Z+
+    pub spmx: bool,
+    pub brk: bool,
+    /// EIJMP and EICALL
+    pub eind: bool,
+    pub des: bool,
+    /// XCH, LAS, LAC and LAT
+    pub rmw: bool,
+    /// reduced core: only R16-R31, 16-bit LDS/STS, no ADIW, SBIW, LDD and STD
+    pub tiny: bool,
+}
+
+/// cycles of instructions with architecture dependent timing
+///
+/// All values are total cycles from the AVR Instruction Set Manual without
+/// the wait states of the accessed data memory region (see `MemoryTiming`).
+#[derive(Clone, Debug, PartialEq)]
+pub struct CycleTable {
+    /// LD
+    pub ld: u64,
+    /// LD with post-increment
+    pub ld_inc: u64,
+    /// LD with pre-decrement
+    pub ld_dec: u64,
+    pub ldd: u64,
+    pub lds: u64,
+    /// ST
+    pub st: u64,
+    /// ST with post-increment
+    pub st_inc: u64,
+    /// ST with pre-decrement
+    pub st_dec: u64,
+    pub std: u64,
+    pub sts: u64,
+    pub push: u64,
+    /// SBI and CBI
+    pub sbi_cbi: u64,
+    /// SBIC and SBIS without skip
+    pub sbic_sbis: u64,
+    /// RCALL, ICALL and EICALL
+    pub rcall: u64,
+    pub call: u64,
+    /// RET and RETI
+    pub ret: u64,
+    /// XCH, LAS, LAC and LAT
+    pub rmw: u64,
+    /// interrupt response
+    pub interrupt: u64,
+    /// additional cycles of interrupt response when waking up from sleep
+    pub wake_up: u64,
+}
+
+/// AVRe core with 16-bit PC
+const AVRE_16: CycleTable = CycleTable {
+    ld: 2, ld_inc: 2, ld_dec: 3, ldd: 2, lds: 2,
+    st: 2, st_inc: 2, st_dec: 2, std: 2, sts: 2,
+    push: 2, sbi_cbi: 2, sbic_sbis: 1,
+    rcall: 3, call: 4, ret: 4, rmw: 2,
+    interrupt: 4, wake_up: 4,
+};
+
+/// AVRe core with 22-bit PC
+const AVRE_22: CycleTable = CycleTable {
+    rcall: 4, call: 5, ret: 5, interrupt: 5,
+    ..AVRE_16
+};
+
+/// AVRxm core (XMEGA) with 16-bit PC
+const AVRXM_16: CycleTable = CycleTable {
+    ld: 1, ld_inc: 1, ld_dec: 2, ldd: 2, lds: 2,
+    st: 1, st_inc: 1, st_dec: 2, std: 2, sts: 2,
+    push: 1, sbi_cbi: 1, sbic_sbis: 2,
+    rcall: 2, call: 3, ret: 4, rmw: 2,
+    interrupt: 5, wake_up: 5,
+};
+
+/// AVRxm core (XMEGA) with 22-bit PC
+const AVRXM_22: CycleTable = CycleTable {
+    rcall: 3, call: 4, ret: 5,
+    ..AVRXM_16
+};
+
+/// AVRxt core (tinyAVR 0/1/2-series, megaAVR 0-series)
+const AVRXT: CycleTable = CycleTable {
+    ld: 2, ld_inc: 2, ld_dec: 2, ldd: 2, lds: 3,
+    st: 1, st_inc: 1, st_dec: 1, std: 1, sts: 2,
+    sbic_sbis: 1,
+    ..AVRXM_16
+};
+
+/// AVRrc core (reduced core tinyAVR)
+const AVRRC: CycleTable = CycleTable {
+    ld: 1, ld_inc: 2, ld_dec: 2, ldd: 2, lds: 1,
+    st: 1, st_inc: 1, st_dec: 2, std: 1, sts: 1,
+    push: 1, sbi_cbi: 1, sbic_sbis: 1,
+    rcall: 3, call: 4, ret: 6, rmw: 2,
+    interrupt: 4, wake_up: 4,
+};
+
+/// additional cycles of data memory accesses to each region
+#[derive(Clone, Debug, PartialEq)]
+pub struct RegionCycles {
+    pub sram: u64,
+    /// external SRAM
+    pub xram: u64,
+    /// memory mapped EEPROM
+    pub eeprom: u64,
+    /// flash mapped into data space
+    pub flash: u64,
+    pub io: u64,
+}
+
+/// wait states of LD, LDD, LDS (load) and ST, STD, STS (store)
+///
+/// Defaults are derived from the architecture and can be changed for
+/// configurable wait states like the external memory interface.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MemoryTiming {
+    pub load: RegionCycles,
+    pub store: RegionCycles,
+}
+
+const NO_WAIT_STATES: RegionCycles = RegionCycles { sram: 0, xram: 0, eeprom: 0, flash: 0, io: 0 };
+
+/// external SRAM takes one additional cycle
+const AVRE_WAIT_STATES: RegionCycles = RegionCycles { xram: 1, ..NO_WAIT_STATES };
+
+/// loads from internal and external SRAM and EEPROM take one additional cycle
+const AVRXM_LOAD_WAIT_STATES: RegionCycles = RegionCycles {
+    sram: 1, xram: 1, eeprom: 1, ..NO_WAIT_STATES
+};
+
+/// loads from flash take one additional cycle
+const AVRRC_LOAD_WAIT_STATES: RegionCycles = RegionCycles { flash: 1, ..NO_WAIT_STATES };
+
+impl RegionCycles {
+    /// additional cycles of an access to `region`
+    pub fn of(&self, region: DataMemoryType) -> u64 {
+        match region {
+            DataMemoryType::SRam => self.sram,
+            DataMemoryType::XRam => self.xram,
+            DataMemoryType::Eeprom => self.eeprom,
+            DataMemoryType::Flash => self.flash,
+            DataMemoryType::Io => self.io,
+            DataMemoryType::Register | DataMemoryType::Reserved => 0,
+        }
+    }
+}
+
+const AVR1: InstrSet = InstrSet {
+    lpm: true, sram: false, lpmx: false, jmp_call: false, mul: false, movw: false,
+    elpm: false, elpmx: false, spm: false, spmx: false, brk: false, eind: false,
+    des: false, rmw: false, tiny: false,
+};
+
+const AVR2: InstrSet = InstrSet { sram: true, ..AVR1 };
+
+const AVR25: InstrSet = InstrSet { lpmx: true, movw: true, spm: true, brk: true, ..AVR2 };
+
+const AVR3: InstrSet = InstrSet { jmp_call: true, ..AVR2 };
+
+const AVR31: InstrSet = InstrSet { elpm: true, ..AVR3 };
+
+const AVR35: InstrSet = InstrSet { lpmx: true, movw: true, spm: true, brk: true, ..AVR3 };
+
+const AVR4: InstrSet = InstrSet { mul: true, ..AVR25 };
+
+const AVR5: InstrSet = InstrSet { jmp_call: true, ..AVR4 };
+
+const AVR51: InstrSet = InstrSet { elpm: true, elpmx: true, ..AVR5 };
+
+const AVR6: InstrSet = InstrSet { eind: true, ..AVR51 };
+
+const XMEGA2: InstrSet = InstrSet { spmx: true, des: true, rmw: true, ..AVR5 };
+
+const XMEGA3: InstrSet = InstrSet { spmx: true, ..AVR5 };
+
+const XMEGA4: InstrSet = InstrSet { elpm: true, elpmx: true, ..XMEGA2 };
+
+const XMEGA6: InstrSet = InstrSet { eind: true, ..XMEGA4 };
+
+const AVRTINY: InstrSet = InstrSet { lpm: false, sram: true, brk: true, tiny: true, ..AVR1 };
+
+/// all instructions of all architectures without reduced core
+pub const ALL_INSTRUCTIONS: InstrSet = InstrSet {
+    lpm: true, sram: true, lpmx: true, jmp_call: true, mul: true, movw: true,
+    elpm: true, elpmx: true, spm: true, spmx: true, brk: true, eind: true,
+    des: true, rmw: true, tiny: false,
+};
+
+impl AvrArch {
+    /// architecture with number `arch` of `__AVR_ARCH__`
+    pub fn from_arch_number(arch: usize) -> Option<AvrArch> {
+        use self::AvrArch::*;
+
+        match arch {
+            1 => Some(Avr1),
+            2 => Some(Avr2),
+            25 => Some(Avr25),
+            3 => Some(Avr3),
+            31 => Some(Avr31),
+            35 => Some(Avr35),
+            4 => Some(Avr4),
+            5 => Some(Avr5),
+            51 => Some(Avr51),
+            6 => Some(Avr6),
+            100 => Some(AvrTiny),
+            102 => Some(Xmega2),
+            103 => Some(Xmega3),
+            104 => Some(Xmega4),
+            105 => Some(Xmega5),
+            106 => Some(Xmega6),
+            107 => Some(Xmega7),
+            _ => None,
+        }
+    }
+
+    pub fn instr_set(self) -> InstrSet {
+        use self::AvrArch::*;
+
+        match self {
+            Avr1 => AVR1,
+            Avr2 => AVR2,
+            Avr25 => AVR25,
+            Avr3 => AVR3,
+            Avr31 => AVR31,
+            Avr35 => AVR35,
+            Avr4 => AVR4,
+            Avr5 => AVR5,
+            Avr51 => AVR51,
+            Avr6 => AVR6,
+            Xmega2 => XMEGA2,
+            Xmega3 => XMEGA3,
+            Xmega4 | Xmega5 => XMEGA4,
+            Xmega6 | Xmega7 => XMEGA6,
+            AvrTiny => AVRTINY,
+        }
+    }
+
+    pub fn memory_timing(self) -> MemoryTiming {
+        use self::AvrArch::*;
+
+        match self {
+            Xmega2 | Xmega4 | Xmega5 | Xmega6 | Xmega7 => MemoryTiming {
+                load: AVRXM_LOAD_WAIT_STATES,
+                store: NO_WAIT_STATES,
+            },
+            Xmega3 => MemoryTiming { load: NO_WAIT_STATES, store: NO_WAIT_STATES },
+            AvrTiny => MemoryTiming { load: AVRRC_LOAD_WAIT_STATES, store: NO_WAIT_STATES },
+            _ => MemoryTiming { load: AVRE_WAIT_STATES, store: AVRE_WAIT_STATES },
+        }
+    }
+
+    pub fn cycles(self) -> &'static CycleTable {
+        use self::AvrArch::*;
+
+        match self {
+            Avr6 => &AVRE_22,
+            Xmega2 | Xmega4 | Xmega5 => &AVRXM_16,
+            Xmega6 | Xmega7 => &AVRXM_22,
+            Xmega3 => &AVRXT,
+            AvrTiny => &AVRRC,
+            _ => &AVRE_16,
+        }
+    }
+}
+
+impl InstrSet {
+    /// `instr` is available
+    pub fn supports(&self, instr: &Instruction) -> bool {
+        use instruction_set::Instruction::*;
+
+        let available = match *instr {
+            LdZ { zop, .. } => zop == RegIncDec::Unchanged || self.sram,
+            StZ { zop, .. } => zop == RegIncDec::Unchanged || self.sram,
+            LdX { .. } | LdY { .. } | StX { .. } | StY { .. } | LddY { .. } | LddZ { .. }
+            | StdY { .. } | StdZ { .. } | Lds16 { .. } | Sts16 { .. } | Push { .. } | Pop { .. }
+            | Adiw { .. } | Sbiw { .. } | Ijmp | Icall => self.sram,
+            Lds { .. } | Sts { .. } => self.tiny,
+            Lpm0 => self.lpm,
+            Lpm { .. } | LpmInc { .. } => self.lpmx,
+            Jmp { .. } | Call { .. } => self.jmp_call,
+            Mul { .. } | Muls { .. } | Mulsu { .. }
+            | Fmul { .. } | Fmuls { .. } | Fmulsu { .. } => self.mul,
+            Movw { .. } => self.movw,
+            Elpm0 => self.elpm,
+            Elpm { .. } | ElpmInc { .. } => self.elpmx,
+            Spm => self.spm,
+            SpmInc => self.spmx,
+            Break => self.brk,
+            Eijmp | Eicall => self.eind,
+            Des { .. } => self.des,
+            Xch { .. } | Las { .. } | Lac { .. } | Lat { .. } => self.rmw,
+            _ => true,
+        };
+
+        available && (!self.tiny || is_reduced_core_instr(instr))
+    }
+}
+
+/// instruction is available on the reduced core of tinyAVR devices (AVRrc)
+fn is_reduced_core_instr(instr: &Instruction) -> bool {
+    use instruction_set::Instruction::*;
+
+    match *instr {
+        Adiw { .. } | Sbiw { .. } | Lds16 { .. } | Sts16 { .. } => false,
+
+        LddY { .. } | LddZ { .. } | StdY { .. } | StdZ { .. } => false,
+
+        Adc { d, r } | Add { d, r } | And { d, r } | Cp { d, r } | Cpc { d, r }
+        | Cpse { d, r } | Eor { d, r } | Mov { d, r } | Or { d, r } | Sbc { d, r }
+        | Sub { d, r } => d >= 16 && r >= 16,
+
+        Asr { d } | Com { d } | Dec { d } | Inc { d } | Lsr { d } | Neg { d }
+        | Ror { d } | Swap { d } | Bld { d, .. } | Bst { d, .. } | In { d, .. }
+        | LdX { d, .. } | LdY { d, .. } | LdZ { d, .. } => d >= 16,
+
+        Out { r, .. } | Pop { r } | Push { r } | Sbrc { r, .. } | Sbrs { r, .. }
+        | StX { r, .. } | StY { r, .. } | StZ { r, .. } => r >= 16,
+
+        _ => true,
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use instruction_set::Instruction::*;
+
+    #[test]
+    fn arch_numbers() {
+        assert_eq!(AvrArch::from_arch_number(25), Some(AvrArch::Avr25));
+        assert_eq!(AvrArch::from_arch_number(51), Some(AvrArch::Avr51));
+        assert_eq!(AvrArch::from_arch_number(100), Some(AvrArch::AvrTiny));
+        assert_eq!(AvrArch::from_arch_number(107), Some(AvrArch::Xmega7));
+        assert_eq!(AvrArch::from_arch_number(101), None);
+    }
+
+    #[test]
+    fn availability() {
+        let mul = Mul { d: 16, r: 17 };
+        assert!(!AvrArch::Avr25.instr_set().supports(&mul));
+        assert!(AvrArch::Avr4.instr_set().supports(&mul));
+        assert!(!AvrArch::AvrTiny.instr_set().supports(&mul));
+
+        let jmp = Jmp { k: 0 };
+        assert!(!AvrArch::Avr4.instr_set().supports(&jmp));
+        assert!(AvrArch::Avr3.instr_set().supports(&jmp));
+
+        let elpm = Elpm { d: 0 };
+        assert!(!AvrArch::Xmega2.instr_set().supports(&elpm));
+        assert!(AvrArch::Xmega4.instr_set().supports(&elpm));
+        assert!(!AvrArch::Avr31.instr_set().supports(&elpm));
+        assert!(AvrArch::Avr31.instr_set().supports(&Elpm0));
+
+        assert!(AvrArch::Xmega2.instr_set().supports(&Des { k: 0 }));
+        assert!(!AvrArch::Xmega3.instr_set().supports(&Des { k: 0 }));
+        assert!(!AvrArch::Avr6.instr_set().supports(&Xch { d: 16 }));
+        assert!(AvrArch::Avr6.instr_set().supports(&Eicall));
+
+        let ldx = LdX { d: 16, xop: RegIncDec::Inc };
+        assert!(!AvrArch::Avr1.instr_set().supports(&ldx));
+        assert!(AvrArch::Avr1.instr_set().supports(&LdZ { d: 0, zop: RegIncDec::Unchanged }));
+        assert!(AvrArch::AvrTiny.instr_set().supports(&ldx));
+        assert!(!AvrArch::AvrTiny.instr_set().supports(&LdX { d: 15, xop: RegIncDec::Inc }));
+        assert!(AvrArch::AvrTiny.instr_set().supports(&Lds { d: 16, k: 0x40 }));
+        assert!(!AvrArch::Avr5.instr_set().supports(&Lds { d: 16, k: 0x40 }));
+    }
+
+    #[test]
+    fn cycles() {
+        assert_eq!(AvrArch::Avr5.cycles().push, 2);
+        assert_eq!(AvrArch::Xmega2.cycles().push, 1);
+
+        assert_eq!(AvrArch::Avr5.cycles().call, 4);
+        assert_eq!(AvrArch::Avr6.cycles().call, 5);
+        assert_eq!(AvrArch::Xmega4.cycles().call, 3);
+        assert_eq!(AvrArch::Xmega7.cycles().call, 4);
+
+        assert_eq!(AvrArch::AvrTiny.cycles().ret, 6);
+        assert_eq!(AvrArch::Xmega3.cycles().sbic_sbis, 1);
+        assert_eq!(AvrArch::Xmega2.cycles().sbic_sbis, 2);
+    }
+}
@@ -19,6 +19,8 @@ pub fn u32le(a: u8, b: u8, c: u8, d: u8) -> u32 {
     (d as u32) << 24 | (c as u32) << 16 | (b as u32) << 8 | (a as u32)
 }
 
+pub fn read_u32le(bytes: &[u8]) -> u32 { u32le(bytes[0], bytes[1], bytes[2], bytes[3]) }
+
 pub fn u32be(a: u8, b: u8, c: u8, d: u8) -> u32 {
     (a as u32) << 24 | (b as u32) << 16 | (c as u32) << 8 | (d as u32)
 }
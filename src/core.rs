@@ -1,9 +1,12 @@
 use decoder::Decoder;
 use decoder::AvrDecoderCache;
+use arch::AvrArch;
+use arch::MemoryTiming;
 use debug::AvrDebugger;
 use byte_convert::u32be;
 use byte_convert::u16be;
 use std::ops::Range;
+use std::cmp;
 use byte_convert::u16le;
 use byte_convert::u8bits;
 use byte_convert::read_u16le;
@@ -11,6 +14,18 @@ use std::collections::HashMap;
 use models::register_service::McuIoRegistersService;
 use models::register_service::IoRegAddrs;
 use byte_convert::u32le;
+use interrupts::InterruptRequests;
+use interrupts::InterruptController;
+use interrupts::FixedPriorityController;
+use models::sleep::SleepMode;
+use models::sleep::ClockDomain;
+use models::nvm::Nvm;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::cell::Cell;
+use executable::ElfFile;
+use executable::elf_data_address;
+use fuses::Fuses;
 
 /// Signals send by cpu
 #[derive(Copy, Clone, Debug, PartialEq)]
@@ -23,6 +38,77 @@ pub enum CpuSignal {
 
     /// execution of break instruction
     Break,
+
+    /// cpu sleeps and no interrupt can wake it up
+    Sleeping,
+
+    /// access to reserved data memory with `MemoryFaultPolicy::Signal`
+    MemoryFault { addr: usize, write: bool },
+
+    /// push below the limit of the stack guard or, with a guard, below
+    /// address 0 (`pc` in words)
+    StackOverflow { pc: usize, sp: usize },
+
+    /// pop above the top of the stack guard or, with a guard, above
+    /// address 0xFFFF (`pc` in words)
+    StackUnderflow { pc: usize, sp: usize },
+}
+
+/// cause of a reset
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ResetSource {
+    PowerOn,
+
+    /// RESET pin
+    External,
+
+    Watchdog,
+
+    BrownOut,
+
+    /// SWRST in RST_CTRL (XMEGA)
+    Software,
+}
+
+/// bounds of the stack checked by pushes and pops
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct StackGuard {
+    /// lowest data space address the stack may grow to
+    pub limit: usize,
+
+    /// highest data space address of the stack
+    pub top: usize,
+}
+
+impl StackGuard {
+    pub fn new(limit: usize, top: usize) -> StackGuard {
+        StackGuard { limit, top }
+    }
+
+    /// stack between the end of static data (`__heap_start` or `_end`) and `__stack`
+    pub fn from_elf(elf: &ElfFile) -> Option<StackGuard> {
+        let limit = elf.symbol("__heap_start").or_else(|| elf.symbol("_end"))?;
+        let top = elf.symbol("__stack")?;
+        Some(StackGuard::new(elf_data_address(limit), elf_data_address(top)))
+    }
+}
+
+/// SP is a 16-bit register and wraps around
+const SP_MASK: usize = 0xFFFF;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum StackFault {
+    Overflow, Underflow
+}
+
+/// handling of data memory accesses to reserved addresses
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum MemoryFaultPolicy {
+    /// stop execution with `CpuSignal::MemoryFault` after the instruction
+    Signal,
+
+    /// log access and continue, reads return 0 and writes are ignored
+    Log,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -30,13 +116,23 @@ pub enum AccessError {
     ReadError(usize), WriteError(usize),
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum DataMemoryType {
-    SRam, XRam, Eeprom, Io, Register, Reserved
+    SRam, XRam, Eeprom, Flash, Io, Register, Reserved
 }
 
 pub type IoReadFunc = Box<Fn(&AvrCoreState, usize, bool) -> u8 + Send + Sync>;
 pub type IoWriteFunc = Box<Fn(&mut AvrCoreState, usize, u8) + Send + Sync>;
 
+/// brings a peripheral up to `core.cycles` and returns the cycle count of its next event
+pub type ClockFunc = Box<Fn(&mut AvrCoreState) -> Option<u64> + Send + Sync>;
+
+/// restores reset values of a peripheral
+pub type ResetFunc = Box<Fn(&mut AvrCoreState, ResetSource) + Send + Sync>;
+
+/// called when the cpu jumps to an interrupt vector, e.g. to clear its flag
+pub type InterruptAckFunc = Box<Fn(&mut AvrCoreState, usize) + Send + Sync>;
+
 pub struct AvrCoreState {
     /// cycle counter
     pub cycles: u64,
@@ -53,6 +149,21 @@ pub struct AvrCoreState {
     /// interrupt flag (I)
     pub interrupt: bool,
 
+    /// pending interrupt requests
+    pub irq: InterruptRequests,
+
+    /// delay interrupt dispatch for one instruction (after SEI and RETI)
+    pub irq_delay: bool,
+
+    /// sleep mode entered with SLEEP or `None` if cpu is running
+    pub sleep: Option<SleepMode>,
+
+    /// cycle count at the last watchdog reset (WDR)
+    pub watchdog: u64,
+
+    /// cycles the I/O and the asynchronous clock were stopped by sleep modes
+    stopped_cycles: [u64; 2],
+
     pub t: bool,
 
     pub h: bool,
@@ -75,6 +186,21 @@ pub struct AvrCoreState {
     pub rampy: u8,
     pub rampz: u8,
 
+    /// valid bits of X, Y and Z pointers in data space including RAMPX/Y/Z
+    ///
+    /// 8 bits for data spaces up to 256 bytes, otherwise 16 bits and the
+    /// RAMP bits needed to address data memory beyond 64 KiB.
+    pub pointer_mask: u32,
+
+    /// extended indirect register for EIJMP and EICALL
+    pub eind: u8,
+
+    /// cycle count after the last DES instruction
+    pub des_end: Option<u64>,
+
+    /// reset requested by a peripheral, executed after the current instruction
+    pub reset_request: Option<ResetSource>,
+
     /// flash bytes
     /// TODO: write through write_flash
     pub flash: Vec<u8>,
@@ -87,6 +213,24 @@ pub struct AvrCoreState {
 }
 
 impl AvrCoreState {
+    /// cycle count of clock `domain`, without the cycles it was stopped
+    pub fn clock_cycles(&self, domain: ClockDomain) -> u64 {
+        self.cycles - self.stopped_cycles(domain)
+    }
+
+    /// cpu cycle count at which clock `domain` reaches `cycles` if kept running
+    pub fn cpu_cycles(&self, domain: ClockDomain, cycles: u64) -> u64 {
+        cycles + self.stopped_cycles(domain)
+    }
+
+    fn stopped_cycles(&self, domain: ClockDomain) -> u64 {
+        match domain {
+            ClockDomain::Io => self.stopped_cycles[0],
+            ClockDomain::Asynchronous => self.stopped_cycles[1],
+            ClockDomain::Always => 0,
+        }
+    }
+
     pub fn read_reg(&self, addr: u8) -> u8 { self.regs[addr as usize] }
     pub fn write_reg(&mut self, addr: u8, data: u8) -> () { self.regs[addr as usize] = data; }
 
@@ -104,16 +248,35 @@ impl AvrCoreState {
     pub fn read_y(&self) -> u16 { u16le(self.read_reg(28), self.read_reg(29)) }
     pub fn read_z(&self) -> u16 { u16le(self.read_reg(30), self.read_reg(31)) }
 
+    /// data space address of X
     pub fn read_ramped_x(&self) -> u32 {
-        u32le(self.read_reg(26), self.read_reg(27), self.rampx, 0)
+        u32le(self.read_reg(26), self.read_reg(27), self.rampx, 0) & self.pointer_mask
     }
+    /// data space address of Y
     pub fn read_ramped_y(&self) -> u32 {
-        u32le(self.read_reg(28), self.read_reg(29), self.rampy, 0)
+        u32le(self.read_reg(28), self.read_reg(29), self.rampy, 0) & self.pointer_mask
     }
+    /// data space address of Z
     pub fn read_ramped_z(&self) -> u32 {
+        u32le(self.read_reg(30), self.read_reg(31), self.rampz, 0) & self.pointer_mask
+    }
+
+    /// program memory address RAMPZ:Z of ELPM and SPM
+    pub fn read_extended_z(&self) -> u32 {
         u32le(self.read_reg(30), self.read_reg(31), self.rampz, 0)
     }
 
+    /// `value` wrapped around to the pointer width
+    pub fn wrap_pointer(&self, value: u32) -> u32 {
+        value & self.pointer_mask
+    }
+
+    /// merge data space address `value` into `ramp:high:low` of a pointer
+    fn merge_pointer(&self, low: u8, high: u8, ramp: u8, value: u32) -> u32 {
+        let old = u32le(low, high, ramp, 0);
+        (old & !self.pointer_mask) | (value & self.pointer_mask)
+    }
+
     pub fn write_x(&mut self, value: u16) {
         self.write_reg(26, (value & 0xFF) as u8);
         self.write_reg(27, (value >> 8) as u8);
@@ -129,17 +292,29 @@ impl AvrCoreState {
         self.write_reg(31, (value >> 8) as u8);
     }
 
+    /// set data space address of X, bits outside of the pointer width are kept
     pub fn write_ramped_x(&mut self, value: u32) {
+        let value = self.merge_pointer(self.read_reg(26), self.read_reg(27), self.rampx, value);
         self.write_x((value & 0xFFFF) as u16);
         self.rampx = ((value >> 16) & 0xFF) as u8;
     }
 
+    /// set data space address of Y, bits outside of the pointer width are kept
     pub fn write_ramped_y(&mut self, value: u32) {
+        let value = self.merge_pointer(self.read_reg(28), self.read_reg(29), self.rampy, value);
         self.write_y((value & 0xFFFF) as u16);
         self.rampy = ((value >> 16) & 0xFF) as u8;
     }
 
+    /// set data space address of Z, bits outside of the pointer width are kept
     pub fn write_ramped_z(&mut self, value: u32) {
+        let value = self.merge_pointer(self.read_reg(30), self.read_reg(31), self.rampz, value);
+        self.write_z((value & 0xFFFF) as u16);
+        self.rampz = ((value >> 16) & 0xFF) as u8;
+    }
+
+    /// set program memory address RAMPZ:Z of ELPM and SPM
+    pub fn write_extended_z(&mut self, value: u32) {
         self.write_z((value & 0xFFFF) as u16);
         self.rampz = ((value >> 16) & 0xFF) as u8;
     }
@@ -161,8 +336,13 @@ impl AvrCoreState {
         self.interrupt = value & (1 << 7) != 0;
     }
 
+    /// data space address RAMPD:`addr` of LDS and STS
     pub fn ramped_addr(&self, addr: u16) -> usize {
-        addr as usize + ((self.rampd as usize) << 16)
+        if self.pointer_mask > 0xFFFF {
+            addr as usize + ((self.rampd as usize) << 16)
+        } else {
+            addr as usize
+        }
     }
 
 }
@@ -179,6 +359,29 @@ pub struct AvrVm {
     io_regs_r: HashMap<usize, IoReadFunc>,
     io_reg_state: Vec<u8>,
 
+    interrupt_controller: Box<InterruptController + Send + Sync>,
+
+    clocks: Vec<(ClockDomain, ClockFunc)>,
+
+    resets: Vec<ResetFunc>,
+
+    interrupt_acks: HashMap<usize, InterruptAckFunc>,
+
+    /// NVM controller used by SPM
+    pub nvm: Option<Arc<Mutex<Nvm>>>,
+
+    /// handling of accesses to reserved data memory
+    pub memory_fault_policy: MemoryFaultPolicy,
+
+    /// first memory fault of the current step
+    memory_fault: Cell<Option<CpuSignal>>,
+
+    /// stack bounds or `None` to disable stack checks
+    pub stack_guard: Option<StackGuard>,
+
+    /// first stack guard violation of the current step with SP
+    stack_fault: Option<(StackFault, usize)>,
+
     pub decoder: AvrDecoderCache
 }
 
@@ -188,9 +391,14 @@ impl AvrVm {
         let info = info.clone();
         let mut result = AvrVm {
             core: AvrCoreState {
-                pc: 0,
-                sp: 0,
+                pc: info.reset_vector(),
+                sp: reset_sp(&info),
                 interrupt: false,
+                irq: InterruptRequests::new(),
+                irq_delay: false,
+                sleep: None,
+                watchdog: 0,
+                stopped_cycles: [0; 2],
                 t: false,
                 h: false,
                 sign: false,
@@ -204,6 +412,10 @@ impl AvrVm {
                 rampx: 0,
                 rampy: 0,
                 rampz: 0,
+                pointer_mask: info.pointer_mask(),
+                eind: 0,
+                des_end: None,
+                reset_request: None,
 
                 flash: Vec::new(),
 
@@ -219,67 +431,113 @@ impl AvrVm {
             io_regs_r: HashMap::new(),
             io_reg_state: vec![0u8; info.ios],
 
+            interrupt_controller: Box::new(FixedPriorityController),
+            clocks: Vec::new(),
+            resets: Vec::new(),
+            interrupt_acks: HashMap::new(),
+            nvm: None,
+            memory_fault_policy: MemoryFaultPolicy::Log,
+            memory_fault: Cell::new(None),
+            stack_guard: None,
+            stack_fault: None,
+
             debugger: AvrDebugger::new(),
-            decoder: AvrDecoderCache::new()
+            decoder: AvrDecoderCache::for_arch(info.arch)
         };
         result.core.flash.resize(info.flash_bytes, 0);
         result
     }
 
     pub fn push3(&mut self, v: u32) {
-        self.core.sp -= 3;
-
-        let sp = self.core.sp;
-        self.write_unchecked(sp + 1, (v >> 16) as u8);
-        self.write_unchecked(sp + 2, (v >> 8) as u8);
-        self.write_unchecked(sp + 3, v as u8);
+        let sp = self.push_sp(3);
+        self.write_unchecked((sp + 1) & SP_MASK, (v >> 16) as u8);
+        self.write_unchecked((sp + 2) & SP_MASK, (v >> 8) as u8);
+        self.write_unchecked((sp + 3) & SP_MASK, v as u8);
     }
 
     pub fn push2(&mut self, v: u16) {
-        self.core.sp -= 2;
-
-        let sp = self.core.sp;
-        self.write_unchecked(sp + 1, (v >> 8) as u8);
-        self.write_unchecked(sp + 2, v as u8);
+        let sp = self.push_sp(2);
+        self.write_unchecked((sp + 1) & SP_MASK, (v >> 8) as u8);
+        self.write_unchecked((sp + 2) & SP_MASK, v as u8);
     }
 
     pub fn push(&mut self, v: u8) {
-        self.core.sp -= 1;
-        let sp = self.core.sp;
-        self.write_unchecked(sp + 1, v);
+        let sp = self.push_sp(1);
+        self.write_unchecked((sp + 1) & SP_MASK, v);
     }
 
     pub fn pop3(&mut self) -> u32 {
-        self.core.sp += 3;
-
-        let sp = self.core.sp;
+        let sp = self.pop_sp(3);
         u32be(
             0,
-            self.read_unchecked(sp - 2, false),
-            self.read_unchecked(sp - 1, false),
-            self.read_unchecked(sp + 0, false),
+            self.read_unchecked((sp + 1) & SP_MASK, false),
+            self.read_unchecked((sp + 2) & SP_MASK, false),
+            self.read_unchecked((sp + 3) & SP_MASK, false),
         )
     }
 
     pub fn pop2(&mut self) -> u16 {
-        self.core.sp += 2;
-
-        let sp = self.core.sp;
+        let sp = self.pop_sp(2);
         u16be(
-            self.read_unchecked(sp - 1, false),
-            self.read_unchecked(sp + 0, false),
+            self.read_unchecked((sp + 1) & SP_MASK, false),
+            self.read_unchecked((sp + 2) & SP_MASK, false),
         )
     }
 
     pub fn pop(&mut self) -> u8 {
-        self.core.sp += 1;
+        let sp = self.pop_sp(1);
+        self.read_unchecked((sp + 1) & SP_MASK, false)
+    }
 
-        self.read_unchecked(self.core.sp, false)
+    /// decrement SP by `bytes` and return the new SP
+    ///
+    /// The lowest pushed byte at SP + 1 must be within the stack guard, a
+    /// wrap below address 0 is an overflow if a guard is set and logged
+    /// otherwise.
+    fn push_sp(&mut self, bytes: usize) -> usize {
+        let old_sp = self.core.sp;
+        let sp = old_sp.wrapping_sub(bytes) & SP_MASK;
+        self.core.sp = sp;
+
+        let wrapped = old_sp < bytes;
+        match self.stack_guard {
+            Some(guard) if wrapped || sp + 1 < guard.limit => {
+                self.stack_fault.get_or_insert((StackFault::Overflow, sp));
+            }
+            None if wrapped => warn!(
+                target: "avrvc::core",
+                "SP wrapped below 0x0000 at PC 0x{:x}", self.core.pc),
+            _ => {}
+        }
+        sp
+    }
+
+    /// increment SP by `bytes` and return the old SP
+    ///
+    /// The highest popped byte at the new SP must be within the stack guard,
+    /// a wrap above address 0xFFFF is an underflow if a guard is set and
+    /// logged otherwise.
+    fn pop_sp(&mut self, bytes: usize) -> usize {
+        let old_sp = self.core.sp;
+        let sp = (old_sp + bytes) & SP_MASK;
+        self.core.sp = sp;
+
+        let wrapped = old_sp + bytes > SP_MASK;
+        match self.stack_guard {
+            Some(guard) if wrapped || sp > guard.top => {
+                self.stack_fault.get_or_insert((StackFault::Underflow, sp));
+            }
+            None if wrapped => warn!(
+                target: "avrvc::core",
+                "SP wrapped above 0xFFFF at PC 0x{:x}", self.core.pc),
+            _ => {}
+        }
+        old_sp
     }
 
     pub fn write_flash(&mut self, addr: usize, data: &[u8]) { // TODO: Result<(), WriteError>
         self.core.flash[addr..addr+data.len()].copy_from_slice(&data);
-        self.decoder.refresh(&self.core.flash);
+        self.decoder.invalidate(&self.core.flash, addr..addr+data.len());
     }
 
     pub fn register_io(&mut self, addr: usize, read_func: IoReadFunc, write_func: IoWriteFunc) {
@@ -287,6 +545,34 @@ impl AvrVm {
         self.register_io_write(addr, write_func);
     }
 
+    /// register clock of a peripheral
+    ///
+    /// Clocks are run after each instruction and drive the vm while sleeping.
+    /// Clocks of a `domain` stopped by the current sleep mode are skipped.
+    pub fn register_clock(&mut self, domain: ClockDomain, func: ClockFunc) {
+        self.clocks.push((domain, func));
+    }
+
+    /// run all running clocks and return the cycle count of the next event
+    fn run_clocks(&mut self) -> Option<u64> {
+        let core = &mut self.core;
+        let sleep = core.sleep;
+        self.clocks.iter()
+            .filter(|clock| clock.0.is_running(sleep))
+            .filter_map(|clock| (clock.1)(core))
+            .min()
+    }
+
+    /// register reset values of a peripheral
+    pub fn register_reset(&mut self, func: ResetFunc) {
+        self.resets.push(func);
+    }
+
+    /// register acknowledge of interrupt `vector`
+    pub fn register_interrupt_ack(&mut self, vector: usize, func: InterruptAckFunc) {
+        self.interrupt_acks.insert(vector, func);
+    }
+
     pub fn register_io_read(&mut self, addr: usize, func: IoReadFunc) {
         self.io_regs_r.insert(addr, func);
     }
@@ -302,7 +588,9 @@ impl AvrVm {
                 None => self.io_reg_state[addr]
             }
         } else {
-            debug_assert!(false, "read from reserved memory: 0x{:08x}", addr);
+            if !view {
+                self.memory_fault(addr, false);
+            }
             0
         }
     }
@@ -315,7 +603,7 @@ impl AvrVm {
             }
             self.io_reg_state[addr] = value;
         } else {
-            debug_assert!(false, "write to reserved memory: 0x{:08x}", addr);
+            self.memory_fault(addr, true);
         }
     }
 
@@ -327,8 +615,12 @@ impl AvrVm {
         } else if addr >= self.core.eeprom_offset
                 && addr < self.core.eeprom_offset + self.core.eeprom.len() {
             self.core.eeprom[addr - self.core.eeprom_offset]
+        } else if let Some(offset) = self.mapped_flash_offset(addr) {
+            self.core.flash[offset]
         } else {
-            debug_assert!(false, "read from reserved memory: 0x{:08x}", addr);
+            if !view {
+                self.memory_fault(addr, false);
+            }
             0
         }
     }
@@ -343,7 +635,23 @@ impl AvrVm {
             let offset = self.core.eeprom_offset;
             self.core.eeprom[addr - offset] = value;
         } else {
-            debug_assert!(false, "write to reserved memory: 0x{:08x}", addr);
+            self.memory_fault(addr, true);
+        }
+    }
+
+    /// data memory region of `addr`
+    pub fn region(&self, addr: usize) -> DataMemoryType {
+        if addr >= self.core.ram_offset && addr < self.core.ram_offset + self.core.ram.len() {
+            DataMemoryType::SRam
+        } else if addr < self.io_reg_state.len() {
+            DataMemoryType::Io
+        } else if addr >= self.core.eeprom_offset
+            && addr < self.core.eeprom_offset + self.core.eeprom.len() {
+            DataMemoryType::Eeprom
+        } else if self.mapped_flash_offset(addr).is_some() {
+            DataMemoryType::Flash
+        } else {
+            DataMemoryType::Reserved
         }
     }
 
@@ -355,6 +663,8 @@ impl AvrVm {
         } else if addr >= self.core.eeprom_offset
             && addr < self.core.eeprom_offset + self.core.eeprom.len() {
             Ok((self.core.eeprom[addr - self.core.eeprom_offset], DataMemoryType::Eeprom))
+        } else if let Some(offset) = self.mapped_flash_offset(addr) {
+            Ok((self.core.flash[offset], DataMemoryType::Flash))
         } else {
             Err(AccessError::ReadError(addr))
         }
@@ -372,6 +682,9 @@ impl AvrVm {
             let offset = self.core.eeprom_offset;
             self.core.eeprom[addr - offset] = value;
             Ok(DataMemoryType::Eeprom)
+        } else if self.mapped_flash_offset(addr).is_some() {
+            // mapped flash is read-only
+            Ok(DataMemoryType::Flash)
         } else {
             return Err(AccessError::WriteError(addr));
         }
@@ -382,8 +695,12 @@ impl AvrVm {
             self.core.ram[addr - self.core.ram_offset]
         } else if addr < self.io_reg_state.len() {
             self.read_io(addr, view)
+        } else if let Some(offset) = self.mapped_flash_offset(addr) {
+            self.core.flash[offset]
         } else {
-            debug_assert!(false, "read from reserved memory: 0x{:08x}", addr);
+            if !view {
+                self.memory_fault(addr, false);
+            }
             0
         }
     }
@@ -394,13 +711,74 @@ impl AvrVm {
             self.core.ram[addr - offset] = value;
         } else if addr < self.io_reg_state.len() {
             self.write_io(addr, value);
+        } else if self.mapped_flash_offset(addr).is_some() {
+            // mapped flash is read-only
         } else {
-            debug_assert!(false, "write to reserved memory: 0x{:08x}", addr);
+            self.memory_fault(addr, true);
+        }
+    }
+
+    /// report access to reserved data memory at `addr`
+    ///
+    /// Depending on `memory_fault_policy` the access is logged or the current
+    /// step ends with `CpuSignal::MemoryFault`.
+    pub fn memory_fault(&self, addr: usize, write: bool) {
+        match self.memory_fault_policy {
+            MemoryFaultPolicy::Signal => if self.memory_fault.get().is_none() {
+                self.memory_fault.set(Some(CpuSignal::MemoryFault { addr, write }));
+            },
+            MemoryFaultPolicy::Log => warn!(
+                target: "avrvc.core", "{} reserved memory: 0x{:08x} (pc: 0x{:x})",
+                if write { "write to" } else { "read from" }, addr, self.core.pc * 2),
         }
     }
 
-    pub fn reset(&mut self) {
-        self.core.pc = 0;
+    /// flash offset of data space address `addr` if flash is mapped into data space
+    fn mapped_flash_offset(&self, addr: usize) -> Option<usize> {
+        self.info.mapped_flash
+            .filter(|&start| addr >= start && addr < start + self.core.flash.len())
+            .map(|start| addr - start)
+    }
+
+    /// reset cpu, I/O registers and peripherals
+    ///
+    /// SRAM is only cleared by a power-on reset, EEPROM and flash are kept.
+    pub fn reset(&mut self, source: ResetSource) {
+        info!(target: "avrvc.core", "{:?} reset at pc 0x{:x}", source, self.core.pc * 2);
+
+        {
+            let core = &mut self.core;
+            core.pc = self.info.reset_vector();
+            core.sp = reset_sp(&self.info);
+            core.regs = [0; 32];
+            core.write_sreg(0);
+            core.irq = InterruptRequests::new();
+            core.irq_delay = false;
+            core.sleep = None;
+            core.watchdog = core.cycles;
+            core.rampd = 0;
+            core.rampx = 0;
+            core.rampy = 0;
+            core.rampz = 0;
+            core.eind = 0;
+            core.des_end = None;
+            core.reset_request = None;
+
+            if source == ResetSource::PowerOn {
+                for byte in core.ram.iter_mut() {
+                    *byte = 0;
+                }
+            }
+        }
+
+        for byte in self.io_reg_state.iter_mut() {
+            *byte = 0;
+        }
+
+        let core = &mut self.core;
+        for func in &self.resets {
+            func(core, source);
+        }
     }
 
     pub fn crash(&mut self, crash_info: CpuSignal) -> Result<(), CpuSignal> {
@@ -408,18 +786,154 @@ impl AvrVm {
         Err(crash_info)
     }
 
+    /// request interrupt `vector`
+    pub fn raise_interrupt(&mut self, vector: usize) {
+        self.core.irq.raise(vector);
+    }
+
+    pub fn set_interrupt_controller(
+        &mut self, controller: Box<InterruptController + Send + Sync>
+    ) {
+        self.interrupt_controller = controller;
+    }
+
+    /// jump to interrupt vector
+    ///
+    /// pushes the current PC and disables interrupts until RETI, on XMEGA
+    /// devices only those of the same or a lower level.
+    pub fn dispatch_interrupt(&mut self, vector: usize) {
+        let pc = self.core.pc;
+        if self.info.pc_bytes == 3 {
+            self.push3(pc as u32);
+        } else {
+            self.push2(pc as u16);
+        }
+
+        self.core.cycles += self.info.arch.cycles().interrupt;
+
+        self.interrupt_controller.acknowledge(&mut self.core, vector);
+        self.core.irq.clear(vector);
+        if let Some(func) = self.interrupt_acks.get(&vector) {
+            func(&mut self.core, vector);
+        }
+        self.core.pc = self.interrupt_controller.vector_table() + vector * self.info.vector_size;
+    }
+
+    /// return from interrupt service routine
+    pub fn return_from_interrupt(&mut self) {
+        if self.info.pc_bytes == 3 {
+            self.core.pc = self.pop3() as usize;
+        } else {
+            self.core.pc = self.pop2() as usize;
+        }
+
+        self.interrupt_controller.reti(&mut self.core);
+        self.core.irq_delay = true;
+    }
+
     pub fn step(&mut self) -> Result<(), CpuSignal> {
+        let pc = self.core.pc;
+        let result = self.step_core();
+        if let Some(source) = self.core.reset_request {
+            self.reset(source);
+        }
+
+        let memory_fault = self.memory_fault.take();
+        match self.stack_fault.take() {
+            Some((StackFault::Overflow, sp)) =>
+                return self.crash(CpuSignal::StackOverflow { pc, sp }),
+            Some((StackFault::Underflow, sp)) =>
+                return self.crash(CpuSignal::StackUnderflow { pc, sp }),
+            None => (),
+        }
+        match memory_fault {
+            Some(fault) => self.crash(fault),
+            None => result,
+        }
+    }
+
+    fn step_core(&mut self) -> Result<(), CpuSignal> {
+        if self.core.sleep.is_some() {
+            return self.wake_up();
+        }
+
+        if self.core.irq_delay {
+            self.core.irq_delay = false;
+        } else if self.core.interrupt && self.core.irq.has_pending() {
+            if let Some(vector) = self.interrupt_controller.select(&self.core) {
+                self.dispatch_interrupt(vector);
+                return Ok(());
+            }
+        }
+
         let instr = self.decoder.decode(&self.core.flash, self.core.pc * 2);
         self.debugger.pre_instr_hook(self, &instr)?;
-        instr.execute(self)
+        let result = instr.execute(self);
+        if !self.clocks.is_empty() {
+            self.run_clocks();
+        }
+        result
+    }
+
+    /// fast-forward cycles of sleeping cpu until an interrupt wakes it up
+    ///
+    /// `CpuSignal::Sleeping` is returned if no clock has a further event and
+    /// running the clocks again does not change the interrupt requests.
+    fn wake_up(&mut self) -> Result<(), CpuSignal> {
+        let mut settled = false;
+        loop {
+            let requests = self.core.irq.clone();
+            let next_event = self.run_clocks();
+
+            if self.core.interrupt && self.core.irq.has_pending() {
+                if let Some(vector) = self.interrupt_controller.select(&self.core) {
+                    self.core.sleep = None;
+                    self.core.cycles += self.info.arch.cycles().wake_up;
+                    self.dispatch_interrupt(vector);
+                    return Ok(());
+                }
+            }
+
+            match next_event {
+                Some(cycles) if cycles > self.core.cycles => {
+                    let skipped = cycles - self.core.cycles;
+                    let sleep = self.core.sleep;
+                    if !ClockDomain::Io.is_running(sleep) {
+                        self.core.stopped_cycles[0] += skipped;
+                    }
+                    if !ClockDomain::Asynchronous.is_running(sleep) {
+                        self.core.stopped_cycles[1] += skipped;
+                    }
+                    self.core.cycles = cycles;
+                    settled = false;
+                }
+                // a clock may have changed inputs of clocks run before it
+                _ if !settled || self.core.irq != requests => settled = true,
+                _ => return Err(CpuSignal::Sleeping),
+            }
+        }
     }
 
 }
 
 
+/// stack pointer after reset
+///
+/// ATmega8 and ATmega16 start with 0, XMEGA and tinyAVR devices with RAMEND.
+fn reset_sp(info: &AvrVmInfo) -> usize {
+    if info.xmega || info.tiny { info.ram.end - 1 } else { 0 }
+}
+
+
 /// core informations needed for instruction execution
 #[derive(Clone)]
 pub struct AvrVmInfo {
+    /// architecture selecting available instructions and cycle counts
+    pub arch: AvrArch,
+
+    /// wait states of data memory regions
+    pub memory_timing: MemoryTiming,
+
     /// bytes needed for PC
     pub pc_bytes: i32, // TODO: make read-only
 
@@ -431,6 +945,30 @@ pub struct AvrVmInfo {
 
     pub flash_bytes: usize, // TODO: make read-only
 
+    /// size of a interrupt vector table entry in words
+    pub vector_size: usize,
+
+    /// start of boot section in bytes if fixed by device
+    pub boot_section: Option<usize>,
+
+    /// positions of SE and SM0-SM2 in MCUCR or SMCR, `None` for XMEGA devices
+    pub sleep_bits: Option<[u8; 4]>,
+
+    /// start of no-read-while-write flash section in bytes
+    pub nrww_section: Option<usize>,
+
+    /// size of a flash page written with SPM in bytes
+    pub spm_pagesize: usize,
+
+    /// start of flash mapped into data space
+    pub mapped_flash: Option<usize>,
+
+    /// fuses and lock bits
+    pub fuses: Fuses,
+
+    /// bit of the watchdog always on fuse (WDTON or WDLOCK)
+    pub watchdog_fuse: Option<u8>,
+
     pub ios: usize,
 
     pub ram: Range<usize>,
@@ -439,6 +977,9 @@ pub struct AvrVmInfo {
 
     pub io_regs: IoRegAddrs,
 
+    /// interrupt vector numbers by name without `_vect`, e.g. `TCC0_OVF`
+    pub vectors: IoRegAddrs,
+
     pub io_reg_offset: usize
 }
 
@@ -448,11 +989,31 @@ impl AvrVmInfo {
         let register_service = McuIoRegistersService::new();
         let infos = register_service.get_mcu_registers(name).unwrap();
 
+        let arch = AvrArch::from_arch_number(infos["#__AVR_ARCH__"])
+            .expect("unknown AVR architecture");
+
         AvrVmInfo {
+            arch,
+            memory_timing: arch.memory_timing(),
             pc_bytes: if infos.contains_key("#__AVR_3_BYTE_PC__") { 3 } else { 2 },
             xmega: infos.contains_key("#__AVR_XMEGA__"),
-            tiny: false, // TODO
+            tiny: infos.contains_key("#__AVR_TINY__"),
             flash_bytes: infos["#FLASHEND"] + 1,
+            vector_size: if infos["#FLASHEND"] >= 0x2000 { 2 } else { 1 },
+            boot_section: infos.get("#BOOT_SECTION_START").cloned(),
+            sleep_bits: ["#SE", "#SM0", "#SM1", "#SM2"].iter()
+                .map(|&name| infos.get(name).map(|&bit| bit as u8))
+                .collect::<Option<Vec<u8>>>()
+                .map(|bits| [bits[0], bits[1], bits[2], bits[3]]),
+            nrww_section: infos.get("#NRWW_START_ADDR")
+                .or_else(|| infos.get("#BOOT_SECTION_START"))
+                .cloned(),
+            spm_pagesize: infos.get("#SPM_PAGESIZE").cloned().unwrap_or(0),
+            mapped_flash: infos.get("#__AVR_TINY_PM_BASE_ADDRESS__").cloned(),
+            fuses: Fuses::default(),
+            watchdog_fuse: infos.get("#FUSE_WDTON")
+                .or_else(|| infos.get("#FUSE_WDLOCK"))
+                .map(|&fuse| !fuse as u8),
             ios: *infos.get("#IO_SIZE").unwrap_or(&infos["#RAMSTART"]),
             ram: infos["#RAMSTART"]..(infos["#RAMEND"] + 1),
             eeprom: infos
@@ -463,7 +1024,73 @@ impl AvrVmInfo {
                 .filter(|&x| !x.0.starts_with('#'))
                 .map(|x| (*x.0, *x.1))
                 .collect(),
+            vectors: infos.iter()
+                .filter(|&x| x.0.starts_with('#') && x.0.ends_with("_vect_num"))
+                .map(|x| (&x.0[1..x.0.len() - "_vect_num".len()], *x.1))
+                .collect(),
             io_reg_offset: if infos["#__AVR_ARCH__"] >= 100 { 0x00 } else { 0x20 }
         }
     }
+
+    /// valid bits of data space pointers derived from the end of data memory
+    pub fn pointer_mask(&self) -> u32 {
+        let end = match self.mapped_flash {
+            Some(start) => start + self.flash_bytes,
+            None => cmp::max(self.ram.end, self.eeprom.end),
+        };
+        if end <= 0x100 {
+            0xFF
+        } else {
+            cmp::max(0xFFFF, u32::MAX >> ((end - 1) as u32).leading_zeros())
+        }
+    }
+    /// load fuses and lock bits from the `.fuse` and `.lock` sections of `elf`
+    pub fn load_fuses(&mut self, elf: &ElfFile) {
+        let mut fuses = self.fuses;
+        if let Some(section) = elf.section(".fuse") {
+            fuses.set_fuse_bytes(self, &section.data);
+        }
+        if let Some(&lock) = elf.section(".lock").and_then(|section| section.data.first()) {
+            fuses.set_lock_byte(self, lock);
+        }
+        self.fuses = fuses;
+    }
+
+    /// start of the boot section in bytes
+    ///
+    /// The boot section of classic devices is selected by BOOTSZ and is at
+    /// most the no-read-while-write section.
+    pub fn boot_start(&self) -> Option<usize> {
+        self.boot_section.or_else(|| self.nrww_section.map(|nrww| {
+            self.flash_bytes - ((self.flash_bytes - nrww) >> self.fuses.boot_size)
+        }))
+    }
+
+    fn is_boot_section(&self, addr: usize) -> bool {
+        match self.boot_start() {
+            Some(start) => addr >= start,
+            None => false,
+        }
+    }
+
+    /// reset vector in words
+    pub fn reset_vector(&self) -> usize {
+        match self.boot_start() {
+            Some(start) if self.fuses.boot_reset => start / 2,
+            _ => 0,
+        }
+    }
+
+    /// LPM or ELPM at byte address `pc` can read flash byte `addr`
+    pub fn can_lpm_read(&self, pc: usize, addr: usize) -> bool {
+        let boot = self.is_boot_section(addr);
+        boot == self.is_boot_section(pc)
+            || if boot { self.fuses.boot_lock } else { self.fuses.app_lock }.can_read()
+    }
+
+    /// SPM can erase and write the page of flash byte `addr`
+    pub fn can_spm_write(&self, addr: usize) -> bool {
+        if self.is_boot_section(addr) { self.fuses.boot_lock } else { self.fuses.app_lock }
+            .can_write()
+    }
 }
@@ -1,5 +1,9 @@
 use instruction_set::Instruction;
+use arch::AvrArch;
+use arch::InstrSet;
+use arch::ALL_INSTRUCTIONS;
 use std::collections::HashMap;
+use std::ops::Range;
 use byte_convert::u16le;
 use byte_convert::u8bits;
 use byte_convert::bit_at;
@@ -138,21 +142,21 @@ fn add_ldssts(
     for d in 16..32u8 {
         for k in 0..128u8 {
             let addr = u8bits(
-                bit_at(k, 0),
-                bit_at(k, 1),
-                bit_at(k, 2),
-                bit_at(k, 3),
-                bit_at(k, 5),
-                bit_at(k, 6),
+                !bit_at(k, 4),
                 bit_at(k, 4),
-                !bit_at(k, 4)
+                bit_at(k, 6),
+                bit_at(k, 5),
+                bit_at(k, 3),
+                bit_at(k, 2),
+                bit_at(k, 1),
+                bit_at(k, 0)
             );
             instr16.insert(
                 base
-                    | (k as u16 & 0xF0) << 4
+                    | (k as u16 & 0x70) << 4
                     | ((d - 16) as u16) << 4
                     | (k as u16 & 0x0F) << 0,
-                factory(addr, k));
+                factory(d, addr));
         }
     }
 }
@@ -202,12 +206,29 @@ fn add_instr12(
 }
 
 pub struct AvrDecoder {
-    instr16: HashMap<u16, Instruction>
+    instr16: HashMap<u16, Instruction>,
+
+    /// available instructions
+    instr_set: InstrSet
 }
 
 impl AvrDecoder {
 
+    /// decoder for all instructions except the reduced core ones
     pub fn new() -> AvrDecoder {
+        AvrDecoder::with_instr_set(ALL_INSTRUCTIONS)
+    }
+
+    /// decoder for instructions available on `arch`
+    pub fn for_arch(arch: AvrArch) -> AvrDecoder {
+        AvrDecoder::with_instr_set(arch.instr_set())
+    }
+
+    /// decoder for `instr_set`
+    ///
+    /// Unsupported instructions decode as invalid. The reduced core uses
+    /// 16-bit LDS/STS with a 7-bit address instead of LDD/STD.
+    pub fn with_instr_set(instr_set: InstrSet) -> AvrDecoder {
         use instruction_set::RegIncDec;
         use instruction_set::Instruction::*;
 
@@ -215,11 +236,46 @@ impl AvrDecoder {
 
         instr16.insert(0, Nop);
         instr16.insert(0b_1001_0101_0000_1000_u16, Ret);
+        instr16.insert(0b_1001_0101_0001_1000_u16, Reti);
         instr16.insert(0b_1001_0101_1001_1000_u16, Break);
+        instr16.insert(0b_1001_0101_1000_1000_u16, Sleep);
+        instr16.insert(0b_1001_0101_1010_1000_u16, Wdr);
+        instr16.insert(0b_1001_0101_1110_1000_u16, Spm);
+        instr16.insert(0b_1001_0101_1111_1000_u16, SpmInc);
+        instr16.insert(0b_1001_0100_0000_1001_u16, Ijmp);
+        instr16.insert(0b_1001_0100_0001_1001_u16, Eijmp);
+        instr16.insert(0b_1001_0101_0000_1001_u16, Icall);
+        instr16.insert(0b_1001_0101_0001_1001_u16, Eicall);
+
+        for k in 0..16u8 {
+            instr16.insert(0b_1001_0100_0000_1011_u16 | ((k as u16) << 4), Des { k });
+        }
+
+        // BSET s
+        instr16.insert(0b_1001_0100_0000_1000_u16, Sec);
+        instr16.insert(0b_1001_0100_0001_1000_u16, Sez);
+        instr16.insert(0b_1001_0100_0010_1000_u16, Sen);
+        instr16.insert(0b_1001_0100_0011_1000_u16, Sev);
+        instr16.insert(0b_1001_0100_0100_1000_u16, Ses);
+        instr16.insert(0b_1001_0100_0101_1000_u16, Seh);
+        instr16.insert(0b_1001_0100_0110_1000_u16, Set);
+        instr16.insert(0b_1001_0100_0111_1000_u16, Sei);
+
+        // BCLR s
+        instr16.insert(0b_1001_0100_1000_1000_u16, Clc);
+        instr16.insert(0b_1001_0100_1001_1000_u16, Clz);
+        instr16.insert(0b_1001_0100_1010_1000_u16, Cln);
+        instr16.insert(0b_1001_0100_1011_1000_u16, Clv);
+        instr16.insert(0b_1001_0100_1100_1000_u16, Cls);
+        instr16.insert(0b_1001_0100_1101_1000_u16, Clh);
+        instr16.insert(0b_1001_0100_1110_1000_u16, Clt);
         instr16.insert(0b_1001_0100_1111_1000_u16, Cli);
+
+        instr16.insert(0b_1001_0101_1100_1000_u16, Lpm0);
         instr16.insert(0b_1001_0101_1101_1000_u16, Elpm0);
 
         add_instr5(&mut instr16, 0b_1001_0100_0000_0000_u16, |d| Com { d });
+        add_instr5(&mut instr16, 0b_1001_0100_0000_0001_u16, |d| Neg { d });
         add_instr5(&mut instr16, 0b_1001_0100_0000_0010_u16, |d| Swap { d });
         add_instr5(&mut instr16, 0b_1001_0100_0000_0101_u16, |d| Asr { d });
         add_instr5(&mut instr16, 0b_1001_0100_0000_0110_u16, |d| Lsr { d });
@@ -228,6 +284,12 @@ impl AvrDecoder {
         add_instr5(&mut instr16, 0b_1001_0100_0000_0011_u16, |d| Inc { d });
         add_instr5(&mut instr16, 0b_1001_0000_0000_1111_u16, |r| Pop { r });
         add_instr5(&mut instr16, 0b_1001_0010_0000_1111_u16, |r| Push { r });
+        add_instr5(&mut instr16, 0b_1001_0010_0000_0100_u16, |d| Xch { d });
+        add_instr5(&mut instr16, 0b_1001_0010_0000_0101_u16, |d| Las { d });
+        add_instr5(&mut instr16, 0b_1001_0010_0000_0110_u16, |d| Lac { d });
+        add_instr5(&mut instr16, 0b_1001_0010_0000_0111_u16, |d| Lat { d });
+        add_instr5(&mut instr16, 0b_1001_0000_0000_0100_u16, |d| Lpm { d });
+        add_instr5(&mut instr16, 0b_1001_0000_0000_0101_u16, |d| LpmInc { d });
         add_instr5(&mut instr16, 0b_1001_0000_0000_0110_u16, |d| Elpm { d });
         add_instr5(&mut instr16, 0b_1001_0000_0000_0111_u16, |d| ElpmInc { d });
         add_instr5(&mut instr16, 0b_1001_0000_0000_1100_u16, |d| LdX { d, xop: RegIncDec::Unchanged });
@@ -272,8 +334,10 @@ impl AvrDecoder {
         add_instr26(&mut instr16, 0b_1001_0111_0000_0000_u16, |d, k| Sbiw { d, k });
         add_instr26(&mut instr16, 0b_1001_0110_0000_0000_u16, |d, k| Adiw { d, k });
 
-        add_instr_sbix(&mut instr16, 0b_1001_1001_0000_0000_u16, |r, b| Sbic { r, b });
-        add_instr_sbix(&mut instr16, 0b_1001_1011_0000_0000_u16, |r, b| Sbis { r, b });
+        add_instr_sbix(&mut instr16, 0b_1001_1000_0000_0000_u16, |a, b| Cbi { a, b });
+        add_instr_sbix(&mut instr16, 0b_1001_1010_0000_0000_u16, |a, b| Sbi { a, b });
+        add_instr_sbix(&mut instr16, 0b_1001_1001_0000_0000_u16, |a, b| Sbic { a, b });
+        add_instr_sbix(&mut instr16, 0b_1001_1011_0000_0000_u16, |a, b| Sbis { a, b });
         add_instr53(&mut instr16, 0b_1111_1000_0000_0000_u16, |d, b| Bld { d, b });
         add_instr53(&mut instr16, 0b_1111_1010_0000_0000_u16, |d, b| Bst { d, b });
         add_instr53(&mut instr16, 0b_1111_1100_0000_0000_u16, |r, b| Sbrc { r, b });
@@ -288,10 +352,8 @@ impl AvrDecoder {
 
         add_movw(&mut instr16, 0b_0000_0001_0000_0000_u16, |d, r| Movw { d, r });
 
-        add_ldssts(&mut instr16, 0b_1010_0000_0000_0000_u16, |d, k| { Lds { d, k } });
-        add_ldssts(&mut instr16, 0b_1010_1000_0000_0000_u16, |r, k| { Sts { r, k } });
-
         add_instr55(&mut instr16, 0b_0000_0100_0000_0000_u16, |d, r| Cpc { d, r });
+        add_instr55(&mut instr16, 0b_0001_0000_0000_0000_u16, |d, r| Cpse { d, r });
         add_instr55(&mut instr16, 0b_0000_1000_0000_0000_u16, |d, r| Sbc { d, r });
         add_instr55(&mut instr16, 0b_0000_1100_0000_0000_u16, |d, r| Add { d, r });
         add_instr55(&mut instr16, 0b_0001_0100_0000_0000_u16, |d, r| Cp { d, r });
@@ -305,6 +367,9 @@ impl AvrDecoder {
 
         add_instr44(&mut instr16, 0b_0000_0010_0000_0000_u16, |d, r| Muls { d, r });
         add_instr33(&mut instr16, 0b_0000_0011_0000_0000_u16, |d, r| Mulsu { d, r });
+        add_instr33(&mut instr16, 0b_0000_0011_0000_1000_u16, |d, r| Fmul { d, r });
+        add_instr33(&mut instr16, 0b_0000_0011_1000_0000_u16, |d, r| Fmuls { d, r });
+        add_instr33(&mut instr16, 0b_0000_0011_1000_1000_u16, |d, r| Fmulsu { d, r });
 
         add_stdldd(&mut instr16, 0b_1000_0010_0000_1000_u16, |r, q| StdY { r, q });
         add_stdldd(&mut instr16, 0b_1000_0010_0000_0000_u16, |r, q| StdZ { r, q });
@@ -330,7 +395,14 @@ impl AvrDecoder {
             }
         }
 
-        AvrDecoder { instr16 }
+        instr16.retain(|_, instr| instr_set.supports(instr));
+
+        if instr_set.tiny {
+            add_ldssts(&mut instr16, 0b_1010_0000_0000_0000_u16, |d, k| Lds { d, k });
+            add_ldssts(&mut instr16, 0b_1010_1000_0000_0000_u16, |r, k| Sts { r, k });
+        }
+
+        AvrDecoder { instr16, instr_set }
     }
 
     fn decode_jump_call(flash: &Vec<u8>, pos: usize, w0: u16) -> usize {
@@ -360,6 +432,10 @@ impl AvrDecoder {
         )
     }
 
+    fn has_lds_sts16(&self) -> bool {
+        self.instr_set.sram && !self.instr_set.tiny
+    }
+
     pub fn is_2word_instruction(opcode: u16) -> bool {
         (
             ((opcode >> 8) & 0b11111110 == 0b10010100) && (opcode & 0b1100 == 0b1100)
@@ -382,7 +458,7 @@ impl Decoder for AvrDecoder {
         let w0 = u16le(b0, b1);
 
         match b1 & 0b11111110 {
-            0b10010100 =>
+            0b10010100 if self.instr_set.jmp_call =>
                 match b0 & 0b1110 {
                     0b1100 => return Instruction::Jmp {
                         k: AvrDecoder::decode_jump_call(bytes, pos, w0) as u32 },
@@ -391,12 +467,12 @@ impl Decoder for AvrDecoder {
                     _ => { }
                 },
 
-            0b10010010 if b0 & 0b1111 == 0b0000 => {
+            0b10010010 if b0 & 0b1111 == 0b0000 && self.has_lds_sts16() => {
                 let (r, k) = AvrDecoder::decode_ldssts16(bytes, pos, w0);
                 return Instruction::Sts16 { r, k }
             }
 
-            0b10010000 if b0 & 0b1111 == 0b0000 => {
+            0b10010000 if b0 & 0b1111 == 0b0000 && self.has_lds_sts16() => {
                 let (d, k) = AvrDecoder::decode_ldssts16(bytes, pos, w0);
                 return Instruction::Lds16 { d, k }
             }
@@ -425,10 +501,30 @@ impl AvrDecoderCache {
         }
     }
 
+    pub fn for_arch(arch: AvrArch) -> AvrDecoderCache {
+        AvrDecoderCache {
+            opcodes: vec!(),
+            decoder: AvrDecoder::for_arch(arch)
+        }
+    }
+
     pub fn refresh(&mut self, flash: &Vec<u8>) {
         info!(target: "avrvc.decoder", "Refreshing instruction cache ...");
         self.opcodes = (0..flash.len()).map(|pc| self.decoder.decode(flash, pc)).collect();
     }
+
+    /// decode again all instructions reading bytes of `range`
+    pub fn invalidate(&mut self, flash: &Vec<u8>, range: Range<usize>) {
+        if self.opcodes.len() != flash.len() {
+            return self.refresh(flash);
+        }
+
+        // 32-bit instructions starting in front of range
+        let start = range.start.saturating_sub(3);
+        for pos in start..range.end {
+            self.opcodes[pos] = self.decoder.decode(flash, pos);
+        }
+    }
 }
 
 impl Decoder for AvrDecoderCache {
@@ -491,4 +587,65 @@ mod tests {
         let instr = decoder.decode(&bytes, 0);
         assert_eq!(instr, Instruction::LdZ { d: 0, zop: RegIncDec::Inc });
     }
+
+    #[test]
+    fn test_lds_sts_reduced_core() {
+        let decoder = AvrDecoder::for_arch(AvrArch::AvrTiny);
+        // lds r16, 0x40
+        let instr = decoder.decode(&vec![0x00, 0xa1], 0);
+        assert_eq!(instr, Instruction::Lds { d: 16, k: 0x40 });
+        // lds r31, 0x80
+        let instr = decoder.decode(&vec![0xf0, 0xa0], 0);
+        assert_eq!(instr, Instruction::Lds { d: 31, k: 0x80 });
+        // sts 0xbf, r17
+        let instr = decoder.decode(&vec![0x1f, 0xae], 0);
+        assert_eq!(instr, Instruction::Sts { r: 17, k: 0xbf });
+
+        // same opcode is LDD on other cores
+        let instr = AvrDecoder::new().decode(&vec![0x00, 0xa1], 0);
+        assert_eq!(instr, Instruction::LddZ { d: 16, q: 32 });
+    }
+
+    #[test]
+    fn test_unsupported_reduced_core() {
+        let decoder = AvrDecoder::for_arch(AvrArch::AvrTiny);
+        let opcodes: [u16; 8] = [
+            0x9c00, // mul r0, r0
+            0x0101, // movw r0, r2
+            0x9601, // adiw r24, 1
+            0x0c00, // add r0, r0
+            0x95c8, // lpm
+            0x9004, // lpm r0, Z
+            0x940c, // jmp
+            0x9100, // lds r16, 0x0000
+        ];
+        for &opcode in opcodes.iter() {
+            let bytes = vec![opcode as u8, (opcode >> 8) as u8, 0, 0];
+            assert_eq!(decoder.decode(&bytes, 0), Instruction::Invaild { opcode });
+        }
+
+        // add r16, r17
+        let instr = decoder.decode(&vec![0x01, 0x0f], 0);
+        assert_eq!(instr, Instruction::Add { d: 16, r: 17 });
+        // ld r16, Y
+        let instr = decoder.decode(&vec![0x08, 0x81], 0);
+        assert_eq!(instr, Instruction::LdY { d: 16, yop: RegIncDec::Unchanged });
+    }
+
+    #[test]
+    fn test_unsupported_arch() {
+        // mul r16, r17
+        let bytes = vec![0x01, 0x9f];
+        assert_eq!(AvrDecoder::for_arch(AvrArch::Avr25).decode(&bytes, 0),
+                   Instruction::Invaild { opcode: 0x9f01 });
+        assert_eq!(AvrDecoder::for_arch(AvrArch::Avr4).decode(&bytes, 0),
+                   Instruction::Mul { d: 16, r: 17 });
+
+        // jmp 0x0000
+        let bytes = vec![0x0c, 0x94, 0x00, 0x00, 0x00, 0x00];
+        assert_eq!(AvrDecoder::for_arch(AvrArch::Avr4).decode(&bytes, 0),
+                   Instruction::Invaild { opcode: 0x940c });
+        assert_eq!(AvrDecoder::for_arch(AvrArch::Avr5).decode(&bytes, 0),
+                   Instruction::Jmp { k: 0 });
+    }
 }
\ No newline at end of file
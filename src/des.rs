@@ -0,0 +1,179 @@
+const IP: [u8; 64] = [
+    58, 50, 42, 34, 26, 18, 10, 2, 60, 52, 44, 36, 28, 20, 12, 4,
+    62, 54, 46, 38, 30, 22, 14, 6, 64, 56, 48, 40, 32, 24, 16, 8,
+    57, 49, 41, 33, 25, 17, 9, 1, 59, 51, 43, 35, 27, 19, 11, 3,
+    61, 53, 45, 37, 29, 21, 13, 5, 63, 55, 47, 39, 31, 23, 15, 7,
+];
+
+const FP: [u8; 64] = [
+    40, 8, 48, 16, 56, 24, 64, 32, 39, 7, 47, 15, 55, 23, 63, 31,
+    38, 6, 46, 14, 54, 22, 62, 30, 37, 5, 45, 13, 53, 21, 61, 29,
+    36, 4, 44, 12, 52, 20, 60, 28, 35, 3, 43, 11, 51, 19, 59, 27,
+    34, 2, 42, 10, 50, 18, 58, 26, 33, 1, 41, 9, 49, 17, 57, 25,
+];
+
+const E: [u8; 48] = [
+    32, 1, 2, 3, 4, 5, 4, 5, 6, 7, 8, 9,
+    8, 9, 10, 11, 12, 13, 12, 13, 14, 15, 16, 17,
+    16, 17, 18, 19, 20, 21, 20, 21, 22, 23, 24, 25,
+    24, 25, 26, 27, 28, 29, 28, 29, 30, 31, 32, 1,
+];
+
+const P: [u8; 32] = [
+    16, 7, 20, 21, 29, 12, 28, 17, 1, 15, 23, 26, 5, 18, 31, 10,
+    2, 8, 24, 14, 32, 27, 3, 9, 19, 13, 30, 6, 22, 11, 4, 25,
+];
+
+const PC1: [u8; 56] = [
+    57, 49, 41, 33, 25, 17, 9, 1, 58, 50, 42, 34, 26, 18,
+    10, 2, 59, 51, 43, 35, 27, 19, 11, 3, 60, 52, 44, 36,
+    63, 55, 47, 39, 31, 23, 15, 7, 62, 54, 46, 38, 30, 22,
+    14, 6, 61, 53, 45, 37, 29, 21, 13, 5, 28, 20, 12, 4,
+];
+
+const PC2: [u8; 48] = [
+    14, 17, 11, 24, 1, 5, 3, 28, 15, 6, 21, 10,
+    23, 19, 12, 4, 26, 8, 16, 7, 27, 20, 13, 2,
+    41, 52, 31, 37, 47, 55, 30, 40, 51, 45, 33, 48,
+    44, 49, 39, 56, 34, 53, 46, 42, 50, 36, 29, 32,
+];
+
+const SHIFTS: [u8; 16] = [1, 1, 2, 2, 2, 2, 2, 2, 1, 2, 2, 2, 2, 2, 2, 1];
+
+const SBOXES: [[u8; 64]; 8] = [
+    [
+        14, 4, 13, 1, 2, 15, 11, 8, 3, 10, 6, 12, 5, 9, 0, 7,
+        0, 15, 7, 4, 14, 2, 13, 1, 10, 6, 12, 11, 9, 5, 3, 8,
+        4, 1, 14, 8, 13, 6, 2, 11, 15, 12, 9, 7, 3, 10, 5, 0,
+        15, 12, 8, 2, 4, 9, 1, 7, 5, 11, 3, 14, 10, 0, 6, 13,
+    ],
+    [
+        15, 1, 8, 14, 6, 11, 3, 4, 9, 7, 2, 13, 12, 0, 5, 10,
+        3, 13, 4, 7, 15, 2, 8, 14, 12, 0, 1, 10, 6, 9, 11, 5,
+        0, 14, 7, 11, 10, 4, 13, 1, 5, 8, 12, 6, 9, 3, 2, 15,
+        13, 8, 10, 1, 3, 15, 4, 2, 11, 6, 7, 12, 0, 5, 14, 9,
+    ],
+    [
+        10, 0, 9, 14, 6, 3, 15, 5, 1, 13, 12, 7, 11, 4, 2, 8,
+        13, 7, 0, 9, 3, 4, 6, 10, 2, 8, 5, 14, 12, 11, 15, 1,
+        13, 6, 4, 9, 8, 15, 3, 0, 11, 1, 2, 12, 5, 10, 14, 7,
+        1, 10, 13, 0, 6, 9, 8, 7, 4, 15, 14, 3, 11, 5, 2, 12,
+    ],
+    [
+        7, 13, 14, 3, 0, 6, 9, 10, 1, 2, 8, 5, 11, 12, 4, 15,
+        13, 8, 11, 5, 6, 15, 0, 3, 4, 7, 2, 12, 1, 10, 14, 9,
+        10, 6, 9, 0, 12, 11, 7, 13, 15, 1, 3, 14, 5, 2, 8, 4,
+        3, 15, 0, 6, 10, 1, 13, 8, 9, 4, 5, 11, 12, 7, 2, 14,
+    ],
+    [
+        2, 12, 4, 1, 7, 10, 11, 6, 8, 5, 3, 15, 13, 0, 14, 9,
+        14, 11, 2, 12, 4, 7, 13, 1, 5, 0, 15, 10, 3, 9, 8, 6,
+        4, 2, 1, 11, 10, 13, 7, 8, 15, 9, 12, 5, 6, 3, 0, 14,
+        11, 8, 12, 7, 1, 14, 2, 13, 6, 15, 0, 9, 10, 4, 5, 3,
+    ],
+    [
+        12, 1, 10, 15, 9, 2, 6, 8, 0, 13, 3, 4, 14, 7, 5, 11,
+        10, 15, 4, 2, 7, 12, 9, 5, 6, 1, 13, 14, 0, 11, 3, 8,
+        9, 14, 15, 5, 2, 8, 12, 3, 7, 0, 4, 10, 1, 13, 11, 6,
+        4, 3, 2, 12, 9, 5, 15, 10, 11, 14, 1, 7, 6, 0, 8, 13,
+    ],
+    [
+        4, 11, 2, 14, 15, 0, 8, 13, 3, 12, 9, 7, 5, 10, 6, 1,
+        13, 0, 11, 7, 4, 9, 1, 10, 14, 3, 5, 12, 2, 15, 8, 6,
+        1, 4, 11, 13, 12, 3, 7, 14, 10, 15, 6, 8, 0, 5, 9, 2,
+        6, 11, 13, 8, 1, 4, 10, 7, 9, 5, 0, 15, 14, 2, 3, 12,
+    ],
+    [
+        13, 2, 8, 4, 6, 15, 11, 1, 10, 9, 3, 14, 5, 0, 12, 7,
+        1, 15, 13, 8, 10, 3, 7, 4, 12, 5, 6, 11, 0, 14, 9, 2,
+        7, 11, 4, 1, 9, 12, 14, 2, 0, 6, 10, 13, 15, 3, 5, 8,
+        2, 1, 14, 7, 4, 10, 8, 13, 15, 12, 9, 0, 3, 5, 6, 11,
+    ],
+];
+
+/// permute `input` of width `bits` with FIPS 46 bit numbering (1 is MSB)
+fn permute(input: u64, bits: u32, table: &[u8]) -> u64 {
+    table.iter().fold(0, |output, &pos| {
+        (output << 1) | ((input >> (bits - pos as u32)) & 1)
+    })
+}
+
+fn rotate28(value: u64, n: u8) -> u64 {
+    ((value << n) | (value >> (28 - n))) & 0x0FFF_FFFF
+}
+
+/// 48-bit key of round `round` (0-15)
+fn subkey(key: u64, round: u8) -> u64 {
+    let cd = permute(key, 64, &PC1);
+    let shift = SHIFTS[..=round as usize].iter().sum();
+    let c = rotate28(cd >> 28, shift);
+    let d = rotate28(cd & 0x0FFF_FFFF, shift);
+    permute((c << 28) | d, 56, &PC2)
+}
+
+fn feistel(r: u64, subkey: u64) -> u64 {
+    let x = permute(r, 32, &E) ^ subkey;
+    let s = SBOXES.iter().enumerate().fold(0, |s, (i, sbox)| {
+        let six = (x >> (42 - 6 * i)) & 0x3F;
+        let row = ((six >> 4) & 0b10) | (six & 1);
+        let col = (six >> 1) & 0xF;
+        (s << 4) | sbox[(row * 16 + col) as usize] as u64
+    });
+    permute(s, 32, &P)
+}
+
+/// execute DES round `round` (FIPS 46) on `data` with `key`
+///
+/// 16 rounds in increasing order encrypt or decrypt a block. The layout of
+/// the intermediate data between rounds is not documented by Atmel: the
+/// initial permutation is applied before round 0, L and R are kept in the
+/// high and low half between rounds and the final permutation is applied
+/// after round 15. The key is not modified.
+pub fn des_round(data: u64, key: u64, round: u8, decrypt: bool) -> u64 {
+    let round = round & 0xF;
+    let block = if round == 0 { permute(data, 64, &IP) } else { data };
+    let subkey = subkey(key, if decrypt { 15 - round } else { round });
+
+    let l = block >> 32;
+    let r = block & 0xFFFF_FFFF;
+    let new_r = l ^ feistel(r, subkey);
+
+    if round == 15 {
+        permute((new_r << 32) | r, 64, &FP)
+    } else {
+        (r << 32) | new_r
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn des(data: u64, key: u64, decrypt: bool) -> u64 {
+        (0..16).fold(data, |block, round| des_round(block, key, round, decrypt))
+    }
+
+    // (key, plaintext, ciphertext)
+    const KNOWN_ANSWERS: [(u64, u64, u64); 5] = [
+        (0x0101010101010101, 0x95F8A5E5DD31D900, 0x8000000000000000),
+        (0x0101010101010101, 0x0000000000000000, 0x8CA64DE9C1B123A7),
+        (0x0123456789ABCDEF, 0x4E6F772069732074, 0x3FA40E8A984D4815),
+        (0x133457799BBCDFF1, 0x0123456789ABCDEF, 0x85E813540F0AB405),
+        (0x7CA110454A1A6E57, 0x01A1D6D039776742, 0x690F5B0D9A26939B),
+    ];
+
+    #[test]
+    fn encrypt() {
+        for &(key, plain, cipher) in KNOWN_ANSWERS.iter() {
+            assert_eq!(des(plain, key, false), cipher, "key: 0x{:016X}", key);
+        }
+    }
+
+    #[test]
+    fn decrypt() {
+        for &(key, plain, cipher) in KNOWN_ANSWERS.iter() {
+            assert_eq!(des(cipher, key, true), plain, "key: 0x{:016X}", key);
+        }
+    }
+}
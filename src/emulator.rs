@@ -3,13 +3,36 @@ use core::AvrVmInfo;
 use models::register_gpio;
 use models::usart::register_usarts;
 use models::usart::Usarts;
+use models::pmic::register_pmic;
+use models::pmic::Pmic;
+use models::nvm::register_nvm;
+use models::nvm::Nvm;
+use models::reset::register_reset_controller;
+use models::reset::ResetController;
+use models::timer::register_timers;
+use models::timer::Timers;
+use models::classic_timer::register_classic_timers;
+use models::classic_timer::ClassicTimers;
+use models::gpio::register_ports;
+use models::gpio::Ports;
+use models::exint::register_external_interrupts;
+use models::exint::ExternalInterrupts;
+use std::sync::Arc;
+use std::sync::Mutex;
 
 
 pub struct AvrEmulator {
     pub vm: AvrVm,
 
     // peripherals
-    pub usarts: Usarts
+    pub usarts: Usarts,
+    pub pmic: Option<Arc<Mutex<Pmic>>>,
+    pub nvm: Option<Arc<Mutex<Nvm>>>,
+    pub reset: Option<Arc<Mutex<ResetController>>>,
+    pub timers: Timers,
+    pub classic_timers: Option<Arc<Mutex<ClassicTimers>>>,
+    pub ports: Ports,
+    pub external_interrupts: Option<Arc<Mutex<ExternalInterrupts>>>
 }
 
 impl AvrEmulator {
@@ -18,10 +41,24 @@ impl AvrEmulator {
 
         register_gpio(&mut vm);
         let usarts = register_usarts(&mut vm);
+        let pmic = register_pmic(&mut vm);
+        let nvm = register_nvm(&mut vm);
+        let reset = register_reset_controller(&mut vm);
+        let timers = register_timers(&mut vm);
+        let classic_timers = register_classic_timers(&mut vm);
+        let ports = register_ports(&mut vm);
+        let external_interrupts = register_external_interrupts(&mut vm, &ports, reset.as_ref());
 
         AvrEmulator {
             vm,
-            usarts
+            usarts,
+            pmic,
+            nvm,
+            reset,
+            timers,
+            classic_timers,
+            ports,
+            external_interrupts
         }
     }
 
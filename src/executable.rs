@@ -2,6 +2,9 @@ use std::fs::File;
 use std::io::prelude::*;
 use std::path::Path;
 use std::ffi::OsStr;
+use std::collections::HashMap;
+use byte_convert::read_u16le;
+use byte_convert::read_u32le;
 
 trait ExecutableReader {
     fn parse(bytes: &Vec<u8>) -> Vec<u8>;
@@ -28,3 +31,132 @@ impl ExecutableReader for BinaryReader {
     }
 }
 
+
+
+/// offset of data space addresses in AVR ELF files
+pub const ELF_DATA_OFFSET: u32 = 0x80_0000;
+
+/// data space address of ELF symbol `value`
+pub fn elf_data_address(value: u32) -> usize {
+    if value >= ELF_DATA_OFFSET { (value - ELF_DATA_OFFSET) as usize } else { value as usize }
+}
+
+/// section of an ELF file
+pub struct ElfSection {
+    pub name: String,
+
+    /// load address
+    pub addr: u32,
+
+    /// content, empty for sections without data like `.bss`
+    pub data: Vec<u8>,
+}
+
+/// sections and symbols of a 32-bit little endian ELF file
+pub struct ElfFile {
+    pub sections: Vec<ElfSection>,
+    pub symbols: HashMap<String, u32>,
+}
+
+const SHT_SYMTAB: u32 = 2;
+const SHT_NOBITS: u32 = 8;
+
+impl ElfFile {
+    /// parse ELF file or return `None` if it is malformed
+    pub fn parse(bytes: &[u8]) -> Option<ElfFile> {
+        if bytes.get(0..6)? != b"\x7fELF\x01\x01" {
+            return None;
+        }
+
+        let header = bytes.get(0..52)?;
+        let shoff = read_u32le(&header[32..]) as usize;
+        let shentsize = read_u16le(&header[46..]) as usize;
+        let shnum = read_u16le(&header[48..]) as usize;
+        let shstrndx = read_u16le(&header[50..]) as usize;
+
+        let headers = (0..shnum)
+            .map(|i| bytes.get(shoff + i * shentsize..shoff + i * shentsize + 40))
+            .collect::<Option<Vec<&[u8]>>>()?;
+        let content = |header: &[u8]| {
+            let offset = read_u32le(&header[16..]) as usize;
+            bytes.get(offset..offset + read_u32le(&header[20..]) as usize)
+        };
+        let names = content(headers.get(shstrndx)?)?;
+
+        let mut sections = Vec::with_capacity(shnum);
+        let mut symbols = HashMap::new();
+        for header in &headers {
+            let sh_type = read_u32le(&header[4..]);
+            let data = if sh_type == SHT_NOBITS { &[][..] } else { content(header)? };
+
+            if sh_type == SHT_SYMTAB {
+                let strings = content(headers.get(read_u32le(&header[24..]) as usize)?)?;
+                for symbol in data.chunks(16).filter(|symbol| symbol.len() == 16) {
+                    let name = read_string(strings, read_u32le(symbol) as usize)?;
+                    if !name.is_empty() {
+                        symbols.insert(name, read_u32le(&symbol[4..]));
+                    }
+                }
+            }
+
+            sections.push(ElfSection {
+                name: read_string(names, read_u32le(header) as usize)?,
+                addr: read_u32le(&header[12..]),
+                data: data.to_vec(),
+            });
+        }
+
+        Some(ElfFile { sections, symbols })
+    }
+
+    pub fn section(&self, name: &str) -> Option<&ElfSection> {
+        self.sections.iter().find(|section| section.name == name)
+    }
+
+    pub fn symbol(&self, name: &str) -> Option<u32> {
+        self.symbols.get(name).cloned()
+    }
+}
+
+/// zero terminated string at `offset` of a string table
+fn read_string(table: &[u8], offset: usize) -> Option<String> {
+    let bytes = table.get(offset..)?;
+    let end = bytes.iter().position(|&b| b == 0)?;
+    Some(String::from_utf8_lossy(&bytes[..end]).into_owned())
+}
+
+pub fn read_elf_file(filepath: &Path) -> ElfFile {
+    let mut f = File::open(filepath).expect("file not found");
+    let mut contents: Vec<u8> = Vec::new();
+    f.read_to_end(&mut contents)
+        .expect("something went wrong reading the file");
+
+    ElfFile::parse(&contents).expect("invalid ELF file")
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_elf() {
+        let elf = read_elf_file(Path::new("tests/nothing/main.elf"));
+
+        let text = elf.section(".text").unwrap();
+        assert_eq!(text.addr, 0);
+        assert_eq!(text.data.len(), 566);
+        assert_eq!(elf.section(".data").unwrap().addr, 0x80_2000);
+        assert!(elf.section(".fuse").is_none());
+
+        assert_eq!(elf.symbol("__stack"), Some(0x3FFF));
+        assert_eq!(elf.symbol("_end").map(elf_data_address), Some(0x2000));
+        assert_eq!(elf_data_address(0x3FFF), 0x3FFF);
+    }
+
+    #[test]
+    fn parse_invalid_elf() {
+        assert!(ElfFile::parse(b"\x7fELF").is_none());
+        assert!(ElfFile::parse(&[0u8; 64]).is_none());
+    }
+}
@@ -0,0 +1,189 @@
+use core::AvrVmInfo;
+use byte_convert::bit_at;
+
+
+/// protection of a flash section by lock bits
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SectionLock {
+    /// no restrictions
+    NoLock,
+
+    /// SPM can not erase or write the section
+    WriteLock,
+
+    /// LPM and ELPM from the other section can not read the section
+    ReadLock,
+
+    /// write and read lock
+    ReadWriteLock,
+}
+
+impl SectionLock {
+    /// lock from a two bit group of the lock byte (BLBx2:BLBx1 or BLBx)
+    pub fn from_bits(bits: u8) -> SectionLock {
+        match bits & 0b11 {
+            0b11 => SectionLock::NoLock,
+            0b10 => SectionLock::WriteLock,
+            0b01 => SectionLock::ReadLock,
+            _ => SectionLock::ReadWriteLock,
+        }
+    }
+
+    pub fn can_write(self) -> bool {
+        self == SectionLock::NoLock || self == SectionLock::ReadLock
+    }
+
+    pub fn can_read(self) -> bool {
+        self == SectionLock::NoLock || self == SectionLock::WriteLock
+    }
+}
+
+
+/// fuse and lock bit configuration
+///
+/// The watchdog timer is not modeled, `watchdog_always_on` is only recorded.
+/// The application table section of XMEGA devices (BLBAT) is not modeled.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Fuses {
+    /// reset vector at the start of the boot section (BOOTRST)
+    pub boot_reset: bool,
+
+    /// boot section size of classic devices (BOOTSZ1:0), 0 selects the largest
+    pub boot_size: u8,
+
+    /// watchdog can not be disabled (WDTON or WDLOCK)
+    pub watchdog_always_on: bool,
+
+    /// protection of the application section (BLB0 or BLBA)
+    pub app_lock: SectionLock,
+
+    /// protection of the boot section (BLB1 or BLBB)
+    pub boot_lock: SectionLock,
+}
+
+impl Default for Fuses {
+    /// factory settings
+    fn default() -> Fuses {
+        Fuses {
+            boot_reset: false,
+            boot_size: 0,
+            watchdog_always_on: false,
+            app_lock: SectionLock::NoLock,
+            boot_lock: SectionLock::NoLock,
+        }
+    }
+}
+
+impl Fuses {
+    /// set fuses from fuse bytes in the order of the `.fuse` section
+    ///
+    /// Programmed fuses are zero, missing bytes are unprogrammed.
+    pub fn set_fuse_bytes(&mut self, info: &AvrVmInfo, bytes: &[u8]) {
+        let byte = |i: usize| bytes.get(i).cloned().unwrap_or(0xFF);
+
+        let watchdog_byte = if info.xmega {
+            self.boot_reset = !bit_at(byte(2), 6);
+            byte(4)
+        } else if info.tiny {
+            byte(0)
+        } else {
+            self.boot_reset = !bit_at(byte(1), 0);
+            self.boot_size = (byte(1) >> 1) & 0b11;
+            byte(1)
+        };
+        self.watchdog_always_on = match info.watchdog_fuse {
+            Some(mask) => watchdog_byte & mask == 0,
+            None => false,
+        };
+    }
+
+    /// set lock bits from the lock byte of the `.lock` section
+    pub fn set_lock_byte(&mut self, info: &AvrVmInfo, lock: u8) {
+        if info.xmega {
+            self.app_lock = SectionLock::from_bits(lock >> 4);
+            self.boot_lock = SectionLock::from_bits(lock >> 6);
+        } else if !info.tiny {
+            self.app_lock = SectionLock::from_bits(lock >> 2);
+            self.boot_lock = SectionLock::from_bits(lock >> 4);
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use executable::ElfFile;
+    use executable::ElfSection;
+    use std::collections::HashMap;
+
+    #[test]
+    fn classic_fuses() {
+        let mut info = AvrVmInfo::from_name("atmega8");
+        assert_eq!(info.boot_start(), Some(0x1800));
+        assert_eq!(info.reset_vector(), 0);
+
+        // WDTON, BOOTSZ = 10, BOOTRST
+        info.fuses.set_fuse_bytes(&info.clone(), &[0xE1, 0x94]);
+        assert!(info.fuses.watchdog_always_on);
+        assert!(info.fuses.boot_reset);
+        assert_eq!(info.fuses.boot_size, 2);
+        assert_eq!(info.boot_start(), Some(0x1E00));
+        assert_eq!(info.reset_vector(), 0xF00);
+
+        // atmega16 has no WDTON
+        let mut info = AvrVmInfo::from_name("atmega16");
+        info.fuses.set_fuse_bytes(&info.clone(), &[0xE1, 0x00]);
+        assert!(!info.fuses.watchdog_always_on);
+        assert_eq!(info.boot_start(), Some(0x3800));
+    }
+
+    #[test]
+    fn xmega_fuses() {
+        let mut info = AvrVmInfo::from_name("atxmega128a4u");
+        info.fuses.set_fuse_bytes(&info.clone(), &[0xFF, 0x00, 0xBF, 0xFF, 0xFD, 0xFF]);
+        assert!(info.fuses.boot_reset);
+        assert!(info.fuses.watchdog_always_on);
+        assert_eq!(info.reset_vector(), 0x10000);
+    }
+
+    #[test]
+    fn lock_bits() {
+        let mut info = AvrVmInfo::from_name("atmega16");
+        // BLB0 mode 3, BLB1 mode 4
+        info.fuses.set_lock_byte(&info.clone(), 0b1101_0011);
+        assert_eq!(info.fuses.app_lock, SectionLock::ReadWriteLock);
+        assert_eq!(info.fuses.boot_lock, SectionLock::ReadLock);
+
+        assert!(!info.can_spm_write(0x0100));
+        assert!(info.can_spm_write(0x3800));
+        assert!(info.can_lpm_read(0x0000, 0x0100));
+        assert!(!info.can_lpm_read(0x0000, 0x3800));
+        assert!(!info.can_lpm_read(0x3800, 0x0100));
+        assert!(info.can_lpm_read(0x3800, 0x3900));
+
+        let mut info = AvrVmInfo::from_name("atxmega128a4u");
+        // BLBB = WLOCK, BLBA = RLOCK
+        info.fuses.set_lock_byte(&info.clone(), 0b1001_1111);
+        assert_eq!(info.fuses.app_lock, SectionLock::ReadLock);
+        assert_eq!(info.fuses.boot_lock, SectionLock::WriteLock);
+    }
+
+    #[test]
+    fn load_from_elf() {
+        let mut info = AvrVmInfo::from_name("atmega16");
+        let elf = ElfFile {
+            sections: vec![
+                ElfSection { name: ".fuse".to_string(), addr: 0x82_0000, data: vec![0xE1, 0x98] },
+                ElfSection { name: ".lock".to_string(), addr: 0x83_0000, data: vec![0xCF] },
+            ],
+            symbols: HashMap::new(),
+        };
+
+        info.load_fuses(&elf);
+        assert!(info.fuses.boot_reset);
+        assert_eq!(info.fuses.boot_size, 0);
+        assert_eq!(info.fuses.boot_lock, SectionLock::ReadWriteLock);
+        assert_eq!(info.fuses.app_lock, SectionLock::NoLock);
+    }
+}
@@ -13,6 +13,9 @@ pub const SIGILL: u32 = 4;
 /// Trace/breakpoint trap
 pub const SIGTRAP: u32 = 5;
 
+/// Invalid memory reference
+pub const SIGSEGV: u32 = 11;
+
 
 #[derive(Copy, Clone, PartialEq)]
 pub enum DebuggerState {
@@ -81,7 +84,9 @@ impl GdbDebugger {
     fn get_signal_code(&self, signal: CpuSignal) -> u32 {
         match signal {
             CpuSignal::InvaildOpcode { .. } | CpuSignal::PcOutOfBounds { .. } => SIGILL,
-            CpuSignal::Break => SIGTRAP
+            CpuSignal::Break | CpuSignal::Sleeping => SIGTRAP,
+            CpuSignal::MemoryFault { .. } | CpuSignal::StackOverflow { .. }
+                | CpuSignal::StackUnderflow { .. } => SIGSEGV
         }
     }
 }
\ No newline at end of file
@@ -5,6 +5,10 @@ use bits::BitOps;
 
 use decoder::AvrDecoder;
 use byte_convert::u16le;
+use byte_convert::u32le;
+use des::des_round;
+use models::sleep::selected_sleep_mode;
+use std::sync::Arc;
 use core::DataMemoryType;
 use byte_convert::bit_at_u16;
 use byte_convert::as_signed;
@@ -46,16 +50,36 @@ pub enum Instruction {
     Brvs { k: i8 },
     Bst { d: u8, b: u8 },
     Call { k: u32 },
+    Cbi { a: u8, b: u8 },
+    Clc,
+    Clh,
     Cli,
+    Cln,
+    Cls,
+    Clt,
+    Clv,
+    Clz,
     Com { d: u8 },
     Cp { d: u8, r: u8},
     Cpc { d: u8, r: u8},
     Cpi { d: u8, k: u8 },
+    Cpse { d: u8, r: u8 },
     Dec { d: u8 },
+    Des { k: u8 },
+    Eicall,
+    Eijmp,
     Elpm0,
     Elpm { d: u8 },
     ElpmInc { d: u8 },
     Eor { d: u8, r: u8 },
+    Fmul { d: u8, r: u8 },
+    Fmuls { d: u8, r: u8 },
+    Fmulsu { d: u8, r: u8 },
+    Icall,
+    Ijmp,
+    Lac { d: u8 },
+    Las { d: u8 },
+    Lat { d: u8 },
     LdX { d: u8, xop: RegIncDec },
     LdY { d: u8, yop: RegIncDec },
     LdZ { d: u8, zop: RegIncDec },
@@ -64,6 +88,9 @@ pub enum Instruction {
     Ldi { d: u8, k: u8 },
     Lds { d: u8, k: u8 },
     Lds16 { d: u8, k: u16 },
+    Lpm0,
+    Lpm { d: u8 },
+    LpmInc { d: u8 },
     Lsr { d: u8 },
     In  { d: u8, a: u8 },
     Inc { d: u8 },
@@ -73,6 +100,7 @@ pub enum Instruction {
     Mul { d: u8, r: u8 },
     Muls { d: u8, r: u8 },
     Mulsu { d: u8, r: u8 },
+    Neg { d: u8 },
     Nop,
     Or { d: u8, r: u8 },
     Ori { d: u8, k: u8 },
@@ -81,15 +109,28 @@ pub enum Instruction {
     Push { r: u8 },
     Rcall { k: i16 },
     Ret,
+    Reti,
     Rjmp { k: i16 },
     Ror { d: u8 },
     Sbc { d: u8, r: u8 },
     Sbci { d: u8, k: u8 },
-    Sbic { r: u8, b: u8 },
-    Sbis { r: u8, b: u8 },
+    Sbi { a: u8, b: u8 },
+    Sbic { a: u8, b: u8 },
+    Sbis { a: u8, b: u8 },
     Sbiw { d: u8, k: u8 },
     Sbrc { r: u8, b: u8 },
     Sbrs { r: u8, b: u8 },
+    Sec,
+    Seh,
+    Sei,
+    Sen,
+    Ses,
+    Set,
+    Sleep,
+    Spm,
+    SpmInc,
+    Sev,
+    Sez,
     StX { r: u8, xop: RegIncDec },
     StY { r: u8, yop: RegIncDec },
     StZ { r: u8, zop: RegIncDec },
@@ -100,6 +141,8 @@ pub enum Instruction {
     Sub { d: u8, r: u8 },
     Subi { d: u8, k: u8 },
     Swap { d: u8 },
+    Wdr,
+    Xch { d: u8 },
 
     Invaild { opcode: u16 }
 }
@@ -126,7 +169,7 @@ fn calc_v(rd: u8, rr: u8, r: u8) -> bool {
 fn relative_pc(vm: &mut AvrVm, k: i32) -> Result<usize, CpuSignal> {
     let new_pc = vm.core.pc as i32 + k;
     if new_pc < 0 {
-        vm.reset();
+        vm.core.pc = 0;
         Err(CpuSignal::PcOutOfBounds {
             pc: new_pc as i32
         })
@@ -143,121 +186,187 @@ fn rjmp(vm: &mut AvrVm, k: i32) -> Result<(), CpuSignal> {
 }
 
 fn ldd(vm: &mut AvrVm, yz: u32, q: u8, d: u8) {
-    let yzq = match vm.read(yz as usize + q as usize, false) {
-        Ok((yzq, DataMemoryType::SRam)) => {
-            if vm.info.xmega { vm.core.cycles += 1; }
-            yzq
-        },
-        Ok((_, DataMemoryType::Eeprom)) => 0,
-        Ok((yzq, _)) => yzq,
-        Err(_) => 0,
-    };
+    let yzq = ld_read(vm, vm.core.wrap_pointer(yz + q as u32) as usize);
     vm.core.write_reg(d, yzq);
-    if vm.info.xmega {
-        vm.core.cycles += 1;
-    } else {
-        vm.core.cycles += 2;
-    }
+    vm.core.cycles += vm.info.arch.cycles().ldd - 1;
 }
 
 fn ld(vm: &mut AvrVm, xyz: u32, op: RegIncDec, d: u8) -> u32 {
     let mut xyz = xyz;
 
-    // TODO: only change 8 bit or 16 bit if data space < 256B or < 64KB
-    if op == RegIncDec::Dec { xyz -= 1 }
+    if op == RegIncDec::Dec { xyz = vm.core.wrap_pointer(xyz.wrapping_sub(1)) }
 
     let r = ld_read(vm, xyz as usize);
     vm.core.write_reg(d, r);
 
-    // TODO: only change 8 bit or 16 bit if data space < 256B or < 64KB
-    if op == RegIncDec::Inc { xyz += 1 }
+    if op == RegIncDec::Inc { xyz = vm.core.wrap_pointer(xyz + 1) }
 
-    if vm.info.xmega {
-        if op == RegIncDec::Dec { vm.core.cycles += 1 }
-    } else {
-        match op {
-            RegIncDec::Unchanged => {},
-            RegIncDec::Inc => vm.core.cycles += 1,
-            RegIncDec::Dec => vm.core.cycles += 2,
-        }
-    }
+    let cycles = vm.info.arch.cycles();
+    vm.core.cycles += match op {
+        RegIncDec::Unchanged => cycles.ld,
+        RegIncDec::Inc => cycles.ld_inc,
+        RegIncDec::Dec => cycles.ld_dec,
+    } - 1;
 
     xyz
 }
 
+/// load with LD, LDD or LDS including wait states of the memory region
 fn ld_read(vm: &mut AvrVm, addr: usize) -> u8 {
     match vm.read(addr, false) {
-        Ok((q, DataMemoryType::SRam)) => {
-            if vm.info.xmega { vm.core.cycles += 1; }
-            q
+        Ok((q, region)) => {
+            vm.core.cycles += vm.info.memory_timing.load.of(region);
+            if region == DataMemoryType::Eeprom && !is_eeprom_mapped(vm) { 0 } else { q }
         },
-        Ok((_, DataMemoryType::Eeprom)) => 0,
-        Ok((q, _)) => q,
-        Err(_) => 0,
+        Err(_) => {
+            vm.memory_fault(addr, false);
+            0
+        },
+    }
+}
+
+/// store with ST, STD or STS including wait states of the memory region
+///
+/// stores to mapped EEPROM are ignored because the EEPROM page buffer is not
+/// modeled.
+fn st_write(vm: &mut AvrVm, addr: usize, value: u8) {
+    let region = vm.region(addr);
+    if region != DataMemoryType::Eeprom {
+        vm.write_u8_noneeprom(addr, value);
+    }
+    vm.core.cycles += vm.info.memory_timing.store.of(region);
+}
+
+/// EEPROM is accessible in data space (EEMAPEN of XMEGA devices)
+fn is_eeprom_mapped(vm: &AvrVm) -> bool {
+    match vm.nvm {
+        Some(ref nvm) => nvm.lock().unwrap().is_eeprom_mapped(),
+        None => true,
     }
 }
 
 fn st(vm: &mut AvrVm, xyz: u32, op: RegIncDec, r: u8) -> u32 {
     let mut xyz = xyz;
 
-    // TODO: only change 8 bit or 16 bit if data space < 256B or < 64KB
-    if op == RegIncDec::Dec { xyz -= 1 }
+    if op == RegIncDec::Dec { xyz = vm.core.wrap_pointer(xyz.wrapping_sub(1)) }
 
     let rr = vm.core.read_reg(r);
-    vm.write_u8_noneeprom(xyz as usize, rr);
+    st_write(vm, xyz as usize, rr);
 
-    // TODO: only change 8 bit or 16 bit if data space < 256B or < 64KB
-    if op == RegIncDec::Inc { xyz += 1 }
+    if op == RegIncDec::Inc { xyz = vm.core.wrap_pointer(xyz + 1) }
 
-    if vm.info.xmega || vm.info.tiny {
-        if op == RegIncDec::Dec { vm.core.cycles += 1; }
-    } else {
-        vm.core.cycles += 1;
-    }
+    let cycles = vm.info.arch.cycles();
+    vm.core.cycles += match op {
+        RegIncDec::Unchanged => cycles.st,
+        RegIncDec::Inc => cycles.st_inc,
+        RegIncDec::Dec => cycles.st_dec,
+    } - 1;
 
     xyz
 }
 
+/// byte address of the executed one word instruction, PC was already incremented
+fn instruction_address(vm: &AvrVm) -> usize {
+    let words = vm.core.flash.len() / 2;
+    (vm.core.pc + words - 1) % words * 2
+}
+
+/// read flash byte with LPM/ELPM
+///
+/// Address bits beyond the flash size are ignored, the read-while-write section
+/// and sections locked by lock bits read 0xFF.
+fn read_flash(vm: &AvrVm, addr: usize) -> u8 {
+    let addr = addr % vm.core.flash.len();
+    if !vm.info.can_lpm_read(instruction_address(vm), addr) {
+        return 0xFF;
+    }
+    if let Some(ref nvm) = vm.nvm {
+        if !nvm.lock().unwrap().is_readable(&vm.core, addr) {
+            return 0xFF;
+        }
+    }
+    vm.core.flash[addr]
+}
+
 fn elpm(vm: &mut AvrVm, d: u8) -> u32 {
-    let z = vm.core.read_ramped_z() as usize;
-    let r = vm.core.flash[z];
+    let z = vm.core.read_extended_z() as usize;
+    let r = read_flash(vm, z);
     vm.core.write_reg(d, r);
     vm.core.cycles += 2;
 
     z as u32
 }
 
-fn call(vm: &mut AvrVm, dest: usize) {
+fn lpm(vm: &mut AvrVm, d: u8) -> u16 {
+    let z = vm.core.read_z();
+    let r = read_flash(vm, z as usize);
+    vm.core.write_reg(d, r);
+    vm.core.cycles += 2;
+
+    z
+}
+
+/// store to flash with the NVM controller
+fn spm(vm: &mut AvrVm) -> u32 {
+    let z = vm.core.read_extended_z();
+    let nvm = match vm.nvm {
+        Some(ref nvm) => Arc::clone(nvm),
+        None => return z,
+    };
+
+    // SPM is only executed from the boot section
+    let addr = instruction_address(vm);
+    if addr < vm.info.boot_start().unwrap_or(0) {
+        info!(target: "avrvc::nvm", "SPM at 0x{:05x} outside of boot section ignored", addr);
+        return z;
+    }
+
+    let writable = vm.info.can_spm_write(z as usize);
+    let changed = nvm.lock().unwrap().spm(&mut vm.core, z as usize, writable);
+    if let Some(range) = changed {
+        vm.decoder.invalidate(&vm.core.flash, range);
+    }
+    z
+}
+
+/// push PC as return address and jump to `dest`
+///
+/// the call instruction takes `cycles` in total.
+fn call(vm: &mut AvrVm, dest: usize, cycles: u64) {
     let pc = vm.core.pc;
     if vm.info.pc_bytes == 3 {
-        vm.push3((pc + 1) as u32);
-        if vm.info.xmega {
-            vm.core.cycles += 3;
-        } else {
-            vm.core.cycles += 4;
-        }
+        vm.push3(pc as u32);
     } else {
-        vm.push2((pc + 1) as u16);
-        if vm.info.xmega {
-            vm.core.cycles += 2;
-        } else {
-            vm.core.cycles += 3;
-        }
+        vm.push2(pc as u16);
     }
+    vm.core.cycles += cycles - 1;
     vm.core.pc = dest;
 }
 
-fn skip_if_bit(vm: &mut AvrVm, r: u8, b: u8, test_state: bool) {
-    let rr = vm.core.read_reg(r);
-    if (rr & (1 << b) != 0) == test_state {
-        let instr16 = AvrDecoder::is_2word_instruction(u16le(
-            vm.core.flash[vm.core.pc], vm.core.flash[vm.core.pc + 1]
-        ));
-        vm.core.pc += 1 + instr16 as usize;
-        vm.core.cycles += 1 + instr16 as u64;
+/// skip next instruction
+fn skip(vm: &mut AvrVm) {
+    let pos = vm.core.pc * 2;
+    let instr32 = !vm.info.tiny && AvrDecoder::is_2word_instruction(u16le(
+        vm.core.flash[pos], vm.core.flash[pos + 1]
+    ));
+    vm.core.pc += 1 + instr32 as usize;
+    vm.core.cycles += 1 + instr32 as u64;
+}
+
+fn skip_if_bit(vm: &mut AvrVm, value: u8, b: u8, test_state: bool) {
+    if (value & (1 << b) != 0) == test_state {
+        skip(vm);
     }
 }
 
+/// set bit `b` of I/O register `a` to `value`
+fn write_io_bit(vm: &mut AvrVm, a: u8, b: u8, value: bool) {
+    let addr = a as usize + vm.info.io_reg_offset;
+    let io = vm.read_io(addr, false);
+    vm.write_io(addr, if value { io | (1 << b) } else { io & !(1 << b) });
+    vm.core.cycles += vm.info.arch.cycles().sbi_cbi - 1;
+}
+
 fn cp(vm: &mut AvrVm, left: u8, right: u8) {
     let diff = left as i16 - right as i16;
     let r = (diff & 0xFF) as u8;
@@ -267,6 +376,41 @@ fn cp(vm: &mut AvrVm, left: u8, right: u8) {
     set_zns(vm, r);
 }
 
+/// atomic read-modify-write of data at Z (XMEGA only)
+///
+/// data at Z is replaced by `op(Rd, (Z))` and Rd gets the old value.
+fn rmw_z(vm: &mut AvrVm, opcode: u16, d: u8, op: fn(u8, u8) -> u8) -> Result<(), CpuSignal> {
+    if !vm.info.arch.instr_set().rmw {
+        vm.core.cycles -= 1;
+        return vm.crash(CpuSignal::InvaildOpcode { opcode });
+    }
+
+    let z = vm.core.read_ramped_z() as usize;
+    let old = match vm.read(z, false) {
+        Ok((q, _)) => q,
+        Err(_) => {
+            vm.memory_fault(z, false);
+            0
+        },
+    };
+    let rd = vm.core.read_reg(d);
+    vm.write_u8_noneeprom(z, op(rd, old));
+    vm.core.write_reg(d, old);
+    vm.core.cycles += vm.info.arch.cycles().rmw - 1;
+    Ok(())
+}
+
+/// store result of fractional multiplication
+///
+/// `product` is the 1.7 x 1.7 product before the left shift.
+fn fmul(vm: &mut AvrVm, product: u16) {
+    let r = product << 1;
+    vm.core.write_reg16(0, r);
+    vm.core.zero = r == 0;
+    vm.core.carry = bit_at_u16(product, 15);
+    vm.core.cycles += 1;
+}
+
 impl Instruction {
 
     /// execute instruction
@@ -357,9 +501,22 @@ impl Instruction {
                 state.core.t = (rd & (1 << b)) != 0;
             },
 
-            &Call { k } => call(state, k as usize),
+            &Call { k } => {
+                state.core.pc += 1;
+                let cycles = state.info.arch.cycles().call;
+                call(state, k as usize, cycles);
+            },
 
+            &Clc => state.core.carry = false,
+            &Clh => state.core.h = false,
             &Cli => state.core.interrupt = false,
+            &Cln => state.core.n = false,
+            &Cls => state.core.sign = false,
+            &Clt => state.core.t = false,
+            &Clv => state.core.v = false,
+            &Clz => state.core.zero = false,
+
+            &Cbi { a, b } => write_io_bit(state, a, b, false),
 
             &Com { d } => {
                 let r = !state.core.read_reg(d);
@@ -393,6 +550,12 @@ impl Instruction {
                 cp(state, rd, k);
             }
 
+            &Cpse { d, r } => {
+                if state.core.read_reg(d) == state.core.read_reg(r) {
+                    skip(state);
+                }
+            }
+
             &Dec { d } => {
                 let rd = state.core.read_reg(d);
                 let res = ((rd as i16 - 1i16) & 0xFF) as u8;
@@ -401,11 +564,46 @@ impl Instruction {
                 set_zns(state, res);
             }
 
+            &Des { k } => {
+                if !state.info.arch.instr_set().des {
+                    state.core.cycles -= 1;
+                    let opcode = 0x940B | ((k as u16) << 4);
+                    return state.crash(CpuSignal::InvaildOpcode { opcode });
+                }
+
+                // one extra cycle if not preceded by DES
+                if state.core.des_end != Some(state.core.cycles - 1) {
+                    state.core.cycles += 1;
+                }
+
+                let mut data = [0u8; 8];
+                let mut key = [0u8; 8];
+                data.copy_from_slice(&state.core.regs[0..8]);
+                key.copy_from_slice(&state.core.regs[8..16]);
+                let result = des_round(
+                    u64::from_le_bytes(data), u64::from_le_bytes(key), k, state.core.h);
+                state.core.regs[0..8].copy_from_slice(&result.to_le_bytes());
+
+                state.core.des_end = Some(state.core.cycles);
+            }
+
+            &Eicall => {
+                let dest = u32le(state.core.read_reg(30), state.core.read_reg(31), state.core.eind, 0);
+                let cycles = state.info.arch.cycles().rcall;
+                call(state, dest as usize, cycles);
+            },
+
+            &Eijmp => {
+                let dest = u32le(state.core.read_reg(30), state.core.read_reg(31), state.core.eind, 0);
+                state.core.pc = dest as usize;
+                state.core.cycles += 1;
+            },
+
             &Elpm0 => { elpm(state, 0); },
             &Elpm { d } => { elpm(state, d); },
             &ElpmInc { d } => {
                 let z = elpm(state, d);
-                state.core.write_ramped_z(z + 1);
+                state.core.write_extended_z(z + 1);
             },
 
             &Eor { d, r } => {
@@ -415,8 +613,37 @@ impl Instruction {
                 set_zns(state, r);
             },
 
+            &Fmul { d, r } => {
+                let rr = state.core.read_reg(r) as u16;
+                let rd = state.core.read_reg(d) as u16;
+                fmul(state, rr * rd);
+            }
+
+            &Fmuls { d, r } => {
+                let rr = as_signed(state.core.read_reg(r)) as i16;
+                let rd = as_signed(state.core.read_reg(d)) as i16;
+                fmul(state, as_unsigned16(rr * rd));
+            }
+
+            &Fmulsu { d, r } => {
+                let rr = state.core.read_reg(r) as i16;
+                let rd = as_signed(state.core.read_reg(d)) as i16;
+                fmul(state, as_unsigned16(rr * rd));
+            }
+
+            &Icall => {
+                let dest = state.core.read_z();
+                let cycles = state.info.arch.cycles().rcall;
+                call(state, dest as usize, cycles);
+            },
+
+            &Ijmp => {
+                state.core.pc = state.core.read_z() as usize;
+                state.core.cycles += 1;
+            },
+
             &In { d, a } => {
-                let io = state.read_io(a as usize, false);
+                let io = state.read_io(a as usize + state.info.io_reg_offset, false);
                 state.core.write_reg(d, io);
             },
 
@@ -473,8 +700,30 @@ impl Instruction {
                 state.core.cycles += 1;
             }
 
+            &Neg { d } => {
+                let rd = state.core.read_reg(d);
+                let r = 0u8.wrapping_sub(rd);
+                state.core.h = (r | rd) & (1 << 3) != 0;
+                state.core.v = r == 0x80;
+                state.core.carry = r != 0;
+                set_zns(state, r);
+                state.core.write_reg(d, r);
+            },
+
             &Nop => { },
 
+            &Lac { d } => {
+                return rmw_z(state, 0x9206 | ((d as u16) << 4), d, |rd, z| !rd & z);
+            }
+
+            &Las { d } => {
+                return rmw_z(state, 0x9205 | ((d as u16) << 4), d, |rd, z| rd | z);
+            }
+
+            &Lat { d } => {
+                return rmw_z(state, 0x9207 | ((d as u16) << 4), d, |rd, z| rd ^ z);
+            }
+
             &LdX { d, xop } => {
                 let mut x = state.core.read_ramped_x();
                 x = ld(state, x, xop, d);
@@ -510,13 +759,21 @@ impl Instruction {
                 let addr = state.core.ramped_addr(k);
                 let r = ld_read(state, addr);
                 state.core.write_reg(d, r);
-                state.core.cycles += 1;
+                state.core.cycles += state.info.arch.cycles().lds - 1;
                 state.core.pc += 1;
             },
 
             &Lds { d, k } => {
-                let r = state.read_u8_noneeprom(k as usize, false);
+                let r = ld_read(state, k as usize);
                 state.core.write_reg(d, r);
+                state.core.cycles += state.info.arch.cycles().lds - 1;
+            },
+
+            &Lpm0 => { lpm(state, 0); },
+            &Lpm { d } => { lpm(state, d); },
+            &LpmInc { d } => {
+                let z = lpm(state, d);
+                state.core.write_z(z.wrapping_add(1));
             },
 
             &Lsr { d } => {
@@ -545,7 +802,7 @@ impl Instruction {
 
             &Out { r, a } => {
                 let reg = state.core.read_reg(r);
-                state.write_io(a as usize, reg);
+                state.write_io(a as usize + state.info.io_reg_offset, reg);
             },
 
             &Pop { r } => {
@@ -557,24 +814,27 @@ impl Instruction {
             &Push { r } => {
                 let reg = state.core.read_reg(r);
                 state.push(reg);
-                if !state.info.xmega {
-                    state.core.cycles += 1;
-                }
+                state.core.cycles += state.info.arch.cycles().push - 1;
             },
 
             &Rcall { k } => {
                 let new_pc = relative_pc(state, k as i32)?;
-                call(state, new_pc);
+                let cycles = state.info.arch.cycles().rcall;
+                call(state, new_pc, cycles);
             },
 
             &Ret => {
                 if state.info.pc_bytes == 3 {
                     state.core.pc = state.pop3() as usize;
-                    state.core.cycles += 4;
                 } else {
                     state.core.pc = state.pop2() as usize;
-                    state.core.cycles += 3;
                 }
+                state.core.cycles += state.info.arch.cycles().ret - 1;
+            },
+
+            &Reti => {
+                state.return_from_interrupt();
+                state.core.cycles += state.info.arch.cycles().ret - 1;
             },
 
             &Ror { d } => {
@@ -615,13 +875,12 @@ impl Instruction {
                 state.core.write_reg(d, (res & 0xFF) as u8);
             }
 
-            &Sbic { r, b } | &Sbis { r, b } => {
-                let io_reg_offset = state.info.io_reg_offset as u8;
-                skip_if_bit(
-                    state,
-                    r + io_reg_offset,
-                    b,
-                    matches!(self, &Sbis { .. }));
+            &Sbi { a, b } => write_io_bit(state, a, b, true),
+
+            &Sbic { a, b } | &Sbis { a, b } => {
+                let io = state.read_io(a as usize + state.info.io_reg_offset, false);
+                state.core.cycles += state.info.arch.cycles().sbic_sbis - 1;
+                skip_if_bit(state, io, b, matches!(self, &Sbis { .. }));
             }
 
             &Sbiw { d, k } => {
@@ -635,9 +894,23 @@ impl Instruction {
             }
 
             &Sbrc { r, b } | &Sbrs { r, b } => {
-                skip_if_bit(state, r, b, matches!(self, &Sbrs { .. }));
+                let rr = state.core.read_reg(r);
+                skip_if_bit(state, rr, b, matches!(self, &Sbrs { .. }));
             }
 
+            &Sec => state.core.carry = true,
+            &Seh => state.core.h = true,
+            &Sei => {
+                // instruction after SEI is executed before any interrupt
+                state.core.interrupt = true;
+                state.core.irq_delay = true;
+            },
+            &Sen => state.core.n = true,
+            &Ses => state.core.sign = true,
+            &Set => state.core.t = true,
+            &Sev => state.core.v = true,
+            &Sez => state.core.zero = true,
+
             &StX { r, xop } => {
                 let mut x = state.core.read_ramped_x();
                 x = st(state, x, xop, r);
@@ -659,34 +932,44 @@ impl Instruction {
             &StdY { q, r } => {
                 let y = state.core.read_ramped_y();
                 let rr = state.core.read_reg(r);
-                state.write_u8_noneeprom(y as usize + q as usize, rr);
-                if !state.info.xmega && !state.info.tiny {
-                    state.core.cycles += 1;
-                }
+                st_write(state, state.core.wrap_pointer(y + q as u32) as usize, rr);
+                state.core.cycles += state.info.arch.cycles().std - 1;
             },
 
             &StdZ { q, r } => {
                 let z = state.core.read_ramped_z();
                 let rr = state.core.read_reg(r);
-                state.write_u8_noneeprom(z as usize + q as usize, rr);
-                if !state.info.xmega && !state.info.tiny {
-                    state.core.cycles += 1;
-                }
+                st_write(state, state.core.wrap_pointer(z + q as u32) as usize, rr);
+                state.core.cycles += state.info.arch.cycles().std - 1;
             },
 
             &Sts16 { r, k } => {
                 let addr = state.core.ramped_addr(k);
                 let rr = state.core.read_reg(r);
-                state.write_u8_noneeprom(addr, rr);
-                state.core.cycles += 1;
+                st_write(state, addr, rr);
+                state.core.cycles += state.info.arch.cycles().sts - 1;
                 state.core.pc += 1;
             }
 
             &Sts { r, k } => {
                 let rr = state.core.read_reg(r);
-                state.write_u8_noneeprom(k as usize, rr);
+                st_write(state, k as usize, rr);
+                state.core.cycles += state.info.arch.cycles().sts - 1;
             }
 
+            &Sleep => state.core.sleep = selected_sleep_mode(state),
+
+            &Spm => { spm(state); },
+            &SpmInc => {
+                if !state.info.arch.instr_set().spmx {
+                    state.core.cycles -= 1;
+                    return state.crash(CpuSignal::InvaildOpcode { opcode: 0x95F8 });
+                }
+
+                let z = spm(state);
+                state.core.write_extended_z(z + 2);
+            },
+
             &Sub { d, r } => {
                 let rd = state.core.read_reg(d);
                 let rr = state.core.read_reg(r);
@@ -713,6 +996,12 @@ impl Instruction {
                 state.core.write_reg(d, low << 4 | high);
             }
 
+            &Wdr => state.core.watchdog = state.core.cycles,
+
+            &Xch { d } => {
+                return rmw_z(state, 0x9204 | ((d as u16) << 4), d, |rd, _| rd);
+            }
+
             &Invaild { opcode } => {
                 state.core.cycles -= 1;
                 return state.crash(CpuSignal::InvaildOpcode { opcode });
@@ -738,6 +1027,9 @@ mod tests {
     use super::*;
     use models::xmega_au::XmegaA4U::ATxmega128A4U;
     use models::AvrModel;
+    use core::AvrVmInfo;
+    use core::MemoryFaultPolicy;
+    use core::StackGuard;
     use instruction_set::Instruction::*;
     use std::mem;
     use std::sync::Mutex;
@@ -774,6 +1066,197 @@ mod tests {
         assert_eq!(vm.core.cycles, 4);
     }
 
+    #[test]
+    fn execute_rcall_xmega() {
+        let mut vm = ATXMEGA128A4U.lock().unwrap();
+        fast_reset(&mut vm);
+
+        let old_sp = vm.core.sp;
+        vm.core.pc = 0x1000;
+
+        let cmd = Rcall { k: -5 };
+        cmd.execute(&mut vm).unwrap();
+
+        assert_eq!(vm.core.sp, old_sp - 3);
+        assert_eq!(vm.read_unchecked(old_sp - 0, true), 0x01);
+        assert_eq!(vm.read_unchecked(old_sp - 1, true), 0x10);
+        assert_eq!(vm.read_unchecked(old_sp - 2, true), 0x00);
+        assert_eq!(vm.core.pc, 0x1001 - 5);
+        assert_eq!(vm.core.cycles, 3);
+        vm.core.sp = old_sp;
+    }
+
+    #[test]
+    fn execute_icall_xmega() {
+        let mut vm = ATXMEGA128A4U.lock().unwrap();
+        fast_reset(&mut vm);
+
+        let old_sp = vm.core.sp;
+        vm.core.pc = 0x1ABCD;
+        vm.core.eind = 0x01;
+        vm.core.write_z(0x1337);
+
+        let cmd = Icall;
+        cmd.execute(&mut vm).unwrap();
+
+        assert_eq!(vm.core.sp, old_sp - 3);
+        assert_eq!(vm.read_unchecked(old_sp - 0, true), 0xCE);
+        assert_eq!(vm.read_unchecked(old_sp - 1, true), 0xAB);
+        assert_eq!(vm.read_unchecked(old_sp - 2, true), 0x01);
+        assert_eq!(vm.core.pc, 0x1337);
+        assert_eq!(vm.core.cycles, 3);
+        vm.core.sp = old_sp;
+    }
+
+    #[test]
+    fn execute_eicall_xmega() {
+        let mut vm = ATXMEGA128A4U.lock().unwrap();
+        fast_reset(&mut vm);
+
+        let old_sp = vm.core.sp;
+        vm.core.pc = 0x100;
+        vm.core.eind = 0x01;
+        vm.core.write_z(0x1337);
+
+        let cmd = Eicall;
+        cmd.execute(&mut vm).unwrap();
+
+        assert_eq!(vm.core.sp, old_sp - 3);
+        assert_eq!(vm.read_unchecked(old_sp - 0, true), 0x01);
+        assert_eq!(vm.read_unchecked(old_sp - 1, true), 0x01);
+        assert_eq!(vm.read_unchecked(old_sp - 2, true), 0x00);
+        assert_eq!(vm.core.pc, 0x11337);
+        assert_eq!(vm.core.cycles, 3);
+        vm.core.sp = old_sp;
+    }
+
+    #[test]
+    fn execute_icall_classic() {
+        let mut vm = AvrVm::new(&AvrVmInfo::from_name("atmega16"));
+        vm.core.sp = vm.info.ram.end - 1;
+
+        let old_sp = vm.core.sp;
+        vm.core.pc = 0x100;
+        vm.core.write_z(0x1337);
+
+        let cmd = Icall;
+        cmd.execute(&mut vm).unwrap();
+
+        assert_eq!(vm.core.sp, old_sp - 2);
+        assert_eq!(vm.read_unchecked(old_sp - 0, true), 0x01);
+        assert_eq!(vm.read_unchecked(old_sp - 1, true), 0x01);
+        assert_eq!(vm.core.pc, 0x1337);
+        assert_eq!(vm.core.cycles, 3);
+    }
+
+    #[test]
+    fn execute_ijmp() {
+        let mut vm = ATXMEGA128A4U.lock().unwrap();
+        fast_reset(&mut vm);
+
+        vm.core.eind = 0x01;
+        vm.core.write_z(0x1337);
+
+        let cmd = Ijmp;
+        cmd.execute(&mut vm).unwrap();
+
+        assert_eq!(vm.core.pc, 0x1337);
+        assert_eq!(vm.core.cycles, 2);
+    }
+
+    #[test]
+    fn execute_eijmp() {
+        let mut vm = ATXMEGA128A4U.lock().unwrap();
+        fast_reset(&mut vm);
+
+        vm.core.eind = 0x01;
+        vm.core.write_z(0x1337);
+
+        let cmd = Eijmp;
+        cmd.execute(&mut vm).unwrap();
+
+        assert_eq!(vm.core.pc, 0x11337);
+        assert_eq!(vm.core.cycles, 2);
+    }
+
+    #[test]
+    fn eind_io_register() {
+        let mut vm = ATXMEGA128A4U.lock().unwrap();
+        fast_reset(&mut vm);
+
+        let eind = vm.info.io_regs["EIND"];
+        vm.write_io(eind, 0x01);
+        assert_eq!(vm.core.eind, 0x01);
+        vm.core.eind = 0x00;
+        assert_eq!(vm.read_io(eind, true), 0x00);
+    }
+
+    #[test]
+    fn execute_lpm_string_table() {
+        let mut vm = ATXMEGA128A4U.lock().unwrap();
+        fast_reset(&mut vm);
+
+        vm.core.flash[0x200..0x20C].copy_from_slice(b"Hello\0World\0");
+        vm.core.rampz = 0x01;
+        vm.core.write_z(0x200);
+
+        let mut s = vec![];
+        loop {
+            LpmInc { d: 24 }.execute(&mut vm).unwrap();
+            match vm.core.read_reg(24) {
+                0 => break,
+                c => s.push(c),
+            }
+        }
+
+        assert_eq!(&s, b"Hello");
+        assert_eq!(vm.core.read_z(), 0x206);
+        assert_eq!(vm.core.rampz, 0x01);
+        assert_eq!(vm.core.cycles, 6 * 3);
+
+        Lpm { d: 25 }.execute(&mut vm).unwrap();
+        assert_eq!(vm.core.read_reg(25), b'W');
+        assert_eq!(vm.core.read_z(), 0x206);
+
+        vm.core.write_z(0x202);
+        Lpm0.execute(&mut vm).unwrap();
+        assert_eq!(vm.core.read_reg(0), b'l');
+        vm.core.rampz = 0x00;
+    }
+
+    #[test]
+    fn execute_lpm_wraps_z() {
+        let mut vm = ATXMEGA128A4U.lock().unwrap();
+        fast_reset(&mut vm);
+
+        vm.core.write_z(0xFFFF);
+        LpmInc { d: 24 }.execute(&mut vm).unwrap();
+
+        assert_eq!(vm.core.read_z(), 0x0000);
+        assert_eq!(vm.core.rampz, 0x00);
+    }
+
+    #[test]
+    fn execute_lpm_beyond_flash() {
+        // 16 KiB flash, Z=0x8000 reads address 0
+        let mut vm = AvrVm::new(&AvrVmInfo::from_name("atmega16"));
+        vm.core.flash[0x0000] = 0x42;
+        vm.core.write_z(0x8000);
+        Lpm { d: 24 }.execute(&mut vm).unwrap();
+        assert_eq!(vm.core.read_reg(24), 0x42);
+
+        // 136 KiB flash, RAMPZ:Z=0x30000 reads address 0xE000
+        let mut vm = ATXMEGA128A4U.lock().unwrap();
+        fast_reset(&mut vm);
+        vm.core.flash[0xE000] = 0x24;
+        vm.core.rampz = 0x03;
+        vm.core.write_z(0x0000);
+        Elpm { d: 24 }.execute(&mut vm).unwrap();
+        assert_eq!(vm.core.read_reg(24), 0x24);
+        vm.core.flash[0xE000] = 0x00;
+        vm.core.rampz = 0x00;
+    }
+
     #[test]
     fn execute_jmp() {
         let mut vm = ATXMEGA128A4U.lock().unwrap();
@@ -1071,4 +1554,599 @@ mod tests {
         cmd.execute(&mut vm).unwrap();
         assert_eq!(vm.core.t, true);
     }
-}
\ No newline at end of file
+
+    fn create_classic_vm() -> AvrVm {
+        let mut vm = AvrVm::new(&AvrVmInfo::from_name("atmega16"));
+        // NOPs with a CALL at 0x101
+        let mut flash = vec![0u8; 0x400];
+        flash[0x202] = 0x0E;
+        flash[0x203] = 0x94;
+        vm.write_flash(0, &flash);
+        vm
+    }
+
+    #[test]
+    fn execute_sbi_cbi_classic() {
+        let mut vm = create_classic_vm();
+
+        // PORTB: I/O address 0x18, data address 0x38
+        vm.write_io(0x38, 0b1000_0001);
+
+        Sbi { a: 0x18, b: 3 }.execute(&mut vm).unwrap();
+        assert_eq!(vm.read_io(0x38, true), 0b1000_1001);
+        assert_eq!(vm.core.cycles, 2);
+
+        Cbi { a: 0x18, b: 7 }.execute(&mut vm).unwrap();
+        assert_eq!(vm.read_io(0x38, true), 0b0000_1001);
+        assert_eq!(vm.core.cycles, 4);
+    }
+
+    #[test]
+    fn execute_sbi_cbi_xmega() {
+        let mut vm = ATXMEGA128A4U.lock().unwrap();
+        fast_reset(&mut vm);
+
+        // GPIO0: I/O address 0x00, data address 0x00
+        vm.write_io(0x00, 0b1000_0001);
+
+        Sbi { a: 0x00, b: 3 }.execute(&mut vm).unwrap();
+        assert_eq!(vm.read_io(0x00, true), 0b1000_1001);
+        assert_eq!(vm.core.cycles, 1);
+
+        Cbi { a: 0x00, b: 0 }.execute(&mut vm).unwrap();
+        assert_eq!(vm.read_io(0x00, true), 0b1000_1000);
+        assert_eq!(vm.core.cycles, 2);
+    }
+
+    #[test]
+    fn execute_sbic_sbis_classic() {
+        let mut vm = create_classic_vm();
+        vm.write_io(0x38, 0b0000_0100);
+
+        // no skip
+        vm.core.pc = 0x100;
+        Sbis { a: 0x18, b: 0 }.execute(&mut vm).unwrap();
+        assert_eq!(vm.core.pc, 0x101);
+        assert_eq!(vm.core.cycles, 1);
+
+        // skip 32-bit instruction
+        vm.core.pc = 0x100;
+        vm.core.cycles = 0;
+        Sbis { a: 0x18, b: 2 }.execute(&mut vm).unwrap();
+        assert_eq!(vm.core.pc, 0x103);
+        assert_eq!(vm.core.cycles, 3);
+
+        // skip 16-bit instruction
+        vm.core.pc = 0x102;
+        vm.core.cycles = 0;
+        Sbic { a: 0x18, b: 0 }.execute(&mut vm).unwrap();
+        assert_eq!(vm.core.pc, 0x104);
+        assert_eq!(vm.core.cycles, 2);
+
+        // register r24 must not be tested instead of the I/O register
+        vm.core.pc = 0x100;
+        vm.core.write_reg(0x18, 0x00);
+        Sbic { a: 0x18, b: 2 }.execute(&mut vm).unwrap();
+        assert_eq!(vm.core.pc, 0x101);
+    }
+
+    #[test]
+    fn execute_sbic_sbis_xmega() {
+        let mut vm = ATXMEGA128A4U.lock().unwrap();
+        fast_reset(&mut vm);
+        let old_pc = vm.core.pc;
+
+        vm.write_io(0x00, 0b0000_0100);
+        vm.write_flash(0x400, &[0x00, 0x00]);
+
+        vm.core.pc = 0x1FF;
+        Sbic { a: 0x00, b: 2 }.execute(&mut vm).unwrap();
+        assert_eq!(vm.core.pc, 0x200);
+        assert_eq!(vm.core.cycles, 2);
+
+        vm.core.pc = 0x1FF;
+        vm.core.cycles = 0;
+        Sbis { a: 0x00, b: 2 }.execute(&mut vm).unwrap();
+        assert_eq!(vm.core.pc, 0x201);
+        assert_eq!(vm.core.cycles, 3);
+
+        vm.core.pc = old_pc;
+    }
+
+    #[test]
+    fn execute_neg() {
+        let mut vm = ATXMEGA128A4U.lock().unwrap();
+        fast_reset(&mut vm);
+
+        vm.core.write_reg(10, 0x01);
+        Neg { d: 10 }.execute(&mut vm).unwrap();
+        assert_eq!(vm.core.read_reg(10), 0xFF);
+        assert_eq!(vm.core.carry, true);
+        assert_eq!(vm.core.h, true);
+        assert_eq!(vm.core.n, true);
+        assert_eq!(vm.core.v, false);
+        assert_eq!(vm.core.zero, false);
+        assert_eq!(vm.core.cycles, 1);
+
+        vm.core.write_reg(10, 0x80);
+        Neg { d: 10 }.execute(&mut vm).unwrap();
+        assert_eq!(vm.core.read_reg(10), 0x80);
+        assert_eq!(vm.core.carry, true);
+        assert_eq!(vm.core.h, false);
+        assert_eq!(vm.core.v, true);
+
+        vm.core.write_reg(10, 0x00);
+        Neg { d: 10 }.execute(&mut vm).unwrap();
+        assert_eq!(vm.core.read_reg(10), 0x00);
+        assert_eq!(vm.core.carry, false);
+        assert_eq!(vm.core.zero, true);
+    }
+
+    #[test]
+    fn execute_cpse() {
+        let mut vm = create_classic_vm();
+
+        vm.core.write_reg(1, 0x42);
+        vm.core.write_reg(2, 0x42);
+        vm.core.write_reg(3, 0x43);
+
+        vm.core.pc = 0x100;
+        Cpse { d: 1, r: 3 }.execute(&mut vm).unwrap();
+        assert_eq!(vm.core.pc, 0x101);
+        assert_eq!(vm.core.cycles, 1);
+
+        // skip 32-bit instruction
+        vm.core.pc = 0x100;
+        vm.core.cycles = 0;
+        Cpse { d: 1, r: 2 }.execute(&mut vm).unwrap();
+        assert_eq!(vm.core.pc, 0x103);
+        assert_eq!(vm.core.cycles, 3);
+
+        // skip 16-bit instruction
+        vm.core.pc = 0x102;
+        vm.core.cycles = 0;
+        Cpse { d: 1, r: 2 }.execute(&mut vm).unwrap();
+        assert_eq!(vm.core.pc, 0x104);
+        assert_eq!(vm.core.cycles, 2);
+    }
+
+    #[test]
+    fn execute_sbrc_sbrs() {
+        let mut vm = create_classic_vm();
+
+        vm.core.write_reg(31, 0b1000_0000);
+
+        // highest bit over 32-bit instruction
+        vm.core.pc = 0x100;
+        Sbrs { r: 31, b: 7 }.execute(&mut vm).unwrap();
+        assert_eq!(vm.core.pc, 0x103);
+        assert_eq!(vm.core.cycles, 3);
+
+        vm.core.pc = 0x100;
+        vm.core.cycles = 0;
+        Sbrc { r: 31, b: 7 }.execute(&mut vm).unwrap();
+        assert_eq!(vm.core.pc, 0x101);
+        assert_eq!(vm.core.cycles, 1);
+
+        // lowest bit over 16-bit instruction
+        vm.core.pc = 0x102;
+        vm.core.cycles = 0;
+        Sbrc { r: 31, b: 0 }.execute(&mut vm).unwrap();
+        assert_eq!(vm.core.pc, 0x104);
+        assert_eq!(vm.core.cycles, 2);
+
+        // r0 is a register like any other
+        vm.core.write_reg(0, 0b0000_0001);
+        vm.core.pc = 0x102;
+        Sbrs { r: 0, b: 0 }.execute(&mut vm).unwrap();
+        assert_eq!(vm.core.pc, 0x104);
+    }
+
+    /// reference model of FMUL/FMULS/FMULSU: (R1:R0, C, Z)
+    fn fmul_reference(a: u8, b: u8, signed_a: bool, signed_b: bool) -> (u16, bool, bool) {
+        let a = if signed_a { a as i8 as i32 } else { a as i32 };
+        let b = if signed_b { b as i8 as i32 } else { b as i32 };
+        let product = (a * b) as u32 & 0xFFFF;
+        let result = ((product << 1) & 0xFFFF) as u16;
+        (result, product & 0x8000 != 0, result == 0)
+    }
+
+    #[test]
+    fn execute_fmul_exhaustive() {
+        let mut vm = create_classic_vm();
+
+        let table: [(fn(u8, u8) -> Instruction, bool, bool); 3] = [
+            (|d, r| Fmul { d, r }, false, false),
+            (|d, r| Fmuls { d, r }, true, true),
+            (|d, r| Fmulsu { d, r }, true, false),
+        ];
+
+        for &(factory, signed_d, signed_r) in table.iter() {
+            let instr = factory(16, 23);
+            for a in 0..=255u8 {
+                for b in 0..=255u8 {
+                    vm.core.write_reg(16, a);
+                    vm.core.write_reg(23, b);
+                    vm.core.cycles = 0;
+                    instr.execute(&mut vm).unwrap();
+
+                    let (result, carry, zero) = fmul_reference(a, b, signed_d, signed_r);
+                    assert_eq!(vm.core.read_reg16(0), result, "{:?} 0x{:02x} 0x{:02x}", instr, a, b);
+                    assert_eq!(vm.core.carry, carry, "{:?} 0x{:02x} 0x{:02x}", instr, a, b);
+                    assert_eq!(vm.core.zero, zero, "{:?} 0x{:02x} 0x{:02x}", instr, a, b);
+                    assert_eq!(vm.core.cycles, 2);
+                }
+            }
+
+            // same register as both operands
+            vm.core.write_reg(17, 0xC0);
+            factory(17, 17).execute(&mut vm).unwrap();
+            assert_eq!(vm.core.read_reg16(0), fmul_reference(0xC0, 0xC0, signed_d, signed_r).0);
+        }
+    }
+
+    #[test]
+    fn execute_xch_las_lac_lat() {
+        let mut vm = ATXMEGA128A4U.lock().unwrap();
+        fast_reset(&mut vm);
+
+        let addr = vm.info.ram.start + 0x10;
+        vm.core.write_z(addr as u16);
+
+        vm.write_u8_noneeprom(addr, 0b1100_1100);
+        vm.core.write_reg(16, 0b1010_1010);
+        Xch { d: 16 }.execute(&mut vm).unwrap();
+        assert_eq!(vm.read_unchecked(addr, true), 0b1010_1010);
+        assert_eq!(vm.core.read_reg(16), 0b1100_1100);
+        assert_eq!(vm.core.cycles, 2);
+
+        vm.write_u8_noneeprom(addr, 0b1100_1100);
+        vm.core.write_reg(16, 0b1010_1010);
+        Las { d: 16 }.execute(&mut vm).unwrap();
+        assert_eq!(vm.read_unchecked(addr, true), 0b1110_1110);
+        assert_eq!(vm.core.read_reg(16), 0b1100_1100);
+
+        vm.write_u8_noneeprom(addr, 0b1100_1100);
+        vm.core.write_reg(16, 0b1010_1010);
+        Lac { d: 16 }.execute(&mut vm).unwrap();
+        assert_eq!(vm.read_unchecked(addr, true), 0b0100_0100);
+        assert_eq!(vm.core.read_reg(16), 0b1100_1100);
+
+        vm.write_u8_noneeprom(addr, 0b1100_1100);
+        vm.core.write_reg(16, 0b1010_1010);
+        Lat { d: 16 }.execute(&mut vm).unwrap();
+        assert_eq!(vm.read_unchecked(addr, true), 0b0110_0110);
+        assert_eq!(vm.core.read_reg(16), 0b1100_1100);
+    }
+
+    #[test]
+    fn execute_xch_classic() {
+        let mut vm = create_classic_vm();
+
+        assert_eq!(
+            Xch { d: 17 }.execute(&mut vm),
+            Err(CpuSignal::InvaildOpcode { opcode: 0x9314 }));
+        assert_eq!(
+            Lat { d: 31 }.execute(&mut vm),
+            Err(CpuSignal::InvaildOpcode { opcode: 0x93F7 }));
+        assert_eq!(vm.core.cycles, 0);
+    }
+
+    #[test]
+    fn execute_des() {
+        let mut vm = ATXMEGA128A4U.lock().unwrap();
+        fast_reset(&mut vm);
+
+        let key = 0x133457799BBCDFF1u64;
+        let plain = 0x0123456789ABCDEFu64;
+        let cipher = 0x85E813540F0AB405u64;
+
+        vm.core.regs[0..8].copy_from_slice(&plain.to_le_bytes());
+        vm.core.regs[8..16].copy_from_slice(&key.to_le_bytes());
+        vm.core.h = false;
+        for k in 0..16 {
+            Des { k }.execute(&mut vm).unwrap();
+        }
+        assert_eq!(&vm.core.regs[0..8], &cipher.to_le_bytes());
+        assert_eq!(&vm.core.regs[8..16], &key.to_le_bytes());
+        // first DES needs an extra cycle
+        assert_eq!(vm.core.cycles, 17);
+
+        vm.core.cycles = 0;
+        Nop.execute(&mut vm).unwrap();
+        vm.core.h = true;
+        for k in 0..16 {
+            Des { k }.execute(&mut vm).unwrap();
+        }
+        assert_eq!(&vm.core.regs[0..8], &plain.to_le_bytes());
+        assert_eq!(vm.core.cycles, 18);
+    }
+
+    #[test]
+    fn execute_wdr() {
+        let mut vm = ATXMEGA128A4U.lock().unwrap();
+        fast_reset(&mut vm);
+        vm.core.cycles = 1234;
+
+        Wdr.execute(&mut vm).unwrap();
+        assert_eq!(vm.core.watchdog, 1235);
+        assert_eq!(vm.core.cycles, 1235);
+    }
+
+    #[test]
+    fn execute_reduced_core() {
+        let mut vm = AvrVm::new(&AvrVmInfo::from_name("attiny10"));
+        assert!(vm.info.tiny);
+        // mul r16, r17; add r16, r17
+        vm.write_flash(0, &[0x01, 0x9f, 0x01, 0x0f]);
+
+        assert_eq!(vm.step(), Err(CpuSignal::InvaildOpcode { opcode: 0x9f01 }));
+
+        vm.core.pc = 1;
+        vm.core.regs[16] = 1;
+        vm.core.regs[17] = 2;
+        vm.step().unwrap();
+        assert_eq!(vm.core.regs[16], 3);
+
+        Sts { r: 16, k: 0x40 }.execute(&mut vm).unwrap();
+        Lds { d: 17, k: 0x40 }.execute(&mut vm).unwrap();
+        assert_eq!(vm.core.regs[17], 3);
+    }
+
+    #[test]
+    fn execute_ld_mapped_flash() {
+        let mut vm = AvrVm::new(&AvrVmInfo::from_name("attiny10"));
+        vm.write_flash(0x3FE, &[0xAB, 0xCD]);
+        vm.core.write_z(0x43FE);
+
+        LdZ { d: 16, zop: RegIncDec::Inc }.execute(&mut vm).unwrap();
+        assert_eq!(vm.core.regs[16], 0xAB);
+        assert_eq!(vm.core.read_z(), 0x43FF);
+        assert_eq!(vm.core.cycles, 3);
+
+        // mapped flash is read-only
+        StZ { r: 16, zop: RegIncDec::Unchanged }.execute(&mut vm).unwrap();
+        assert_eq!(vm.read_unchecked(0x43FF, true), 0xCD);
+        assert_eq!(vm.core.flash[0x3FF], 0xCD);
+    }
+
+    #[test]
+    fn cycles_per_arch() {
+        let mut classic = create_classic_vm();
+        let mut xmega = ATXMEGA128A4U.lock().unwrap();
+        fast_reset(&mut xmega);
+        classic.core.sp = classic.info.ram.end - 1;
+        let xmega_ram = xmega.info.ram.start as u16;
+        xmega.core.rampx = 0;
+        classic.core.write_x(classic.info.ram.start as u16);
+        xmega.core.write_x(xmega_ram);
+
+        let ld = LdX { d: 16, xop: RegIncDec::Unchanged };
+        ld.execute(&mut classic).unwrap();
+        ld.execute(&mut xmega).unwrap();
+        assert_eq!(classic.core.cycles, 2);
+        assert_eq!(xmega.core.cycles, 2);
+
+        let ld = LdX { d: 16, xop: RegIncDec::Dec };
+        classic.core.write_x(classic.info.ram.start as u16 + 1);
+        xmega.core.write_x(xmega_ram + 1);
+        ld.execute(&mut classic).unwrap();
+        ld.execute(&mut xmega).unwrap();
+        assert_eq!(classic.core.cycles, 2 + 3);
+        assert_eq!(xmega.core.cycles, 2 + 3);
+
+        Push { r: 16 }.execute(&mut classic).unwrap();
+        Push { r: 16 }.execute(&mut xmega).unwrap();
+        assert_eq!(classic.core.cycles, 5 + 2);
+        assert_eq!(xmega.core.cycles, 5 + 1);
+
+        classic.core.write_y(classic.info.ram.start as u16);
+        StdY { q: 1, r: 16 }.execute(&mut classic).unwrap();
+        assert_eq!(classic.core.cycles, 7 + 2);
+    }
+
+    #[test]
+    fn memory_timing() {
+        let mut vm = create_classic_vm();
+        vm.core.write_x(0x38);
+
+        LdX { d: 16, xop: RegIncDec::Unchanged }.execute(&mut vm).unwrap();
+        assert_eq!(vm.core.cycles, 2);
+
+        // I/O wait states
+        vm.info.memory_timing.load.io = 2;
+        vm.info.memory_timing.store.io = 1;
+        LdX { d: 16, xop: RegIncDec::Unchanged }.execute(&mut vm).unwrap();
+        assert_eq!(vm.core.cycles, 2 + 4);
+        StX { r: 16, xop: RegIncDec::Unchanged }.execute(&mut vm).unwrap();
+        assert_eq!(vm.core.cycles, 2 + 4 + 3);
+    }
+
+    #[test]
+    fn pointer_wrap_xmega() {
+        let mut vm = ATxmega128A4U.create_vm();
+
+        // RAMPX is ignored without data memory beyond 64 KiB
+        vm.core.write_x(0xFFFF);
+        LdX { d: 16, xop: RegIncDec::Inc }.execute(&mut vm).unwrap();
+        assert_eq!(vm.core.read_x(), 0x0000);
+        assert_eq!(vm.core.rampx, 0x00);
+        vm.core.rampx = 0x01;
+        assert_eq!(vm.core.read_ramped_x(), 0x0000);
+        vm.core.rampx = 0x00;
+
+        // external memory of 16 MiB
+        vm.core.pointer_mask = 0xFF_FFFF;
+
+        // post-increment carries into RAMPX
+        vm.core.write_x(0xFFFF);
+        LdX { d: 16, xop: RegIncDec::Inc }.execute(&mut vm).unwrap();
+        assert_eq!(vm.core.read_x(), 0x0000);
+        assert_eq!(vm.core.rampx, 0x01);
+
+        // pre-decrement borrows from RAMPY
+        vm.core.rampy = 0x01;
+        vm.core.write_y(0x0000);
+        LdY { d: 16, yop: RegIncDec::Dec }.execute(&mut vm).unwrap();
+        assert_eq!(vm.core.read_y(), 0xFFFF);
+        assert_eq!(vm.core.rampy, 0x00);
+
+        // 24-bit wrap-around
+        vm.core.write_z(0x0000);
+        LdZ { d: 16, zop: RegIncDec::Dec }.execute(&mut vm).unwrap();
+        assert_eq!(vm.core.read_z(), 0xFFFF);
+        assert_eq!(vm.core.rampz, 0xFF);
+        LdZ { d: 16, zop: RegIncDec::Inc }.execute(&mut vm).unwrap();
+        assert_eq!(vm.core.read_ramped_z(), 0x000000);
+
+        // RAMPY selects data
+        let ram = vm.info.ram.start as u16;
+        vm.write(ram as usize + 1, 0x42).unwrap();
+        vm.core.rampy = 0x00;
+        vm.core.write_y(ram);
+        LddY { d: 17, q: 1 }.execute(&mut vm).unwrap();
+        assert_eq!(vm.core.regs[17], 0x42);
+        vm.core.rampy = 0x01;
+        LddY { d: 17, q: 1 }.execute(&mut vm).unwrap();
+        assert_eq!(vm.core.regs[17], 0x00);
+
+        // RAMPD selects data of LDS
+        vm.core.rampd = 0x01;
+        assert_eq!(vm.core.ramped_addr(0x1234), 0x11234);
+    }
+
+    #[test]
+    fn pointer_mask() {
+        assert_eq!(AvrVmInfo::from_name("atmega16").pointer_mask(), 0xFFFF);
+        assert_eq!(AvrVmInfo::from_name("attiny10").pointer_mask(), 0xFFFF);
+
+        let mut info = AvrVmInfo::from_name("atxmega128a4u");
+        assert_eq!(info.pointer_mask(), 0xFFFF);
+        info.ram.end = 0x1_2000;
+        assert_eq!(info.pointer_mask(), 0x1_FFFF);
+        info.ram = 0x60..0x100;
+        info.eeprom = 0..0;
+        assert_eq!(info.pointer_mask(), 0xFF);
+    }
+
+    #[test]
+    fn pointer_wrap_classic() {
+        let mut vm = create_classic_vm();
+        let ram = vm.info.ram.start as u16;
+        vm.write(ram as usize, 0x42).unwrap();
+
+        // RAMPZ is not used for data
+        vm.core.rampz = 0x01;
+        vm.core.write_z(ram);
+        LdZ { d: 16, zop: RegIncDec::Unchanged }.execute(&mut vm).unwrap();
+        assert_eq!(vm.core.regs[16], 0x42);
+
+        vm.core.write_x(0xFFFF);
+        LdX { d: 16, xop: RegIncDec::Inc }.execute(&mut vm).unwrap();
+        assert_eq!(vm.core.read_x(), 0x0000);
+        assert_eq!(vm.core.rampx, 0x00);
+
+        LdX { d: 16, xop: RegIncDec::Dec }.execute(&mut vm).unwrap();
+        assert_eq!(vm.core.read_x(), 0xFFFF);
+
+        // data space of 256 bytes: only XL is changed
+        vm.core.pointer_mask = 0xFF;
+        vm.core.write_x(0x12FF);
+        LdX { d: 16, xop: RegIncDec::Inc }.execute(&mut vm).unwrap();
+        assert_eq!(vm.core.read_x(), 0x1200);
+    }
+
+    #[test]
+    fn memory_fault_signal() {
+        let mut vm = create_classic_vm();
+        vm.memory_fault_policy = MemoryFaultPolicy::Signal;
+        // LD r16, X; ST X, r16; LD r16, X
+        vm.write_flash(0, &[0x0C, 0x91, 0x0C, 0x93, 0x0C, 0x91]);
+
+        vm.core.write_x(0xF000);
+        assert_eq!(vm.step(), Err(CpuSignal::MemoryFault { addr: 0xF000, write: false }));
+        assert_eq!(vm.core.pc, 1);
+        assert_eq!(vm.step(), Err(CpuSignal::MemoryFault { addr: 0xF000, write: true }));
+
+        // fault is reported only once
+        vm.core.write_x(vm.info.ram.start as u16);
+        assert_eq!(vm.step(), Ok(()));
+
+        // debugger views do not fault
+        assert_eq!(vm.read_unchecked(0xF000, true), 0);
+        assert_eq!(vm.step(), Ok(()));
+    }
+
+    #[test]
+    fn memory_fault_log() {
+        let mut vm = create_classic_vm();
+        vm.memory_fault_policy = MemoryFaultPolicy::Log;
+        // ST X, r16; LD r17, X
+        vm.write_flash(0, &[0x0C, 0x93, 0x1C, 0x91]);
+
+        vm.core.write_x(0xF000);
+        vm.core.regs[16] = 0x42;
+        vm.core.regs[17] = 0xFF;
+        assert_eq!(vm.step(), Ok(()));
+        assert_eq!(vm.step(), Ok(()));
+        assert_eq!(vm.core.regs[17], 0x00);
+    }
+
+    #[test]
+    fn stack_guard() {
+        let mut vm = create_classic_vm();
+        let ramend = vm.info.ram.end - 1;
+        vm.stack_guard = Some(StackGuard::new(ramend - 1, ramend));
+        // PUSH r16 (3x); POP r16 (4x)
+        vm.write_flash(0, &[0x0F, 0x93, 0x0F, 0x93, 0x0F, 0x93,
+            0x0F, 0x91, 0x0F, 0x91, 0x0F, 0x91, 0x0F, 0x91]);
+        vm.core.sp = ramend;
+
+        assert_eq!(vm.step(), Ok(()));
+        assert_eq!(vm.step(), Ok(()));
+        assert_eq!(vm.step(), Err(CpuSignal::StackOverflow { pc: 2, sp: ramend - 3 }));
+
+        assert_eq!(vm.step(), Ok(()));
+        assert_eq!(vm.step(), Ok(()));
+        assert_eq!(vm.step(), Ok(()));
+        assert_eq!(vm.step(), Err(CpuSignal::StackUnderflow { pc: 6, sp: ramend + 1 }));
+
+        // no checks without guard
+        vm.stack_guard = None;
+        vm.core.pc = 0;
+        vm.core.sp = ramend - 5;
+        assert_eq!(vm.step(), Ok(()));
+    }
+
+    #[test]
+    fn stack_wrap() {
+        // SP is 0 after reset on classic devices
+        let mut vm = create_classic_vm();
+        // CALL 0x0000; POP r16 (3x)
+        vm.write_flash(0, &[0x0E, 0x94, 0x00, 0x00,
+            0x0F, 0x91, 0x0F, 0x91, 0x0F, 0x91]);
+        vm.core.sp = 0;
+
+        // without a guard SP and the pushed bytes wrap around
+        assert_eq!(vm.step(), Ok(()));
+        assert_eq!(vm.core.sp, 0xFFFE);
+        assert_eq!(vm.read_unchecked(0x0000, true), 0x02);
+
+        vm.core.pc = 2;
+        assert_eq!(vm.step(), Ok(()));
+        assert_eq!(vm.step(), Ok(()));
+        assert_eq!(vm.core.sp, 0x0000);
+        assert_eq!(vm.core.read_reg(16), 0x02);
+
+        // the wrap is a fault with a guard
+        let ramend = vm.info.ram.end - 1;
+        vm.stack_guard = Some(StackGuard::new(vm.info.ram.start, ramend));
+        vm.core.pc = 0;
+        assert_eq!(vm.step(), Err(CpuSignal::StackOverflow { pc: 0, sp: 0xFFFE }));
+        assert_eq!(vm.core.pc, 0);
+
+        vm.core.sp = 0xFFFF;
+        vm.core.pc = 2;
+        assert_eq!(vm.step(), Err(CpuSignal::StackUnderflow { pc: 2, sp: 0 }));
+    }
+}
@@ -0,0 +1,273 @@
+use std::collections::BTreeMap;
+use core::AvrCoreState;
+
+
+/// interrupt level of XMEGA devices
+///
+/// Classic AVR devices have no interrupt levels.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum InterruptLevel {
+    Off, Low, Medium, High
+}
+
+impl InterruptLevel {
+    /// level from the two bit INTLVL group fields of XMEGA peripherals
+    pub fn from_bits(bits: u8) -> InterruptLevel {
+        match bits & 0b11 {
+            0 => InterruptLevel::Off,
+            1 => InterruptLevel::Low,
+            2 => InterruptLevel::Medium,
+            _ => InterruptLevel::High,
+        }
+    }
+}
+
+
+/// pending interrupt requests of peripherals
+///
+/// Peripherals raise interrupts by vector number. Which one is served next is
+/// decided by the `InterruptController` of the vm.
+#[derive(Clone, PartialEq)]
+pub struct InterruptRequests {
+    pending: BTreeMap<usize, InterruptLevel>
+}
+
+impl InterruptRequests {
+    pub fn new() -> InterruptRequests {
+        InterruptRequests { pending: BTreeMap::new() }
+    }
+
+    /// request interrupt `vector`
+    pub fn raise(&mut self, vector: usize) {
+        self.raise_level(vector, InterruptLevel::Low);
+    }
+
+    /// request interrupt `vector` with level `level`
+    ///
+    /// level `Off` withdraws the request.
+    pub fn raise_level(&mut self, vector: usize, level: InterruptLevel) {
+        if level == InterruptLevel::Off {
+            self.clear(vector);
+        } else {
+            self.pending.insert(vector, level);
+        }
+    }
+
+    /// withdraw request for interrupt `vector`
+    pub fn clear(&mut self, vector: usize) {
+        self.pending.remove(&vector);
+    }
+
+    /// raise or clear interrupt `vector`
+    pub fn set(&mut self, vector: usize, value: bool) {
+        if value {
+            self.raise(vector);
+        } else {
+            self.clear(vector);
+        }
+    }
+
+    /// raise interrupt `vector` with `level` or clear it
+    pub fn set_level(&mut self, vector: usize, value: bool, level: InterruptLevel) {
+        if value {
+            self.raise_level(vector, level);
+        } else {
+            self.clear(vector);
+        }
+    }
+
+    pub fn is_pending(&self, vector: usize) -> bool {
+        self.pending.contains_key(&vector)
+    }
+
+    pub fn has_pending(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    pub fn level(&self, vector: usize) -> Option<InterruptLevel> {
+        self.pending.get(&vector).cloned()
+    }
+
+    /// pending interrupt with the lowest vector number
+    pub fn next(&self) -> Option<usize> {
+        self.pending.keys().next().cloned()
+    }
+
+    /// pending interrupts with levels ordered by vector number
+    pub fn iter(&self) -> impl Iterator<Item=(usize, InterruptLevel)> + Clone + '_ {
+        self.pending.iter().map(|(&vector, &level)| (vector, level))
+    }
+}
+
+
+/// arbitration of pending interrupts
+pub trait InterruptController {
+    /// interrupt to serve next or `None` if no request can be served
+    ///
+    /// only called if the global interrupt flag is set.
+    fn select(&mut self, core: &AvrCoreState) -> Option<usize>;
+
+    /// cpu jumps to interrupt `vector`
+    fn acknowledge(&mut self, core: &mut AvrCoreState, vector: usize);
+
+    /// cpu returns from interrupt
+    fn reti(&mut self, core: &mut AvrCoreState);
+
+    /// start of interrupt vector table in words
+    fn vector_table(&self) -> usize { 0 }
+}
+
+
+/// interrupt handling of classic AVR cores
+///
+/// The interrupt with the lowest vector number wins. The I flag is cleared
+/// when entering an interrupt and set again with RETI.
+pub struct FixedPriorityController;
+
+impl InterruptController for FixedPriorityController {
+    fn select(&mut self, core: &AvrCoreState) -> Option<usize> {
+        core.irq.next()
+    }
+
+    fn acknowledge(&mut self, core: &mut AvrCoreState, _vector: usize) {
+        core.interrupt = false;
+    }
+
+    fn reti(&mut self, core: &mut AvrCoreState) {
+        core.interrupt = true;
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use instruction_set::Instruction::*;
+    use models::test_util::create_vm;
+
+    #[test]
+    fn next_by_priority() {
+        let mut irq = InterruptRequests::new();
+        assert_eq!(irq.next(), None);
+
+        irq.raise(25);
+        irq.raise(14);
+        assert_eq!(irq.next(), Some(14));
+
+        irq.clear(14);
+        assert_eq!(irq.next(), Some(25));
+        assert!(irq.is_pending(25));
+        assert!(!irq.is_pending(14));
+
+        irq.raise_level(25, InterruptLevel::Off);
+        assert!(!irq.has_pending());
+    }
+
+    #[test]
+    fn dispatch_3byte_pc() {
+        let mut vm = create_vm("atxmega128a4u");
+        let old_sp = vm.core.sp;
+
+        vm.core.interrupt = true;
+        vm.core.irq.raise(25);
+        vm.step().unwrap();
+
+        assert_eq!(vm.core.pc, 25 * 2);
+        assert_eq!(vm.core.sp, old_sp - 3);
+        assert_eq!(vm.read_unchecked(old_sp - 0, true), 0x00);
+        assert_eq!(vm.read_unchecked(old_sp - 1, true), 0x01);
+        assert_eq!(vm.read_unchecked(old_sp - 2, true), 0x00);
+        assert_eq!(vm.core.interrupt, false);
+        assert_eq!(vm.core.cycles, 5);
+        assert!(!vm.core.irq.is_pending(25));
+    }
+
+    #[test]
+    fn dispatch_2byte_pc() {
+        let mut vm = create_vm("atmega8");
+        let old_sp = vm.core.sp;
+
+        vm.core.interrupt = true;
+        vm.core.irq.raise(3);
+        vm.step().unwrap();
+
+        // ATmega8 uses one word vectors
+        assert_eq!(vm.core.pc, 3);
+        assert_eq!(vm.core.sp, old_sp - 2);
+        assert_eq!(vm.read_unchecked(old_sp - 0, true), 0x00);
+        assert_eq!(vm.read_unchecked(old_sp - 1, true), 0x01);
+        assert_eq!(vm.core.cycles, 4);
+    }
+
+    #[test]
+    fn dispatch_2word_vectors() {
+        let mut vm = create_vm("atmega16");
+        let old_sp = vm.core.sp;
+
+        vm.core.interrupt = true;
+        vm.core.irq.raise(3);
+        vm.step().unwrap();
+
+        assert_eq!(vm.core.pc, 3 * 2);
+        assert_eq!(vm.core.sp, old_sp - 2);
+        assert_eq!(vm.read_unchecked(old_sp - 0, true), 0x00);
+        assert_eq!(vm.read_unchecked(old_sp - 1, true), 0x01);
+        assert_eq!(vm.core.interrupt, false);
+        assert_eq!(vm.core.cycles, 4);
+        assert!(!vm.core.irq.is_pending(3));
+    }
+
+    #[test]
+    fn return_from_interrupt() {
+        let mut vm = create_vm("atmega16");
+        let old_sp = vm.core.sp;
+
+        vm.core.interrupt = true;
+        vm.core.irq.raise(3);
+        vm.step().unwrap();
+        vm.return_from_interrupt();
+
+        assert_eq!(vm.core.pc, 0x100);
+        assert_eq!(vm.core.sp, old_sp);
+        assert_eq!(vm.core.interrupt, true);
+    }
+
+    #[test]
+    fn no_dispatch_if_disabled() {
+        let mut vm = create_vm("atxmega128a4u");
+
+        vm.core.interrupt = false;
+        vm.core.irq.raise(25);
+        vm.step().unwrap();
+
+        assert_eq!(vm.core.pc, 0x101);
+        assert!(vm.core.irq.is_pending(25));
+    }
+
+    #[test]
+    fn dispatch_delayed_one_instruction() {
+        let mut vm = create_vm("atxmega128a4u");
+
+        vm.core.interrupt = true;
+        vm.core.irq_delay = true;
+        vm.core.irq.raise(25);
+
+        vm.step().unwrap();
+        assert_eq!(vm.core.pc, 0x101);
+
+        vm.step().unwrap();
+        assert_eq!(vm.core.pc, 25 * 2);
+    }
+
+    #[test]
+    fn cli_blocks_dispatch() {
+        let mut vm = create_vm("atxmega128a4u");
+
+        vm.core.interrupt = true;
+        Cli.execute(&mut vm).unwrap();
+        vm.core.irq.raise(25);
+        vm.step().unwrap();
+
+        assert_eq!(vm.core.pc, 0x102);
+    }
+}
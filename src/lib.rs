@@ -23,3 +23,7 @@ pub mod bits;
 pub mod bytelevel;
 pub mod emulator;
 pub mod internals;
+pub mod interrupts;
+pub mod des;
+pub mod arch;
+pub mod fuses;
@@ -0,0 +1,1167 @@
+use core::AvrVm;
+use core::AvrCoreState;
+use std::cmp;
+use std::sync::Arc;
+use std::sync::Mutex;
+use byte_convert::bit_at;
+use models::sleep::ClockDomain;
+use models::sleep::SleepMode;
+
+
+// TIFR and TIMSK bits
+const TOV0: u8 = 1 << 0;
+const OCF0: u8 = 1 << 1;
+const TOV1: u8 = 1 << 2;
+const OCF1B: u8 = 1 << 3;
+const OCF1A: u8 = 1 << 4;
+const ICF1: u8 = 1 << 5;
+const TOV2: u8 = 1 << 6;
+const OCF2: u8 = 1 << 7;
+
+// SFIOR bits
+const PSR10: u8 = 1 << 0;
+const PSR2: u8 = 1 << 1;
+
+// ASSR bits, the update busy flags are always clear
+const AS2: u8 = 1 << 3;
+
+/// input capture edge select bit of TCCR1B
+const ICES1: u8 = 6;
+
+/// interrupt vector names by TIFR bit
+static VECTOR_NAMES: [&str; 8] = [
+    "TIMER0_OVF", "TIMER0_COMP", "TIMER1_OVF", "TIMER1_COMPB",
+    "TIMER1_COMPA", "TIMER1_CAPT", "TIMER2_OVF", "TIMER2_COMP"
+];
+
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Register {
+    Tccr0, Tcnt0, Ocr0,
+    Tccr1a, Tccr1b, Tcnt1l, Tcnt1h, Ocr1al, Ocr1ah, Ocr1bl, Ocr1bh, Icr1l, Icr1h,
+    Tccr2, Tcnt2, Ocr2, Assr,
+    Timsk, Tifr, Sfior,
+}
+
+static REGISTERS: [(&str, Register); 20] = [
+    ("TCCR0", Register::Tccr0), ("TCNT0", Register::Tcnt0), ("OCR0", Register::Ocr0),
+    ("TCCR1A", Register::Tccr1a), ("TCCR1B", Register::Tccr1b),
+    ("TCNT1L", Register::Tcnt1l), ("TCNT1H", Register::Tcnt1h),
+    ("OCR1AL", Register::Ocr1al), ("OCR1AH", Register::Ocr1ah),
+    ("OCR1BL", Register::Ocr1bl), ("OCR1BH", Register::Ocr1bh),
+    ("ICR1L", Register::Icr1l), ("ICR1H", Register::Icr1h),
+    ("TCCR2", Register::Tccr2), ("TCNT2", Register::Tcnt2), ("OCR2", Register::Ocr2),
+    ("ASSR", Register::Assr),
+    ("TIMSK", Register::Timsk), ("TIFR", Register::Tifr), ("SFIOR", Register::Sfior),
+];
+
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Waveform {
+    Normal,
+    Ctc,
+    FastPwm,
+    PhaseCorrect,
+    PhaseFrequencyCorrect,
+}
+
+/// source of the TOP value
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Top {
+    Max,
+    Fixed(u16),
+    Ocra,
+    Icr,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum TimerKind {
+    Timer0,
+    Timer1,
+    Timer2,
+}
+
+
+/// counter and compare units of one timer
+#[derive(Clone)]
+struct Counter {
+    kind: TimerKind,
+
+    /// number of output compare units
+    channels: usize,
+
+    /// TCCRn or TCCR1B:TCCR1A
+    control: u16,
+
+    cnt: u16,
+    ocr: [u16; 2],
+
+    /// OCRnx as written by the cpu, copied to `ocr` at update in PWM modes
+    ocr_buffer: [u16; 2],
+
+    icr: u16,
+
+    /// counting down in phase correct modes
+    down: bool,
+
+    /// levels of the OCnx outputs
+    outputs: u8,
+
+    /// cycle count of the timer clock domain the counter is updated to
+    cycles: u64,
+}
+
+impl Counter {
+    fn new(kind: TimerKind, channels: usize, cycles: u64) -> Counter {
+        Counter {
+            kind,
+            channels,
+            control: 0,
+            cnt: 0,
+            ocr: [0; 2],
+            ocr_buffer: [0; 2],
+            icr: 0,
+            down: false,
+            outputs: 0,
+            cycles,
+        }
+    }
+
+    fn max(&self) -> u16 {
+        if self.kind == TimerKind::Timer1 { 0xFFFF } else { 0xFF }
+    }
+
+    fn overflow_flag(&self) -> u8 {
+        match self.kind {
+            TimerKind::Timer0 => TOV0,
+            TimerKind::Timer1 => TOV1,
+            TimerKind::Timer2 => TOV2,
+        }
+    }
+
+    fn compare_flag(&self, channel: usize) -> u8 {
+        match self.kind {
+            TimerKind::Timer0 => OCF0,
+            TimerKind::Timer1 => if channel == 0 { OCF1A } else { OCF1B },
+            TimerKind::Timer2 => OCF2,
+        }
+    }
+
+    /// TIFR bits of this timer
+    fn flags(&self) -> u8 {
+        match self.kind {
+            TimerKind::Timer0 if self.channels == 0 => TOV0,
+            TimerKind::Timer0 => TOV0 | OCF0,
+            TimerKind::Timer1 => TOV1 | OCF1A | OCF1B | ICF1,
+            TimerKind::Timer2 => TOV2 | OCF2,
+        }
+    }
+
+    fn clock_select(&self) -> u16 {
+        if self.kind == TimerKind::Timer1 { (self.control >> 8) & 0b111 } else { self.control & 0b111 }
+    }
+
+    /// prescaler division factor or `None` if stopped or clocked by an external pin
+    fn prescaler(&self) -> Option<u64> {
+        if self.kind == TimerKind::Timer2 {
+            [None, Some(1), Some(8), Some(32), Some(64), Some(128), Some(256), Some(1024)]
+                [self.clock_select() as usize]
+        } else {
+            [None, Some(1), Some(8), Some(64), Some(256), Some(1024), None, None]
+                [self.clock_select() as usize]
+        }
+    }
+
+    /// waveform generation mode (WGM bits)
+    fn wgm(&self) -> u16 {
+        match self.kind {
+            TimerKind::Timer1 => (self.control & 0b11) | ((self.control >> 9) & 0b1100),
+            _ if self.channels == 0 => 0,
+            _ => ((self.control >> 6) & 0b01) | ((self.control >> 2) & 0b10),
+        }
+    }
+
+    fn mode(&self) -> (Waveform, Top) {
+        if self.kind != TimerKind::Timer1 {
+            return match self.wgm() {
+                1 => (Waveform::PhaseCorrect, Top::Max),
+                2 => (Waveform::Ctc, Top::Ocra),
+                3 => (Waveform::FastPwm, Top::Max),
+                _ => (Waveform::Normal, Top::Max),
+            };
+        }
+
+        match self.wgm() {
+            1 => (Waveform::PhaseCorrect, Top::Fixed(0x00FF)),
+            2 => (Waveform::PhaseCorrect, Top::Fixed(0x01FF)),
+            3 => (Waveform::PhaseCorrect, Top::Fixed(0x03FF)),
+            4 => (Waveform::Ctc, Top::Ocra),
+            5 => (Waveform::FastPwm, Top::Fixed(0x00FF)),
+            6 => (Waveform::FastPwm, Top::Fixed(0x01FF)),
+            7 => (Waveform::FastPwm, Top::Fixed(0x03FF)),
+            8 => (Waveform::PhaseFrequencyCorrect, Top::Icr),
+            9 => (Waveform::PhaseFrequencyCorrect, Top::Ocra),
+            10 => (Waveform::PhaseCorrect, Top::Icr),
+            11 => (Waveform::PhaseCorrect, Top::Ocra),
+            12 => (Waveform::Ctc, Top::Icr),
+            14 => (Waveform::FastPwm, Top::Icr),
+            15 => (Waveform::FastPwm, Top::Ocra),
+            // normal and reserved mode
+            _ => (Waveform::Normal, Top::Max),
+        }
+    }
+
+    fn top_value(&self, top: Top) -> u16 {
+        match top {
+            Top::Max => self.max(),
+            Top::Fixed(value) => value,
+            Top::Ocra => self.ocr[0],
+            Top::Icr => self.icr,
+        }
+    }
+
+    fn is_pwm(&self) -> bool {
+        let waveform = self.mode().0;
+        waveform != Waveform::Normal && waveform != Waveform::Ctc
+    }
+
+    /// compare output mode (COMnx bits)
+    fn com(&self, channel: usize) -> u16 {
+        if self.kind == TimerKind::Timer1 {
+            (self.control >> (6 - 2 * channel)) & 0b11
+        } else {
+            (self.control >> 4) & 0b11
+        }
+    }
+
+    fn write_ocr(&mut self, channel: usize, value: u16) {
+        self.ocr_buffer[channel] = value;
+        if !self.is_pwm() {
+            self.ocr[channel] = value;
+        }
+    }
+
+    /// copy double buffered compare values
+    fn update(&mut self) {
+        self.ocr = self.ocr_buffer;
+    }
+
+    /// count once, returns set TIFR flags
+    fn tick(&mut self) -> u8 {
+        let (waveform, top) = self.mode();
+        let top_value = self.top_value(top);
+        let max = self.max();
+        let mut flags = 0;
+
+        match waveform {
+            Waveform::PhaseCorrect | Waveform::PhaseFrequencyCorrect => {
+                if self.down {
+                    self.cnt = self.cnt.saturating_sub(1);
+                    if self.cnt == 0 {
+                        self.down = false;
+                        flags |= self.overflow_flag();
+                        if waveform == Waveform::PhaseFrequencyCorrect {
+                            self.update();
+                        }
+                    }
+                } else {
+                    self.cnt = self.cnt.wrapping_add(1) & max;
+                    if self.cnt >= top_value {
+                        self.cnt = top_value;
+                        self.down = true;
+                        if top == Top::Icr {
+                            flags |= ICF1;
+                        }
+                        if waveform == Waveform::PhaseCorrect {
+                            self.update();
+                        }
+                    }
+                }
+            }
+            _ => {
+                if self.cnt == top_value || self.cnt == max {
+                    if waveform == Waveform::FastPwm || self.cnt == max {
+                        flags |= self.overflow_flag();
+                    }
+                    self.cnt = 0;
+                    if waveform == Waveform::FastPwm {
+                        self.update();
+                        self.bottom_outputs();
+                    }
+                } else {
+                    self.cnt += 1;
+                    if self.cnt == top_value && top == Top::Icr {
+                        flags |= ICF1;
+                    }
+                }
+            }
+        }
+
+        for channel in 0..self.channels {
+            if self.cnt == self.ocr[channel] {
+                flags |= self.compare_flag(channel);
+                self.compare_output(waveform, channel);
+            }
+        }
+        flags
+    }
+
+    /// fast PWM outputs at BOTTOM
+    fn bottom_outputs(&mut self) {
+        for channel in 0..self.channels {
+            match self.com(channel) {
+                2 => self.outputs |= 1 << channel,
+                3 => self.outputs &= !(1 << channel),
+                _ => (),
+            }
+        }
+    }
+
+    fn compare_output(&mut self, waveform: Waveform, channel: usize) {
+        let output = 1 << channel;
+        let toggle = match waveform {
+            Waveform::Normal | Waveform::Ctc => true,
+            // only OC1A supports toggle in PWM modes
+            _ => self.kind == TimerKind::Timer1 && channel == 0,
+        };
+        // set instead of clear when counting down in phase correct modes
+        let invert = self.down && waveform != Waveform::FastPwm;
+
+        match self.com(channel) {
+            1 if toggle => self.outputs ^= output,
+            2 if invert => self.outputs |= output,
+            2 => self.outputs &= !output,
+            3 if invert => self.outputs &= !output,
+            3 => self.outputs |= output,
+            _ => (),
+        }
+    }
+
+    /// bring counter up to cycle count `cycles`, returns set TIFR flags
+    ///
+    /// The prescaler was reset at cycle count `start` and `period` is the
+    /// number of cpu cycles per count. Counting up to the next compare match,
+    /// TOP or turn is skipped.
+    fn advance(&mut self, cycles: u64, start: u64, period: Option<u64>) -> u8 {
+        let mut flags = 0;
+        if let Some(period) = period {
+            let mut ticks = (cycles.saturating_sub(start) / period)
+                .saturating_sub(self.cycles.saturating_sub(start) / period);
+            let channels = self.flags();
+            while ticks > 0 {
+                let run = self.run_length();
+                let next = self.ticks_to_compare(channels, run).unwrap_or(run);
+                let skipped = cmp::min(next - 1, ticks);
+                self.skip(skipped);
+                ticks -= skipped;
+                if ticks > 0 {
+                    flags |= self.tick();
+                    ticks -= 1;
+                }
+            }
+        }
+        self.cycles = cycles.max(self.cycles);
+        flags
+    }
+
+    /// counting down in a phase correct mode
+    fn is_down(&self) -> bool {
+        let waveform = self.mode().0;
+        self.down
+            && (waveform == Waveform::PhaseCorrect || waveform == Waveform::PhaseFrequencyCorrect)
+    }
+
+    /// number of ticks until the counter reaches TOP, MAX or BOTTOM, or turns
+    /// or wraps around, including that tick
+    fn run_length(&self) -> u64 {
+        let (waveform, top) = self.mode();
+        let top_value = self.top_value(top) as u64;
+        let cnt = self.cnt as u64;
+        match waveform {
+            Waveform::PhaseCorrect | Waveform::PhaseFrequencyCorrect if self.down => cnt.max(1),
+            Waveform::PhaseCorrect | Waveform::PhaseFrequencyCorrect if cnt < top_value =>
+                top_value - cnt,
+            Waveform::PhaseCorrect | Waveform::PhaseFrequencyCorrect => 1,
+            // counting up to TOP or past TOP up to MAX, then wrapping to 0
+            _ if cnt < top_value => top_value - cnt,
+            _ if cnt > top_value && cnt < self.max() as u64 => self.max() as u64 - cnt,
+            _ => 1,
+        }
+    }
+
+    /// ticks until the first compare match of a channel with its flag in
+    /// `enabled` before the end of the run after `run` ticks
+    fn ticks_to_compare(&self, enabled: u8, run: u64) -> Option<u64> {
+        let down = self.is_down();
+        (0..self.channels)
+            .filter(|&channel| enabled & self.compare_flag(channel) != 0)
+            .filter_map(|channel| {
+                let ocr = self.ocr[channel];
+                if down { self.cnt.checked_sub(ocr) } else { ocr.checked_sub(self.cnt) }
+            })
+            .map(|ticks| ticks as u64)
+            .filter(|&ticks| ticks > 0 && ticks < run)
+            .min()
+    }
+
+    /// count `ticks` times without reaching the end of the run
+    fn skip(&mut self, ticks: u64) {
+        if self.is_down() {
+            self.cnt -= ticks as u16;
+        } else {
+            self.cnt += ticks as u16;
+        }
+    }
+
+    /// cycle count of the next count setting one of the flags in `enabled`
+    ///
+    /// Counting within a run is skipped, only the ticks at the ends of the
+    /// runs are simulated.
+    fn find_next_event(&self, enabled: u8, start: u64, period: Option<u64>) -> Option<u64> {
+        let period = period?;
+        if enabled & self.flags() == 0 {
+            return None;
+        }
+
+        // compare values are updated within the first two runs, every event
+        // happens within two periods after that
+        let mut counter = self.clone();
+        let first = self.cycles.saturating_sub(start) / period;
+        let mut ticks = 0;
+        for _ in 0..8 {
+            let run = counter.run_length();
+            if let Some(compare) = counter.ticks_to_compare(enabled, run) {
+                return Some(start + (first + ticks + compare) * period);
+            }
+
+            counter.skip(run - 1);
+            ticks += run;
+            if counter.tick() & enabled != 0 {
+                return Some(start + (first + ticks) * period);
+            }
+        }
+        None
+    }
+}
+
+
+/// Timer0 (8-bit), Timer1 (16-bit) and Timer2 (8-bit, asynchronous) of
+/// classic megaAVR devices
+///
+/// Timer0 and Timer1 share a prescaler, which is reset by PSR10. External
+/// clock pins (T0, T1) and the force output compare strobes are not modeled.
+/// Input capture is triggered by the host with `input_capture`.
+pub struct ClassicTimers {
+    counters: [Counter; 3],
+
+    timsk: u8,
+    tifr: u8,
+
+    /// high byte of 16-bit accesses to Timer1
+    temp: u8,
+
+    assr: u8,
+
+    /// SFIOR bits not used by the timers
+    sfior: u8,
+
+    /// cycle count of the timer clock domain at the last reset of the
+    /// Timer0/Timer1 and Timer2 prescaler
+    prescaler_start: [u64; 2],
+
+    /// cpu cycles per period of the Timer2 oscillator (TOSC) in asynchronous
+    /// mode, `None` if no crystal is connected
+    pub tosc_cycles: Option<u64>,
+
+    /// interrupt vectors by TIFR bit
+    vectors: [Option<usize>; 8],
+
+    /// flags of requested interrupts
+    raised: u8,
+
+    /// cycle count of the next interrupt request
+    next_event: Option<u64>,
+
+    /// `next_event` has to be recalculated
+    dirty: bool,
+
+    /// sleep mode `next_event` was calculated for
+    sleep: Option<SleepMode>,
+}
+
+impl ClassicTimers {
+    pub fn new(timer0_channels: usize) -> ClassicTimers {
+        ClassicTimers {
+            counters: [
+                Counter::new(TimerKind::Timer0, timer0_channels, 0),
+                Counter::new(TimerKind::Timer1, 2, 0),
+                Counter::new(TimerKind::Timer2, 1, 0),
+            ],
+            timsk: 0,
+            tifr: 0,
+            temp: 0,
+            assr: 0,
+            sfior: 0,
+            prescaler_start: [0; 2],
+            tosc_cycles: None,
+            vectors: [None; 8],
+            raised: 0,
+            next_event: None,
+            dirty: true,
+            sleep: None,
+        }
+    }
+
+    /// level of output compare pin `channel` (A = 0, B = 1) of timer `timer`
+    pub fn output(&self, timer: usize, channel: usize) -> bool {
+        self.counters[timer].outputs & (1 << channel) != 0
+    }
+
+    /// edge on the input capture pin (ICP1)
+    ///
+    /// Captures TCNT1 into ICR1 if the edge matches ICES1 and ICR1 is not
+    /// used as TOP.
+    pub fn input_capture(&mut self, core: &mut AvrCoreState, rising: bool) {
+        self.advance(core);
+
+        let timer1 = &mut self.counters[1];
+        if timer1.mode().1 == Top::Icr || bit_at((timer1.control >> 8) as u8, ICES1) != rising {
+            return;
+        }
+        timer1.icr = timer1.cnt;
+        self.tifr |= ICF1;
+        self.dirty = true;
+        self.sync_irq(core);
+    }
+
+    /// cpu cycles per count of timer `timer`
+    fn period(&self, timer: usize) -> Option<u64> {
+        let prescaler = self.counters[timer].prescaler()?;
+        if timer == 2 && self.assr & AS2 != 0 {
+            self.tosc_cycles.map(|tosc| prescaler * tosc)
+        } else {
+            Some(prescaler)
+        }
+    }
+
+    fn prescaler_start(&self, timer: usize) -> u64 {
+        self.prescaler_start[if timer == 2 { 1 } else { 0 }]
+    }
+
+    /// clock of timer `timer`, Timer2 runs from TOSC in asynchronous mode
+    fn domain(&self, timer: usize) -> ClockDomain {
+        if timer == 2 && self.assr & AS2 != 0 {
+            ClockDomain::Asynchronous
+        } else {
+            ClockDomain::Io
+        }
+    }
+
+    fn advance(&mut self, core: &AvrCoreState) {
+        for timer in 0..3 {
+            let cycles = core.clock_cycles(self.domain(timer));
+            let start = self.prescaler_start(timer);
+            let period = self.period(timer);
+            self.tifr |= self.counters[timer].advance(cycles, start, period);
+        }
+    }
+
+    /// raise interrupts of enabled flags and withdraw them for cleared flags
+    fn sync_irq(&mut self, core: &mut AvrCoreState) {
+        for bit in 0..8 {
+            if let Some(vector) = self.vectors[bit] {
+                let flag = 1 << bit;
+                if self.tifr & self.timsk & flag != 0 {
+                    core.irq.raise(vector);
+                    self.raised |= flag;
+                } else if self.raised & flag != 0 {
+                    core.irq.clear(vector);
+                    self.raised &= !flag;
+                }
+            }
+        }
+    }
+
+    /// cpu cycle count of the next interrupt request of timers with a running clock
+    fn find_next_event(&self, core: &AvrCoreState) -> Option<u64> {
+        let vectors = (0..8)
+            .filter(|&bit| self.vectors[bit].is_some())
+            .fold(0, |flags, bit| flags | (1 << bit));
+        let enabled = self.timsk & !self.tifr & vectors;
+        if enabled == 0 {
+            return None;
+        }
+
+        (0..3).filter(|&timer| self.domain(timer).is_running(core.sleep))
+            .filter_map(|timer| {
+                let domain = self.domain(timer);
+                self.counters[timer].find_next_event(
+                    enabled, self.prescaler_start(timer), self.period(timer)
+                ).map(|next| core.cpu_cycles(domain, next))
+            }).min()
+    }
+
+    fn clock(&mut self, core: &mut AvrCoreState) -> Option<u64> {
+        self.advance(core);
+        self.sync_irq(core);
+
+        let passed = match self.next_event {
+            Some(next) => next <= core.cycles,
+            None => false,
+        };
+        if self.dirty || passed || self.sleep != core.sleep {
+            self.next_event = self.find_next_event(core);
+            self.dirty = false;
+            self.sleep = core.sleep;
+        }
+        self.next_event
+    }
+
+    fn acknowledge(&mut self, vector: usize) {
+        for bit in 0..8 {
+            if self.vectors[bit] == Some(vector) {
+                self.tifr &= !(1 << bit);
+                self.raised &= !(1 << bit);
+            }
+        }
+        self.dirty = true;
+    }
+
+    fn reset(&mut self, core: &AvrCoreState) {
+        let mut timers = ClassicTimers::new(self.counters[0].channels);
+        let cycles = core.clock_cycles(ClockDomain::Io);
+        for counter in timers.counters.iter_mut() {
+            counter.cycles = cycles;
+        }
+        timers.prescaler_start = [cycles; 2];
+        timers.tosc_cycles = self.tosc_cycles;
+        timers.vectors = self.vectors;
+        *self = timers;
+    }
+
+    fn read(&mut self, core: &AvrCoreState, register: Register, view: bool) -> u8 {
+        self.advance(core);
+
+        let timer1 = &self.counters[1];
+        match register {
+            Register::Tccr0 => self.counters[0].control as u8,
+            Register::Tcnt0 => self.counters[0].cnt as u8,
+            Register::Ocr0 => self.counters[0].ocr_buffer[0] as u8,
+
+            Register::Tccr1a => timer1.control as u8,
+            Register::Tccr1b => (timer1.control >> 8) as u8,
+            Register::Tcnt1l => self.read16(timer1.cnt, view),
+            Register::Icr1l => self.read16(timer1.icr, view),
+            Register::Tcnt1h | Register::Icr1h => self.temp,
+            // compare registers are read without TEMP
+            Register::Ocr1al => timer1.ocr_buffer[0] as u8,
+            Register::Ocr1ah => (timer1.ocr_buffer[0] >> 8) as u8,
+            Register::Ocr1bl => timer1.ocr_buffer[1] as u8,
+            Register::Ocr1bh => (timer1.ocr_buffer[1] >> 8) as u8,
+
+            Register::Tccr2 => self.counters[2].control as u8,
+            Register::Tcnt2 => self.counters[2].cnt as u8,
+            Register::Ocr2 => self.counters[2].ocr_buffer[0] as u8,
+            Register::Assr => self.assr,
+
+            Register::Timsk => self.timsk,
+            Register::Tifr => self.tifr,
+            Register::Sfior => self.sfior,
+        }
+    }
+
+    /// reading the low byte stores the high byte in TEMP
+    fn read16(&mut self, value: u16, view: bool) -> u8 {
+        if !view {
+            self.temp = (value >> 8) as u8;
+        }
+        value as u8
+    }
+
+    fn write(&mut self, core: &mut AvrCoreState, register: Register, value: u8) {
+        self.advance(core);
+
+        // writing the low byte writes all 16 bits
+        let word = (self.temp as u16) << 8 | value as u16;
+        let assr = self.assr;
+        match register {
+            Register::Tccr0 => {
+                let mask = if self.counters[0].channels == 0 { 0x07 } else { 0x7F };
+                self.write_control(0, value as u16 & mask);
+            }
+            Register::Tcnt0 => self.counters[0].cnt = value as u16,
+            Register::Ocr0 => self.counters[0].write_ocr(0, value as u16),
+
+            Register::Tccr1a => {
+                let control = (self.counters[1].control & 0xFF00) | (value as u16 & 0xF3);
+                self.write_control(1, control);
+            }
+            Register::Tccr1b => {
+                let control = (self.counters[1].control & 0x00FF) | ((value as u16 & 0xDF) << 8);
+                self.write_control(1, control);
+            }
+            Register::Tcnt1l => self.counters[1].cnt = word,
+            Register::Ocr1al => self.counters[1].write_ocr(0, word),
+            Register::Ocr1bl => self.counters[1].write_ocr(1, word),
+            Register::Icr1l => self.counters[1].icr = word,
+            Register::Tcnt1h | Register::Ocr1ah | Register::Ocr1bh | Register::Icr1h =>
+                self.temp = value,
+
+            Register::Tccr2 => self.write_control(2, value as u16 & 0x7F),
+            Register::Tcnt2 => self.counters[2].cnt = value as u16,
+            Register::Ocr2 => self.counters[2].write_ocr(0, value as u16),
+            Register::Assr if value & AS2 != self.assr => {
+                self.assr = value & AS2;
+                // continue Timer2 in the time of its new clock
+                let cycles = core.clock_cycles(self.domain(2));
+                self.counters[2].cycles = cycles;
+                self.prescaler_start[1] = cycles;
+            }
+            Register::Assr => (),
+
+            Register::Timsk => self.timsk = value,
+            Register::Tifr => self.tifr &= !value,
+            Register::Sfior => {
+                if value & PSR10 != 0 {
+                    self.prescaler_start[0] = core.clock_cycles(ClockDomain::Io);
+                }
+                if value & PSR2 != 0 {
+                    self.prescaler_start[1] = core.clock_cycles(self.domain(2));
+                }
+                self.sfior = value & !(PSR10 | PSR2);
+            }
+        }
+
+        // TEMP, an unchanged ASSR and SFIOR bits of other modules do not
+        // change the next event
+        self.dirty |= match register {
+            Register::Tcnt1h | Register::Ocr1ah | Register::Ocr1bh | Register::Icr1h => false,
+            Register::Assr => self.assr != assr,
+            Register::Sfior => value & (PSR10 | PSR2) != 0,
+            _ => true,
+        };
+        self.sync_irq(core);
+    }
+
+    fn write_control(&mut self, timer: usize, control: u16) {
+        let counter = &mut self.counters[timer];
+        counter.control = control;
+        if !counter.is_pwm() {
+            counter.ocr = counter.ocr_buffer;
+        }
+        info!(
+            target: "avrvc::classic_timer",
+            "Timer{} control: CS={} WGM={}", timer, counter.clock_select(), counter.wgm());
+    }
+}
+
+
+pub fn register_classic_timers(vm: &mut AvrVm) -> Option<Arc<Mutex<ClassicTimers>>> {
+    let ioregs = vm.info.io_regs.clone();
+    if !ioregs.contains_key("TIFR") || !ioregs.contains_key("TCCR1B") {
+        return None;
+    }
+
+    let mut timers = ClassicTimers::new(if ioregs.contains_key("OCR0") { 1 } else { 0 });
+    for (bit, name) in VECTOR_NAMES.iter().enumerate() {
+        timers.vectors[bit] = vm.info.vectors.get(name).cloned();
+    }
+    let vectors = timers.vectors;
+    let timers = Arc::new(Mutex::new(timers));
+
+    for &(name, register) in REGISTERS.iter() {
+        if let Some(&addr) = ioregs.get(name) {
+            let timers1 = Arc::clone(&timers);
+            let timers2 = Arc::clone(&timers);
+            vm.register_io(
+                addr,
+                Box::new(move |core, _, view| timers1.lock().unwrap().read(core, register, view)),
+                Box::new(move |core, _, value| timers2.lock().unwrap().write(core, register, value))
+            );
+        }
+    }
+
+    for &vector in vectors.iter().filter_map(|vector| vector.as_ref()) {
+        let timers3 = Arc::clone(&timers);
+        vm.register_interrupt_ack(
+            vector,
+            Box::new(move |_, vector| timers3.lock().unwrap().acknowledge(vector))
+        );
+    }
+
+    let timers4 = Arc::clone(&timers);
+    let timers5 = Arc::clone(&timers);
+    vm.register_clock(ClockDomain::Asynchronous, Box::new(move |core| timers4.lock().unwrap().clock(core)));
+    vm.register_reset(Box::new(move |core, _| timers5.lock().unwrap().reset(core)));
+
+    Some(timers)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use emulator::AvrEmulator;
+    use models::test_util;
+    use models::test_util::write_ioreg;
+    use models::test_util::read_ioreg;
+    use models::test_util::load_ioreg;
+    use models::test_util::vector_address;
+    use instruction_set::Instruction::Sleep;
+    use core::CpuSignal;
+    use models::sleep::SleepMode;
+
+    fn create_emulator(name: &str) -> AvrEmulator {
+        let mut emulator = test_util::create_emulator(name);
+        emulator.vm.core.cycles = 0;
+        emulator
+    }
+
+    fn read_tcnt1(vm: &mut AvrVm) -> u16 {
+        // reading the low byte latches the high byte in TEMP
+        let low = load_ioreg(vm, "TCNT1L") as u16;
+        low | (read_ioreg(vm, "TCNT1H") as u16) << 8
+    }
+
+    #[test]
+    fn timer0_overflow() {
+        let mut emulator = create_emulator("atmega8");
+        let vm = &mut emulator.vm;
+
+        write_ioreg(vm, "TCCR0", 0x02); // clk/8
+        vm.core.cycles = 8 * 255 + 7;
+        assert_eq!(read_ioreg(vm, "TCNT0"), 255);
+        assert_eq!(read_ioreg(vm, "TIFR"), 0);
+
+        vm.core.cycles = 8 * 256;
+        assert_eq!(read_ioreg(vm, "TCNT0"), 0);
+        assert_eq!(read_ioreg(vm, "TIFR"), TOV0);
+
+        // flags are cleared by writing one
+        write_ioreg(vm, "TIFR", !TOV0);
+        assert_eq!(read_ioreg(vm, "TIFR"), TOV0);
+        write_ioreg(vm, "TIFR", TOV0);
+        assert_eq!(read_ioreg(vm, "TIFR"), 0);
+
+        // no compare unit and waveform modes on atmega8
+        write_ioreg(vm, "TCCR0", 0xFF);
+        assert_eq!(read_ioreg(vm, "TCCR0"), 0x07);
+    }
+
+    #[test]
+    fn timer1_temp() {
+        let mut emulator = create_emulator("atmega16");
+        let vm = &mut emulator.vm;
+
+        // high byte first
+        write_ioreg(vm, "TCNT1H", 0x12);
+        assert_eq!(vm.read_io(vm.info.io_regs["TCNT1H"], true), 0x12);
+        write_ioreg(vm, "TCNT1L", 0x34);
+        assert_eq!(read_tcnt1(vm), 0x1234);
+
+        // high byte is latched when reading the low byte
+        write_ioreg(vm, "TCCR1B", 0x01);
+        vm.core.cycles = 0xCC;
+        assert_eq!(load_ioreg(vm, "TCNT1L"), 0x00);
+        vm.core.cycles = 0x200;
+        assert_eq!(read_ioreg(vm, "TCNT1H"), 0x13);
+
+        // compare registers are read directly
+        write_ioreg(vm, "OCR1AH", 0x56);
+        write_ioreg(vm, "OCR1AL", 0x78);
+        assert_eq!(read_ioreg(vm, "OCR1AH"), 0x56);
+        assert_eq!(read_ioreg(vm, "OCR1AL"), 0x78);
+    }
+
+    #[test]
+    fn timer1_ctc() {
+        let mut emulator = create_emulator("atmega16");
+        let vm = &mut emulator.vm;
+
+        write_ioreg(vm, "OCR1AH", 0);
+        write_ioreg(vm, "OCR1AL", 99);
+        write_ioreg(vm, "TCCR1A", 0x40); // toggle OC1A
+        write_ioreg(vm, "TCCR1B", 0x08 | 0x02); // CTC, clk/8
+
+        vm.core.cycles = 8 * 99;
+        assert_eq!(read_tcnt1(vm), 99);
+        assert_eq!(read_ioreg(vm, "TIFR"), OCF1A);
+        write_ioreg(vm, "TIFR", OCF1A);
+        assert!(emulator.classic_timers.as_ref().unwrap().lock().unwrap().output(1, 0));
+
+        // OCR1B matches at BOTTOM
+        vm.core.cycles = 8 * 105;
+        assert_eq!(read_tcnt1(vm), 5);
+        assert_eq!(read_ioreg(vm, "TIFR"), OCF1B);
+    }
+
+    #[test]
+    fn timer0_fast_pwm() {
+        let mut emulator = create_emulator("atmega16");
+        let timers = Arc::clone(emulator.classic_timers.as_ref().unwrap());
+        let vm = &mut emulator.vm;
+
+        write_ioreg(vm, "OCR0", 63);
+        write_ioreg(vm, "TCCR0", 0x48 | 0x20 | 0x01); // fast PWM, non-inverting, clk/1
+
+        // compare value is updated at BOTTOM
+        write_ioreg(vm, "OCR0", 127);
+        assert_eq!(timers.lock().unwrap().counters[0].ocr[0], 63);
+        vm.core.cycles = 256;
+        assert_eq!(read_ioreg(vm, "TIFR") & TOV0, TOV0);
+        assert_eq!(timers.lock().unwrap().counters[0].ocr[0], 127);
+
+        let mut high = 0;
+        for cycles in 256..512 {
+            vm.core.cycles = cycles;
+            read_ioreg(vm, "TCNT0");
+            if timers.lock().unwrap().output(0, 0) {
+                high += 1;
+            }
+        }
+        assert_eq!(high, 127);
+    }
+
+    #[test]
+    fn timer1_phase_correct() {
+        let mut emulator = create_emulator("atmega16");
+        let vm = &mut emulator.vm;
+
+        write_ioreg(vm, "TCCR1A", 0x01); // 8-bit phase correct
+        write_ioreg(vm, "TCCR1B", 0x01);
+
+        vm.core.cycles = 255;
+        assert_eq!(read_tcnt1(vm), 255);
+        vm.core.cycles = 256;
+        assert_eq!(read_tcnt1(vm), 254);
+        assert_eq!(read_ioreg(vm, "TIFR") & TOV1, 0);
+
+        // overflow at BOTTOM
+        vm.core.cycles = 510;
+        assert_eq!(read_tcnt1(vm), 0);
+        assert_eq!(read_ioreg(vm, "TIFR") & TOV1, TOV1);
+    }
+
+    #[test]
+    fn timer1_input_capture() {
+        let mut emulator = create_emulator("atmega16");
+        let timers = Arc::clone(emulator.classic_timers.as_ref().unwrap());
+        let vm = &mut emulator.vm;
+
+        write_ioreg(vm, "TCCR1B", 0x40 | 0x01); // rising edge, clk/1
+        vm.core.cycles = 0x1234;
+
+        // falling edge is ignored
+        timers.lock().unwrap().input_capture(&mut vm.core, false);
+        assert_eq!(read_ioreg(vm, "TIFR"), 0);
+
+        timers.lock().unwrap().input_capture(&mut vm.core, true);
+        assert_eq!(read_ioreg(vm, "TIFR"), ICF1);
+        assert_eq!(load_ioreg(vm, "ICR1L"), 0x34);
+        assert_eq!(read_ioreg(vm, "ICR1H"), 0x12);
+    }
+
+    #[test]
+    fn shared_prescaler() {
+        let mut emulator = create_emulator("atmega16");
+        let vm = &mut emulator.vm;
+
+        write_ioreg(vm, "TCCR0", 0x02); // clk/8
+        write_ioreg(vm, "TCCR1B", 0x02);
+        write_ioreg(vm, "TCCR2", 0x02);
+
+        // reset of Timer0/Timer1 prescaler
+        vm.core.cycles = 5;
+        write_ioreg(vm, "SFIOR", PSR10);
+        assert_eq!(read_ioreg(vm, "SFIOR"), 0);
+
+        vm.core.cycles = 12;
+        assert_eq!(read_ioreg(vm, "TCNT0"), 0);
+        assert_eq!(read_tcnt1(vm), 0);
+        assert_eq!(read_ioreg(vm, "TCNT2"), 1);
+
+        vm.core.cycles = 13;
+        assert_eq!(read_ioreg(vm, "TCNT0"), 1);
+        assert_eq!(read_tcnt1(vm), 1);
+    }
+
+    #[test]
+    fn timer2_async() {
+        let mut emulator = create_emulator("atmega8");
+        let timers = Arc::clone(emulator.classic_timers.as_ref().unwrap());
+        let vm = &mut emulator.vm;
+
+        write_ioreg(vm, "ASSR", 0xFF);
+        assert_eq!(read_ioreg(vm, "ASSR"), AS2);
+        write_ioreg(vm, "TCCR2", 0x01);
+
+        // no crystal
+        vm.core.cycles = 1000;
+        assert_eq!(read_ioreg(vm, "TCNT2"), 0);
+
+        timers.lock().unwrap().tosc_cycles = Some(100);
+        vm.core.cycles = 2000;
+        assert_eq!(read_ioreg(vm, "TCNT2"), 10);
+    }
+
+    #[test]
+    fn overflow_interrupt() {
+        let mut emulator = create_emulator("atmega8");
+        let vm = &mut emulator.vm;
+        let vector = vm.info.vectors["TIMER0_OVF"];
+
+        vm.core.interrupt = true;
+        write_ioreg(vm, "TIMSK", TOV0);
+        write_ioreg(vm, "TCCR0", 0x01);
+
+        let mut dispatched = false;
+        for _ in 0..300 {
+            vm.step().unwrap();
+            if vm.core.pc == vector {
+                dispatched = true;
+                break;
+            }
+        }
+        assert!(dispatched);
+        assert_eq!(vm.core.cycles, 256 + 4);
+
+        // flag is cleared when the vector is executed
+        assert_eq!(read_ioreg(vm, "TIFR"), 0);
+        assert!(!vm.core.irq.is_pending(vector));
+    }
+
+    #[test]
+    fn wake_up_from_sleep() {
+        let mut emulator = create_emulator("atmega16");
+        let vm = &mut emulator.vm;
+        let vector = vm.info.vectors["TIMER1_COMPA"];
+
+        vm.core.interrupt = true;
+        write_ioreg(vm, "OCR1AH", 0x10);
+        write_ioreg(vm, "OCR1AL", 0x00);
+        write_ioreg(vm, "TCCR1B", 0x03); // clk/64
+        vm.core.sleep = Some(SleepMode::Idle);
+
+        // no interrupt enabled
+        assert_eq!(vm.step(), Err(CpuSignal::Sleeping));
+
+        write_ioreg(vm, "TIMSK", OCF1A);
+        assert_eq!(vm.step(), Ok(()));
+        assert_eq!(vm.core.pc, vector * vm.info.vector_size);
+        assert!(vm.core.cycles >= 64 * 0x1000);
+        assert!(vm.core.cycles < 64 * 0x1000 + 20);
+    }
+
+    #[test]
+    fn idle_keeps_timers_running() {
+        let mut emulator = create_emulator("atmega16");
+        let vm = &mut emulator.vm;
+        vm.core.interrupt = true;
+        write_ioreg(vm, "TCCR0", 2); // clk/8
+        write_ioreg(vm, "TIMSK", TOV0);
+        write_ioreg(vm, "MCUCR", 1 << 6); // SE
+
+        Sleep.execute(vm).unwrap();
+        assert_eq!(vm.step(), Ok(()));
+        assert_eq!(vm.core.pc, vector_address(vm, "TIMER0_OVF"));
+        assert_eq!(vm.core.sleep, None);
+    }
+
+    /// counters of all kinds in all modes with CNT, OCRnA, OCRnB and ICR1
+    fn counters() -> Vec<Counter> {
+        let values = [
+            (0x0000, 0x0080, 0x0000, 0x0100),
+            (0x0050, 0x0020, 0x0060, 0x0040),
+            (0x00FF, 0x00FF, 0x00FE, 0x0000),
+            (0x0300, 0x0200, 0x0010, 0x0005),
+        ];
+        let mut counters = Vec::new();
+        for &kind in [TimerKind::Timer0, TimerKind::Timer1, TimerKind::Timer2].iter() {
+            let modes = if kind == TimerKind::Timer1 { 16 } else { 4 };
+            for wgm in 0..modes {
+                for &down in [false, true].iter() {
+                    for &(cnt, ocra, ocrb, icr) in values.iter() {
+                        let mut counter = Counter::new(kind, 2, 0);
+                        counter.control = if kind == TimerKind::Timer1 {
+                            // clk/1, toggle or set OC1A and OC1B
+                            0x0100 | 0x70 | (wgm & 0b11) | ((wgm & 0b1100) << 9)
+                        } else {
+                            // clk/1, toggle or set OCn
+                            0x01 | 0x10 | ((wgm & 0b01) << 6) | ((wgm & 0b10) << 2)
+                        };
+                        counter.channels = if kind == TimerKind::Timer1 { 2 } else { 1 };
+                        counter.cnt = cnt & counter.max();
+                        counter.ocr = [ocra & counter.max(), ocrb];
+                        counter.ocr_buffer = [ocrb & counter.max(), ocra];
+                        counter.icr = icr;
+                        counter.down = down;
+                        counters.push(counter);
+                    }
+                }
+            }
+        }
+        counters
+    }
+
+    fn count_to_next_event(counter: &Counter, enabled: u8) -> Option<u64> {
+        let mut counter = counter.clone();
+        (1..(2 * (counter.max() as u64 + 1) + 2)).find(|_| counter.tick() & enabled != 0)
+    }
+
+    #[test]
+    fn next_event() {
+        for counter in counters() {
+            for &enabled in [TOV0 | TOV1 | TOV2, OCF0 | OCF1A | OCF2, OCF1B, ICF1].iter() {
+                let enabled = enabled & counter.flags();
+                if enabled == 0 {
+                    continue;
+                }
+                assert_eq!(
+                    counter.find_next_event(enabled, 0, Some(1)),
+                    count_to_next_event(&counter, enabled),
+                    "{:?} WGM={} down={} CNT={:04x} OCR={:04x?} ICR={:04x} flags={:02x}",
+                    counter.kind, counter.wgm(), counter.down, counter.cnt, counter.ocr,
+                    counter.icr, enabled);
+            }
+        }
+    }
+
+    #[test]
+    fn advance() {
+        for counter in counters() {
+            for &ticks in [1, 0x40, 0x201, 0x1_2345].iter() {
+                let mut counted = counter.clone();
+                let flags = (0..ticks).fold(0, |flags, _| flags | counted.tick());
+
+                let mut advanced = counter.clone();
+                assert_eq!(
+                    (advanced.advance(ticks, 0, Some(1)), advanced.cnt, advanced.down,
+                        advanced.ocr, advanced.outputs),
+                    (flags, counted.cnt, counted.down, counted.ocr, counted.outputs),
+                    "{:?} WGM={} down={} CNT={:04x} OCR={:04x?} ICR={:04x} ticks={}",
+                    counter.kind, counter.wgm(), counter.down, counter.cnt, counter.ocr,
+                    counter.icr, ticks);
+            }
+        }
+    }
+
+    #[test]
+    fn write_without_event_change() {
+        let mut emulator = create_emulator("atmega16");
+        let timers = Arc::clone(emulator.classic_timers.as_ref().unwrap());
+        let vm = &mut emulator.vm;
+        write_ioreg(vm, "TCCR1B", 0x01);
+        write_ioreg(vm, "TIMSK", TOV1);
+        vm.step().unwrap();
+        assert!(!timers.lock().unwrap().dirty);
+
+        // TEMP, ASSR without change and SFIOR bits of other modules
+        write_ioreg(vm, "OCR1AH", 0x12);
+        write_ioreg(vm, "ASSR", 0);
+        write_ioreg(vm, "SFIOR", 0x08);
+        assert!(!timers.lock().unwrap().dirty);
+
+        write_ioreg(vm, "OCR1AL", 0x34);
+        assert!(timers.lock().unwrap().dirty);
+    }
+}
@@ -0,0 +1,527 @@
+use core::AvrVm;
+use core::AvrCoreState;
+use std::sync::Arc;
+use std::sync::Mutex;
+use models::gpio::Port;
+use models::gpio::Ports;
+use models::reset::ResetController;
+use models::sleep::ClockDomain;
+
+
+/// ISC2 of MCUCSR
+const ISC2: u8 = 1 << 6;
+
+// interrupt sense control (ISCn1:ISCn0)
+const ISC_LOW_LEVEL: u8 = 0;
+const ISC_ANY_CHANGE: u8 = 1;
+const ISC_FALLING: u8 = 2;
+const ISC_RISING: u8 = 3;
+
+/// INT0-INT2 pins and bits of GICR/GIFR on ATmega8/ATmega16
+static MEGA_INTS: [(&str, u8, u8); 3] = [("D", 2, 6), ("D", 3, 7), ("B", 2, 5)];
+
+/// INT0 pin and bit of EIMSK/EIFR on tinyAVR devices
+static TINY_INTS: [(&str, u8, u8); 1] = [("B", 2, 0)];
+
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Register {
+    Control,
+    Mask,
+    Flags,
+    PcEnable,
+    PcFlags,
+    PcMask(usize),
+}
+
+/// external interrupt pin (INTn)
+struct ExternalInterrupt {
+    /// index in `ExternalInterrupts::ports`
+    port: usize,
+    pin: u8,
+
+    /// bit in mask and flag register
+    bit: u8,
+
+    vector: Option<usize>,
+}
+
+/// pin change interrupt of a port (PCINTn)
+struct PinChangeGroup {
+    /// index in `ExternalInterrupts::ports`
+    port: usize,
+
+    /// PCMSKn
+    mask: u8,
+
+    vector: Option<usize>,
+}
+
+
+/// external interrupts (INTn) and pin change interrupts (PCINTn) of classic
+/// and tinyAVR devices
+///
+/// Low level interrupts are requested as long as the pin is low and set no
+/// flag. Pin edges are sensed after each instruction, so a pulse driven by the
+/// host between two steps is seen as both edges. Edges on INT0 and INT1 are
+/// only sensed while the I/O clock is running, INT2 and pin changes are sensed
+/// asynchronously and wake the cpu from all sleep modes.
+pub struct ExternalInterrupts {
+    ports: Vec<Arc<Mutex<Port>>>,
+    ints: Vec<ExternalInterrupt>,
+    groups: Vec<PinChangeGroup>,
+
+    /// MCUCR or EICRA
+    control: u8,
+
+    /// GICR or EIMSK
+    mask: u8,
+
+    /// GIFR or EIFR
+    flags: u8,
+
+    /// PCICR
+    pc_enable: u8,
+
+    /// PCIFR
+    pc_flags: u8,
+
+    /// holds ISC2 of INT2 in MCUCSR
+    reset: Option<Arc<Mutex<ResetController>>>,
+
+    /// requested interrupt vectors
+    raised: Vec<usize>,
+}
+
+impl ExternalInterrupts {
+    /// interrupt sense control of INTn
+    fn sense_control(&self, int: usize) -> u8 {
+        if int < 2 {
+            return (self.control >> (2 * int)) & 0b11;
+        }
+
+        // INT2 is edge triggered only
+        let isc2 = match self.reset {
+            Some(ref reset) => reset.lock().unwrap().control() & ISC2 != 0,
+            None => false,
+        };
+        if isc2 { ISC_RISING } else { ISC_FALLING }
+    }
+
+    /// set flags for pin edges since the last poll
+    fn poll(&mut self, core: &mut AvrCoreState) {
+        let edges: Vec<(u8, u8)> = self.ports.iter()
+            .map(|port| port.lock().unwrap().take_edges())
+            .collect();
+
+        let io_clock = ClockDomain::Io.is_running(core.sleep);
+        let mut flags = 0;
+        for (n, int) in self.ints.iter().enumerate() {
+            let (rising, falling) = edges[int.port];
+            let sensed = match self.sense_control(n) {
+                _ if n < 2 && !io_clock => 0,
+                ISC_ANY_CHANGE => rising | falling,
+                ISC_FALLING => falling,
+                ISC_RISING => rising,
+                _ => 0,
+            };
+            if sensed & (1 << int.pin) != 0 {
+                flags |= 1 << int.bit;
+            }
+        }
+        self.flags |= flags;
+
+        for (n, group) in self.groups.iter().enumerate() {
+            let (rising, falling) = edges[group.port];
+            if (rising | falling) & group.mask != 0 {
+                self.pc_flags |= 1 << n;
+            }
+        }
+
+        self.sync_irq(core);
+    }
+
+    /// request interrupts of enabled flags and low levels, withdraw others
+    fn sync_irq(&mut self, core: &mut AvrCoreState) {
+        let mut requested = vec![];
+        for (n, int) in self.ints.iter().enumerate() {
+            let bit = 1 << int.bit;
+            if self.mask & bit == 0 {
+                continue;
+            }
+            let low_level = self.sense_control(n) == ISC_LOW_LEVEL
+                && !self.ports[int.port].lock().unwrap().level(int.pin);
+            if self.flags & bit != 0 || low_level {
+                requested.extend(int.vector);
+            }
+        }
+        for (n, group) in self.groups.iter().enumerate() {
+            if self.pc_enable & self.pc_flags & (1 << n) != 0 {
+                requested.extend(group.vector);
+            }
+        }
+
+        for &vector in self.raised.iter().filter(|vector| !requested.contains(vector)) {
+            core.irq.clear(vector);
+        }
+        for &vector in requested.iter() {
+            core.irq.raise(vector);
+        }
+        self.raised = requested;
+    }
+
+    fn clock(&mut self, core: &mut AvrCoreState) -> Option<u64> {
+        self.poll(core);
+        None
+    }
+
+    fn acknowledge(&mut self, vector: usize) {
+        for int in self.ints.iter().filter(|int| int.vector == Some(vector)) {
+            self.flags &= !(1 << int.bit);
+        }
+        for (n, group) in self.groups.iter().enumerate() {
+            if group.vector == Some(vector) {
+                self.pc_flags &= !(1 << n);
+            }
+        }
+        self.raised.retain(|&raised| raised != vector);
+    }
+
+    fn reset(&mut self) {
+        self.control = 0;
+        self.mask = 0;
+        self.flags = 0;
+        self.pc_enable = 0;
+        self.pc_flags = 0;
+        for group in self.groups.iter_mut() {
+            group.mask = 0;
+        }
+        self.raised.clear();
+    }
+
+    fn read(&mut self, _core: &AvrCoreState, register: Register, _view: bool) -> u8 {
+        match register {
+            Register::Control => self.control,
+            Register::Mask => self.mask,
+            Register::Flags => self.flags,
+            Register::PcEnable => self.pc_enable,
+            Register::PcFlags => self.pc_flags,
+            Register::PcMask(group) => self.groups[group].mask,
+        }
+    }
+
+    fn write(&mut self, core: &mut AvrCoreState, register: Register, value: u8) {
+        // sense edges with the old configuration
+        self.poll(core);
+
+        match register {
+            Register::Control => self.control = value,
+            Register::Mask => self.mask = value,
+            Register::Flags => self.flags &= !value,
+            Register::PcEnable => self.pc_enable = value,
+            Register::PcFlags => self.pc_flags &= !value,
+            Register::PcMask(group) => self.groups[group].mask = value,
+        }
+        self.sync_irq(core);
+    }
+}
+
+
+pub fn register_external_interrupts(
+    vm: &mut AvrVm, ports: &Ports, reset: Option<&Arc<Mutex<ResetController>>>
+) -> Option<Arc<Mutex<ExternalInterrupts>>> {
+    if vm.info.xmega {
+        return None;
+    }
+
+    let ioregs = vm.info.io_regs.clone();
+    let (registers, pins): (_, &[(&str, u8, u8)]) = if ioregs.contains_key("GICR") {
+        (["MCUCR", "GICR", "GIFR"], &MEGA_INTS)
+    } else if ioregs.contains_key("EIMSK") {
+        (["EICRA", "EIMSK", "EIFR"], &TINY_INTS)
+    } else {
+        return None;
+    };
+
+    let mut letters: Vec<&'static str> = ports.keys().cloned().collect();
+    letters.sort();
+    let mut used_ports: Vec<&'static str> = vec![];
+    let mut port_index = |letter: &'static str| {
+        match used_ports.iter().position(|&used| used == letter) {
+            Some(index) => index,
+            None => {
+                used_ports.push(letter);
+                used_ports.len() - 1
+            }
+        }
+    };
+
+    let mut ints = vec![];
+    for (n, &(letter, pin, bit)) in pins.iter().enumerate() {
+        let vector = vm.info.vectors.get(&*format!("INT{}", n)).cloned();
+        let letter = match letters.iter().find(|&&name| name == letter) {
+            Some(&letter) if vector.is_some() => letter,
+            _ => break,
+        };
+        ints.push(ExternalInterrupt { port: port_index(letter), pin, bit, vector });
+    }
+
+    // PCINTn senses the n-th port
+    let mut groups = vec![];
+    let mut pc_masks = vec![];
+    for (n, &letter) in letters.iter().enumerate().take(3) {
+        let addr = ioregs.get(&*format!("PCMSK{}", n))
+            .or_else(|| if n == 0 { ioregs.get("PCMSK") } else { None });
+        let addr = match addr {
+            Some(&addr) => addr,
+            None => break,
+        };
+        let vector = vm.info.vectors.get(&*format!("PCINT{}", n)).cloned();
+        groups.push(PinChangeGroup { port: port_index(letter), mask: 0, vector });
+        pc_masks.push((addr, Register::PcMask(n)));
+    }
+
+    let vectors: Vec<usize> = ints.iter().filter_map(|int| int.vector)
+        .chain(groups.iter().filter_map(|group| group.vector))
+        .collect();
+
+    let exint = Arc::new(Mutex::new(ExternalInterrupts {
+        ports: used_ports.iter().map(|letter| Arc::clone(&ports[letter])).collect(),
+        ints,
+        groups,
+        control: 0,
+        mask: 0,
+        flags: 0,
+        pc_enable: 0,
+        pc_flags: 0,
+        reset: reset.cloned(),
+        raised: vec![],
+    }));
+
+    let mut addrs = vec![
+        (ioregs[registers[0]], Register::Control),
+        (ioregs[registers[1]], Register::Mask),
+        (ioregs[registers[2]], Register::Flags),
+    ];
+    if let (Some(&enable), Some(&flags)) = (ioregs.get("PCICR"), ioregs.get("PCIFR")) {
+        addrs.push((enable, Register::PcEnable));
+        addrs.push((flags, Register::PcFlags));
+        addrs.extend(pc_masks);
+    }
+
+    for (addr, register) in addrs {
+        let exint1 = Arc::clone(&exint);
+        let exint2 = Arc::clone(&exint);
+        vm.register_io(
+            addr,
+            Box::new(move |core, _, view| exint1.lock().unwrap().read(core, register, view)),
+            Box::new(move |core, _, value| exint2.lock().unwrap().write(core, register, value))
+        );
+    }
+
+    for vector in vectors {
+        let exint3 = Arc::clone(&exint);
+        vm.register_interrupt_ack(
+            vector,
+            Box::new(move |_, vector| exint3.lock().unwrap().acknowledge(vector))
+        );
+    }
+
+    let exint4 = Arc::clone(&exint);
+    let exint5 = Arc::clone(&exint);
+    vm.register_clock(ClockDomain::Always, Box::new(move |core| exint4.lock().unwrap().clock(core)));
+    vm.register_reset(Box::new(move |_, _| exint5.lock().unwrap().reset()));
+
+    Some(exint)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use emulator::AvrEmulator;
+    use models::test_util;
+    use models::test_util::write_ioreg;
+    use models::test_util::read_ioreg;
+    use models::test_util::vector_address;
+    use core::CpuSignal;
+    use models::sleep::SleepMode;
+    use instruction_set::Instruction::Sleep;
+
+    fn create_emulator(name: &str) -> AvrEmulator {
+        let mut emulator = test_util::create_emulator(name);
+        emulator.vm.core.interrupt = true;
+        emulator
+    }
+
+    #[test]
+    fn falling_edge() {
+        let mut emulator = create_emulator("atmega8");
+        let port = Arc::clone(&emulator.ports["D"]);
+        let vm = &mut emulator.vm;
+
+        // pull-up on PD2
+        write_ioreg(vm, "PORTD", 0x04);
+        write_ioreg(vm, "MCUCR", 0x02);
+        write_ioreg(vm, "GICR", 0x40);
+        vm.step().unwrap();
+        assert_eq!(read_ioreg(vm, "GIFR"), 0);
+
+        port.lock().unwrap().drive(2, false);
+        vm.step().unwrap();
+        assert_eq!(read_ioreg(vm, "GIFR"), 0x40);
+        vm.step().unwrap();
+        assert_eq!(vm.core.pc, vector_address(vm, "INT0"));
+
+        // flag is cleared when the vector is executed
+        assert_eq!(read_ioreg(vm, "GIFR"), 0);
+
+        // rising edge is ignored
+        port.lock().unwrap().release(2);
+        vm.step().unwrap();
+        assert_eq!(read_ioreg(vm, "GIFR"), 0);
+    }
+
+    #[test]
+    fn flags_without_enable() {
+        let mut emulator = create_emulator("atmega8");
+        let vm = &mut emulator.vm;
+
+        // toggling an output pin triggers INT1 on any change
+        write_ioreg(vm, "MCUCR", 0x04);
+        write_ioreg(vm, "DDRD", 0x08);
+        write_ioreg(vm, "PORTD", 0x08);
+        vm.step().unwrap();
+        assert_eq!(read_ioreg(vm, "GIFR"), 0x80);
+        assert!(!vm.core.irq.has_pending());
+
+        // flags are cleared by writing one
+        write_ioreg(vm, "GIFR", 0x80);
+        assert_eq!(read_ioreg(vm, "GIFR"), 0);
+    }
+
+    #[test]
+    fn low_level() {
+        let mut emulator = create_emulator("atmega8");
+        let port = Arc::clone(&emulator.ports["D"]);
+        let vm = &mut emulator.vm;
+        let vector = vm.info.vectors["INT1"];
+
+        port.lock().unwrap().drive(3, true);
+        write_ioreg(vm, "GICR", 0x80);
+        vm.step().unwrap();
+        assert!(!vm.core.irq.is_pending(vector));
+
+        // requested as long as the pin is low
+        port.lock().unwrap().drive(3, false);
+        vm.core.interrupt = false;
+        vm.step().unwrap();
+        assert!(vm.core.irq.is_pending(vector));
+        assert_eq!(read_ioreg(vm, "GIFR"), 0);
+
+        port.lock().unwrap().drive(3, true);
+        vm.step().unwrap();
+        assert!(!vm.core.irq.is_pending(vector));
+    }
+
+    #[test]
+    fn int2_sense() {
+        let mut emulator = create_emulator("atmega16");
+        let port = Arc::clone(&emulator.ports["B"]);
+        let vm = &mut emulator.vm;
+
+        // ISC2 selects rising edge
+        write_ioreg(vm, "MCUCSR", 0x40);
+        port.lock().unwrap().drive(2, true);
+        vm.step().unwrap();
+        assert_eq!(read_ioreg(vm, "GIFR"), 0x20);
+
+        write_ioreg(vm, "GIFR", 0x20);
+        write_ioreg(vm, "MCUCSR", 0x00);
+        port.lock().unwrap().drive(2, false);
+        vm.step().unwrap();
+        assert_eq!(read_ioreg(vm, "GIFR"), 0x20);
+
+        write_ioreg(vm, "GICR", 0x20);
+        vm.step().unwrap();
+        assert_eq!(vm.core.pc, vector_address(vm, "INT2"));
+    }
+
+    #[test]
+    fn pin_change() {
+        let mut emulator = create_emulator("attiny10");
+        let port = Arc::clone(&emulator.ports["B"]);
+        let vm = &mut emulator.vm;
+
+        write_ioreg(vm, "PCMSK", 0x01);
+        port.lock().unwrap().drive(1, true);
+        vm.step().unwrap();
+        assert_eq!(read_ioreg(vm, "PCIFR"), 0);
+
+        port.lock().unwrap().drive(0, true);
+        vm.step().unwrap();
+        assert_eq!(read_ioreg(vm, "PCIFR"), 0x01);
+
+        write_ioreg(vm, "PCICR", 0x01);
+        vm.step().unwrap();
+        assert_eq!(vm.core.pc, vector_address(vm, "PCINT0"));
+        assert_eq!(read_ioreg(vm, "PCIFR"), 0);
+    }
+
+    #[test]
+    fn wake_up_from_sleep() {
+        let mut emulator = create_emulator("atmega16");
+        let port = Arc::clone(&emulator.ports["D"]);
+        let vm = &mut emulator.vm;
+
+        write_ioreg(vm, "MCUCR", 0x0C); // INT1 on rising edge
+        write_ioreg(vm, "GICR", 0x80);
+
+        // edges are not sensed without the I/O clock
+        vm.core.sleep = Some(SleepMode::PowerDown);
+        assert_eq!(vm.step(), Err(CpuSignal::Sleeping));
+        port.lock().unwrap().drive(3, true);
+        assert_eq!(vm.step(), Err(CpuSignal::Sleeping));
+        port.lock().unwrap().drive(3, false);
+
+        vm.core.sleep = Some(SleepMode::Idle);
+        assert_eq!(vm.step(), Err(CpuSignal::Sleeping));
+        port.lock().unwrap().drive(3, true);
+        assert_eq!(vm.step(), Ok(()));
+        assert_eq!(vm.core.pc, vector_address(vm, "INT1"));
+        assert_eq!(vm.core.sleep, None);
+    }
+
+    #[test]
+    fn power_down_stops_timers() {
+        let mut emulator = create_emulator("atmega16");
+        let port = Arc::clone(&emulator.ports["D"]);
+        let vm = &mut emulator.vm;
+        write_ioreg(vm, "TCCR0", 2); // clk/8
+        write_ioreg(vm, "TIMSK", 1 << 0); // TOIE0
+        write_ioreg(vm, "PORTD", 1 << 2); // pull-up on INT0
+        write_ioreg(vm, "GICR", 1 << 6); // INT0 at low level
+        write_ioreg(vm, "MCUCR", 1 << 6 | 1 << 5); // SE, power-down
+
+        // pull INT0 low after 10000 cycles
+        vm.register_clock(ClockDomain::Always, Box::new(move |core| {
+            if core.cycles >= 10_000 {
+                port.lock().unwrap().drive(2, false);
+                None
+            } else {
+                Some(10_000)
+            }
+        }));
+
+        Sleep.execute(vm).unwrap();
+        assert_eq!(vm.core.sleep, Some(SleepMode::PowerDown));
+        assert_eq!(vm.step(), Ok(()));
+        assert_eq!(vm.core.pc, vector_address(vm, "INT0"));
+        assert_eq!(vm.core.sleep, None);
+        assert!(vm.core.cycles >= 10_000);
+
+        // Timer0 only counted during the wake-up
+        assert!(read_ioreg(vm, "TCNT0") < 2);
+        assert_eq!(read_ioreg(vm, "TIFR"), 0);
+    }
+}
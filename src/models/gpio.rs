@@ -0,0 +1,724 @@
+use core::AvrVm;
+use core::AvrCoreState;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::Mutex;
+use ::internals::signals::{Broadcast, BroadcastListener};
+use interrupts::InterruptLevel;
+use models::sleep::ClockDomain;
+
+
+/// port letters in the order of the XMEGA virtual port mapping
+static PORT_NAMES: [&str; 16] = [
+    "A", "B", "C", "D", "E", "F", "G", "H", "J", "K", "L", "M", "N", "P", "Q", "R"
+];
+
+pub type Ports = HashMap<&'static str, Arc<Mutex<Port>>>;
+pub type PinChangeSignal = Broadcast<PinChange>;
+pub type PinChangeConnection = BroadcastListener<PinChange>;
+
+// XMEGA port register offsets
+const DIR: usize = 0x00;
+const DIRSET: usize = 0x01;
+const DIRCLR: usize = 0x02;
+const DIRTGL: usize = 0x03;
+const OUT: usize = 0x04;
+const OUTSET: usize = 0x05;
+const OUTCLR: usize = 0x06;
+const OUTTGL: usize = 0x07;
+const IN: usize = 0x08;
+const INTCTRL: usize = 0x09;
+const INT0MASK: usize = 0x0A;
+const INT1MASK: usize = 0x0B;
+const INTFLAGS: usize = 0x0C;
+const PIN0CTRL: usize = 0x10;
+
+// XMEGA virtual port register offsets
+const VPORT_DIR: usize = 0x00;
+const VPORT_OUT: usize = 0x01;
+const VPORT_IN: usize = 0x02;
+const VPORT_INTFLAGS: usize = 0x03;
+
+/// invert enable of PINnCTRL
+const INVEN: u8 = 1 << 6;
+
+// input/sense configuration (ISC) of PINnCTRL
+const ISC_BOTHEDGES: u8 = 0;
+const ISC_RISING: u8 = 1;
+const ISC_FALLING: u8 = 2;
+const ISC_LEVEL: u8 = 3;
+const ISC_INPUT_DISABLE: u8 = 7;
+
+/// reset values of PORTCFG_VPCTRLA and PORTCFG_VPCTRLB (PORTA-PORTD)
+const VPCTRL_RESET: [u8; 2] = [0x10, 0x32];
+
+
+/// change of a pin level
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PinChange {
+    pub pin: u8,
+    pub level: bool,
+}
+
+
+/// 8-bit I/O port
+///
+/// Pins configured as output drive the OUT (PORTx) value, input pins take the
+/// level driven by the host or, if not driven, the level of the pull
+/// configuration. Undriven floating inputs read low. XMEGA ports raise INT0
+/// and INT1 for sensed pins, classic ports record edges for the external
+/// interrupt model. Slew rate and the pull-up registers of tinyAVR devices are
+/// not modeled.
+pub struct Port {
+    name: &'static str,
+    xmega: bool,
+
+    dir: u8,
+    out: u8,
+
+    /// PINnCTRL of XMEGA ports
+    pinctrl: [u8; 8],
+
+    /// levels of the pins driven by the host
+    input: u8,
+
+    /// pins driven by the host
+    driven: u8,
+
+    /// pin levels after the last change
+    pins: u8,
+
+    /// rising and falling edges since the last `take_edges`
+    edges: (u8, u8),
+
+    /// INTCTRL of XMEGA ports
+    intctrl: u8,
+
+    /// INT0MASK and INT1MASK of XMEGA ports
+    int_mask: [u8; 2],
+
+    /// INTFLAGS of XMEGA ports
+    intflags: u8,
+
+    /// INT0 and INT1 interrupt vectors
+    vectors: [Option<usize>; 2],
+
+    /// flags of requested interrupts
+    raised: u8,
+
+    pin_signal: PinChangeSignal,
+}
+
+impl Port {
+    pub fn new(name: &'static str, xmega: bool) -> Port {
+        Port {
+            name,
+            xmega,
+            dir: 0,
+            out: 0,
+            pinctrl: [0; 8],
+            input: 0,
+            driven: 0,
+            pins: 0,
+            edges: (0, 0),
+            intctrl: 0,
+            int_mask: [0; 2],
+            intflags: 0,
+            vectors: [None; 2],
+            raised: 0,
+            pin_signal: Broadcast::new(),
+        }
+    }
+
+    pub fn get_name(&self) -> &str { self.name }
+
+    /// receive changes of the pin levels
+    pub fn connect_to_pin_changes(&mut self) -> PinChangeConnection {
+        self.pin_signal.create_listener()
+    }
+
+    /// drive input pin `pin` to `level`
+    ///
+    /// Has no effect on the level while the pin is configured as output.
+    pub fn drive(&mut self, pin: u8, level: bool) {
+        self.driven |= 1 << pin;
+        if level {
+            self.input |= 1 << pin;
+        } else {
+            self.input &= !(1 << pin);
+        }
+        self.update();
+    }
+
+    /// stop driving pin `pin`
+    pub fn release(&mut self, pin: u8) {
+        self.driven &= !(1 << pin);
+        self.update();
+    }
+
+    /// current level of pin `pin`
+    pub fn level(&self, pin: u8) -> bool {
+        self.pins & (1 << pin) != 0
+    }
+
+    /// current levels of all pins
+    pub fn pins(&self) -> u8 {
+        self.pins
+    }
+
+    /// pin `pin` is configured as output
+    pub fn is_output(&self, pin: u8) -> bool {
+        self.dir & (1 << pin) != 0
+    }
+
+    /// rising and falling pin edges since the last call
+    pub fn take_edges(&mut self) -> (u8, u8) {
+        let edges = self.edges;
+        self.edges = (0, 0);
+        edges
+    }
+
+    /// pins with inverted input and output (INVEN)
+    fn inverted(&self) -> u8 {
+        (0..8).filter(|&pin| self.pinctrl[pin] & INVEN != 0)
+            .fold(0, |inverted, pin| inverted | (1 << pin))
+    }
+
+    /// level of undriven input pin `pin`
+    fn pull(&self, pin: u8) -> bool {
+        if !self.xmega {
+            return self.out & (1 << pin) != 0;
+        }
+
+        match (self.pinctrl[pin as usize] >> 3) & 0b111 {
+            // bus keeper
+            1 => self.level(pin),
+            // pull-up, wired-AND with pull-up
+            3 | 7 => true,
+            _ => false,
+        }
+    }
+
+    fn compute_pins(&self) -> u8 {
+        let outputs = (self.out ^ self.inverted()) & self.dir;
+        let driven = self.input & self.driven & !self.dir;
+        let pulled = (0..8)
+            .filter(|&pin| (self.dir | self.driven) & (1 << pin) == 0 && self.pull(pin))
+            .fold(0, |pulled, pin| pulled | (1 << pin));
+        outputs | driven | pulled
+    }
+
+    /// recalculate pin levels and signal changes
+    fn update(&mut self) {
+        let pins = self.compute_pins();
+        let changed = pins ^ self.pins;
+        self.pins = pins;
+
+        let rising = changed & pins;
+        let falling = changed & !pins;
+        self.edges.0 |= rising;
+        self.edges.1 |= falling;
+        if self.xmega {
+            self.sense(rising, falling);
+        }
+
+        for pin in (0..8).filter(|&pin| changed & (1 << pin) != 0) {
+            let level = pins & (1 << pin) != 0;
+            info!(
+                target: "avrvc::gpio",
+                "P{}{}: {}", self.name, pin, level as u8);
+            self.pin_signal.send(PinChange { pin, level });
+        }
+    }
+
+    /// pins with input sense configuration `isc`
+    fn sensed_pins(&self, isc: u8) -> u8 {
+        (0..8).filter(|&pin| self.pinctrl[pin] & 0b111 == isc)
+            .fold(0, |pins, pin| pins | (1 << pin))
+    }
+
+    /// set interrupt flags of XMEGA ports for pin edges and low levels
+    fn sense(&mut self, rising: u8, falling: u8) {
+        // edges of the inverted input
+        let inverted = self.inverted();
+        let rising_in = (rising & !inverted) | (falling & inverted);
+        let falling_in = (falling & !inverted) | (rising & inverted);
+
+        let triggered = (self.sensed_pins(ISC_BOTHEDGES) & (rising_in | falling_in))
+            | (self.sensed_pins(ISC_RISING) & rising_in)
+            | (self.sensed_pins(ISC_FALLING) & falling_in)
+            | (self.sensed_pins(ISC_LEVEL) & !self.input_register());
+
+        for int in 0..2 {
+            if self.int_mask[int] & triggered != 0 {
+                self.intflags |= 1 << int;
+            }
+        }
+    }
+
+    /// raise INT0 and INT1 of set flags and withdraw them for cleared flags
+    fn sync_irq(&mut self, core: &mut AvrCoreState) {
+        for int in 0..2 {
+            if let Some(vector) = self.vectors[int] {
+                let flag = 1 << int;
+                let level = InterruptLevel::from_bits(self.intctrl >> (2 * int));
+                if self.intflags & flag != 0 && level != InterruptLevel::Off {
+                    core.irq.raise_level(vector, level);
+                    self.raised |= flag;
+                } else if self.raised & flag != 0 {
+                    core.irq.clear(vector);
+                    self.raised &= !flag;
+                }
+            }
+        }
+    }
+
+    /// low level sensed pins request interrupts as long as they are low
+    fn clock(&mut self, core: &mut AvrCoreState) -> Option<u64> {
+        self.sense(0, 0);
+        self.sync_irq(core);
+        None
+    }
+
+    fn acknowledge(&mut self, vector: usize) {
+        for int in 0..2 {
+            if self.vectors[int] == Some(vector) {
+                self.intflags &= !(1 << int);
+                self.raised &= !(1 << int);
+            }
+        }
+    }
+
+    /// set all registers to zero, pins driven by the host stay driven
+    fn reset(&mut self) {
+        self.dir = 0;
+        self.out = 0;
+        self.pinctrl = [0; 8];
+        self.intctrl = 0;
+        self.int_mask = [0; 2];
+        self.intflags = 0;
+        self.raised = 0;
+        self.update();
+    }
+
+    /// value of the IN (PINx) register
+    fn input_register(&self) -> u8 {
+        (self.pins ^ self.inverted()) & !self.sensed_pins(ISC_INPUT_DISABLE)
+    }
+
+    fn read(&mut self, _core: &AvrCoreState, offset: usize, _view: bool) -> u8 {
+        match offset {
+            DIR | DIRSET | DIRCLR | DIRTGL => self.dir,
+            OUT | OUTSET | OUTCLR | OUTTGL => self.out,
+            IN => self.input_register(),
+            INTCTRL => self.intctrl,
+            INT0MASK => self.int_mask[0],
+            INT1MASK => self.int_mask[1],
+            INTFLAGS => self.intflags,
+            _ if (PIN0CTRL..PIN0CTRL + 8).contains(&offset) => self.pinctrl[offset - PIN0CTRL],
+            _ => 0,
+        }
+    }
+
+    fn write(&mut self, core: &mut AvrCoreState, offset: usize, value: u8) {
+        match offset {
+            DIR => self.dir = value,
+            DIRSET => self.dir |= value,
+            DIRCLR => self.dir &= !value,
+            DIRTGL => self.dir ^= value,
+            OUT => self.out = value,
+            OUTSET => self.out |= value,
+            OUTCLR => self.out &= !value,
+            OUTTGL => self.out ^= value,
+            INTCTRL => self.intctrl = value & 0x0F,
+            INT0MASK => self.int_mask[0] = value,
+            INT1MASK => self.int_mask[1] = value,
+            INTFLAGS => self.intflags &= !value,
+            _ if (PIN0CTRL..PIN0CTRL + 8).contains(&offset) =>
+                self.pinctrl[offset - PIN0CTRL] = value & 0x7F,
+            _ => (),
+        }
+        self.update();
+        self.sync_irq(core);
+    }
+
+    fn ddr_read(&mut self, _core: &AvrCoreState, _view: bool) -> u8 {
+        self.dir
+    }
+
+    fn ddr_write(&mut self, _core: &mut AvrCoreState, value: u8) {
+        self.dir = value;
+        self.update();
+    }
+
+    fn port_read(&mut self, _core: &AvrCoreState, _view: bool) -> u8 {
+        self.out
+    }
+
+    fn port_write(&mut self, _core: &mut AvrCoreState, value: u8) {
+        self.out = value;
+        self.update();
+    }
+
+    fn pin_read(&mut self, _core: &AvrCoreState, _view: bool) -> u8 {
+        self.input_register()
+    }
+
+    /// writing one toggles PORTx
+    fn pin_write(&mut self, _core: &mut AvrCoreState, value: u8) {
+        self.out ^= value;
+        self.update();
+    }
+}
+
+
+/// mapping of XMEGA virtual ports VPORT0-3 to ports (PORTCFG_VPCTRLA/B)
+struct VirtualPorts {
+    /// ports by map value
+    ports: Vec<Option<Arc<Mutex<Port>>>>,
+
+    /// VPCTRLA and VPCTRLB
+    ctrl: [u8; 2],
+}
+
+impl VirtualPorts {
+    fn port(&self, vport: usize) -> Option<Arc<Mutex<Port>>> {
+        let map = (self.ctrl[vport / 2] >> (4 * (vport % 2))) & 0x0F;
+        self.ports[map as usize].clone()
+    }
+
+    fn read(&self, core: &AvrCoreState, vport: usize, offset: usize, view: bool) -> u8 {
+        match self.port(vport) {
+            Some(port) => port.lock().unwrap().read(core, offset, view),
+            None => 0,
+        }
+    }
+
+    fn write(&self, core: &mut AvrCoreState, vport: usize, offset: usize, value: u8) {
+        if let Some(port) = self.port(vport) {
+            port.lock().unwrap().write(core, offset, value);
+        }
+    }
+}
+
+
+pub fn register_ports(vm: &mut AvrVm) -> Ports {
+    let ports: Ports = PORT_NAMES.iter().filter_map(
+        |&name| register_one_port(vm, name).map(|port| (name, port))
+    ).collect();
+
+    if vm.info.xmega {
+        register_virtual_ports(vm, &ports);
+    }
+    ports
+}
+
+fn register_one_port(vm: &mut AvrVm, name: &'static str) -> Option<Arc<Mutex<Port>>> {
+    let ioregs = vm.info.io_regs.clone();
+    let base = *ioregs.get(&*format!("PORT{}", name))?;
+
+    let port = Arc::new(Mutex::new(Port::new(name, vm.info.xmega)));
+    if vm.info.xmega {
+        let offsets = [
+            DIR, DIRSET, DIRCLR, DIRTGL, OUT, OUTSET, OUTCLR, OUTTGL, IN,
+            INTCTRL, INT0MASK, INT1MASK, INTFLAGS
+        ];
+        for offset in offsets.iter().cloned().chain(PIN0CTRL..PIN0CTRL + 8) {
+            let port1 = Arc::clone(&port);
+            let port2 = Arc::clone(&port);
+            vm.register_io(
+                base + offset,
+                Box::new(move |core, _, view| port1.lock().unwrap().read(core, offset, view)),
+                Box::new(move |core, _, value| port2.lock().unwrap().write(core, offset, value))
+            );
+        }
+
+        let vectors = [
+            vm.info.vectors.get(&*format!("PORT{}_INT0", name)).cloned(),
+            vm.info.vectors.get(&*format!("PORT{}_INT1", name)).cloned(),
+        ];
+        port.lock().unwrap().vectors = vectors;
+        for &vector in vectors.iter().filter_map(|vector| vector.as_ref()) {
+            let port8 = Arc::clone(&port);
+            vm.register_interrupt_ack(
+                vector,
+                Box::new(move |_, vector| port8.lock().unwrap().acknowledge(vector))
+            );
+        }
+        if vectors.iter().any(|vector| vector.is_some()) {
+            let port9 = Arc::clone(&port);
+            vm.register_clock(
+                ClockDomain::Always,
+                Box::new(move |core| port9.lock().unwrap().clock(core))
+            );
+        }
+    } else {
+        let ddr = *ioregs.get(&*format!("DDR{}", name))?;
+        let pin = *ioregs.get(&*format!("PIN{}", name))?;
+        let port1 = Arc::clone(&port);
+        let port2 = Arc::clone(&port);
+        let port3 = Arc::clone(&port);
+        let port4 = Arc::clone(&port);
+        let port5 = Arc::clone(&port);
+        let port6 = Arc::clone(&port);
+
+        vm.register_io(
+            ddr,
+            Box::new(move |core, _, view| port1.lock().unwrap().ddr_read(core, view)),
+            Box::new(move |core, _, value| port2.lock().unwrap().ddr_write(core, value))
+        );
+        vm.register_io(
+            base,
+            Box::new(move |core, _, view| port3.lock().unwrap().port_read(core, view)),
+            Box::new(move |core, _, value| port4.lock().unwrap().port_write(core, value))
+        );
+        vm.register_io(
+            pin,
+            Box::new(move |core, _, view| port5.lock().unwrap().pin_read(core, view)),
+            Box::new(move |core, _, value| port6.lock().unwrap().pin_write(core, value))
+        );
+    }
+
+    let port7 = Arc::clone(&port);
+    vm.register_reset(Box::new(move |_, _| port7.lock().unwrap().reset()));
+
+    Some(port)
+}
+
+fn register_virtual_ports(vm: &mut AvrVm, ports: &Ports) {
+    let ioregs = vm.info.io_regs.clone();
+    let vports = Arc::new(Mutex::new(VirtualPorts {
+        ports: PORT_NAMES.iter().map(|name| ports.get(name).cloned()).collect(),
+        ctrl: VPCTRL_RESET,
+    }));
+
+    for vport in 0..4 {
+        let base = match ioregs.get(&*format!("VPORT{}", vport)) {
+            Some(&base) => base,
+            None => continue,
+        };
+        for &offset in [VPORT_DIR, VPORT_OUT, VPORT_IN, VPORT_INTFLAGS].iter() {
+            let port_offset = [DIR, OUT, IN, INTFLAGS][offset];
+            let vports1 = Arc::clone(&vports);
+            let vports2 = Arc::clone(&vports);
+            vm.register_io(
+                base + offset,
+                Box::new(move |core, _, view|
+                    vports1.lock().unwrap().read(core, vport, port_offset, view)),
+                Box::new(move |core, _, value|
+                    vports2.lock().unwrap().write(core, vport, port_offset, value))
+            );
+        }
+    }
+
+    for (i, name) in ["PORTCFG_VPCTRLA", "PORTCFG_VPCTRLB"].iter().enumerate() {
+        if let Some(&addr) = ioregs.get(name) {
+            let vports3 = Arc::clone(&vports);
+            let vports4 = Arc::clone(&vports);
+            vm.register_io(
+                addr,
+                Box::new(move |_, _, _| vports3.lock().unwrap().ctrl[i]),
+                Box::new(move |_, _, value| vports4.lock().unwrap().ctrl[i] = value)
+            );
+        }
+    }
+
+    vm.register_reset(Box::new(move |_, _| vports.lock().unwrap().ctrl = VPCTRL_RESET));
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use emulator::AvrEmulator;
+    use futures::Future;
+    use futures::Stream;
+    use models::test_util::prepare_vm;
+    use models::test_util::vector_address;
+    use models::test_util::TEST_PC;
+
+    fn write_ioreg(vm: &mut AvrVm, name: &str, offset: usize, value: u8) {
+        let addr = vm.info.io_regs[name] + offset;
+        vm.write_io(addr, value);
+    }
+
+    fn read_ioreg(vm: &mut AvrVm, name: &str, offset: usize) -> u8 {
+        let addr = vm.info.io_regs[name] + offset;
+        vm.read_io(addr, false)
+    }
+
+    #[test]
+    fn xmega_registers() {
+        let mut emulator = AvrEmulator::from_name("atxmega128a4u");
+        let port = Arc::clone(&emulator.ports["C"]);
+        let vm = &mut emulator.vm;
+
+        write_ioreg(vm, "PORTC", DIR, 0x0F);
+        write_ioreg(vm, "PORTC", DIRSET, 0x30);
+        write_ioreg(vm, "PORTC", DIRCLR, 0x01);
+        write_ioreg(vm, "PORTC", DIRTGL, 0x81);
+        assert_eq!(read_ioreg(vm, "PORTC", DIR), 0xBF);
+
+        write_ioreg(vm, "PORTC", OUT, 0x03);
+        write_ioreg(vm, "PORTC", OUTSET, 0x40);
+        write_ioreg(vm, "PORTC", OUTCLR, 0x01);
+        write_ioreg(vm, "PORTC", OUTTGL, 0x84);
+        assert_eq!(read_ioreg(vm, "PORTC", OUTSET), 0xC6);
+
+        // input pin 6 reads low, output pin 7 high
+        assert_eq!(read_ioreg(vm, "PORTC", IN), 0x86);
+        assert_eq!(port.lock().unwrap().pins(), 0x86);
+
+        // IN is read-only
+        write_ioreg(vm, "PORTC", IN, 0xFF);
+        assert_eq!(read_ioreg(vm, "PORTC", IN), 0x86);
+    }
+
+    #[test]
+    fn xmega_pin_control() {
+        let mut emulator = AvrEmulator::from_name("atxmega128a4u");
+        let port = Arc::clone(&emulator.ports["D"]);
+        let vm = &mut emulator.vm;
+
+        // pull-up
+        write_ioreg(vm, "PORTD", PIN0CTRL + 2, 0x18);
+        assert_eq!(read_ioreg(vm, "PORTD", IN), 0x04);
+        port.lock().unwrap().drive(2, false);
+        assert_eq!(read_ioreg(vm, "PORTD", IN), 0x00);
+        port.lock().unwrap().release(2);
+        assert_eq!(read_ioreg(vm, "PORTD", IN), 0x04);
+
+        // bus keeper holds the last level
+        port.lock().unwrap().drive(3, true);
+        write_ioreg(vm, "PORTD", PIN0CTRL + 3, 0x08);
+        port.lock().unwrap().release(3);
+        assert!(port.lock().unwrap().level(3));
+
+        // inverted output and input
+        write_ioreg(vm, "PORTD", PIN0CTRL + 4, INVEN);
+        write_ioreg(vm, "PORTD", DIRSET, 0x10);
+        assert!(port.lock().unwrap().level(4));
+        assert_eq!(read_ioreg(vm, "PORTD", IN) & 0x10, 0x00);
+    }
+
+    #[test]
+    fn xmega_virtual_ports() {
+        let mut emulator = AvrEmulator::from_name("atxmega128a4u");
+        let port = Arc::clone(&emulator.ports["C"]);
+        let vm = &mut emulator.vm;
+
+        // VPORT2 is PORTC after reset
+        write_ioreg(vm, "VPORT2", VPORT_DIR, 0x01);
+        write_ioreg(vm, "VPORT2", VPORT_OUT, 0x01);
+        assert_eq!(read_ioreg(vm, "PORTC", OUT), 0x01);
+        assert_eq!(read_ioreg(vm, "VPORT2", VPORT_IN), 0x01);
+
+        // map VPORT0 to PORTC and VPORT1 to PORTR
+        write_ioreg(vm, "PORTCFG_VPCTRLA", 0, 0xF2);
+        port.lock().unwrap().drive(1, true);
+        assert_eq!(read_ioreg(vm, "VPORT0", VPORT_IN), 0x03);
+        write_ioreg(vm, "VPORT1", VPORT_DIR, 0x02);
+        assert_eq!(read_ioreg(vm, "PORTR", DIR), 0x02);
+    }
+
+    #[test]
+    fn xmega_interrupts() {
+        let mut emulator = AvrEmulator::from_name("atxmega128a4u");
+        let port = Arc::clone(&emulator.ports["C"]);
+        let vm = &mut emulator.vm;
+        prepare_vm(vm);
+        vm.core.interrupt = true;
+        write_ioreg(vm, "PMIC_CTRL", 0, 0x07);
+
+        // INT0 on rising edge of pin 0, INT1 on low level of pin 1
+        write_ioreg(vm, "PORTC", PIN0CTRL, ISC_RISING);
+        write_ioreg(vm, "PORTC", PIN0CTRL + 1, ISC_LEVEL);
+        write_ioreg(vm, "PORTC", INT0MASK, 0x01);
+        write_ioreg(vm, "PORTC", INT1MASK, 0x02);
+        assert_eq!(read_ioreg(vm, "PORTC", INTFLAGS), 0x02);
+
+        port.lock().unwrap().drive(1, true);
+        write_ioreg(vm, "PORTC", INTFLAGS, 0x02);
+        port.lock().unwrap().drive(0, true);
+        assert_eq!(read_ioreg(vm, "VPORT2", VPORT_INTFLAGS), 0x01);
+
+        // flag is requested at INTCTRL level and cleared by the vector
+        vm.step().unwrap();
+        assert_eq!(vm.core.pc, TEST_PC + 1);
+        write_ioreg(vm, "PORTC", INTCTRL, 0x01);
+        vm.step().unwrap();
+        assert_eq!(vm.core.pc, vector_address(vm, "PORTC_INT0"));
+        assert_eq!(read_ioreg(vm, "PORTC", INTFLAGS), 0x00);
+
+        // low level stays pending
+        write_ioreg(vm, "PORTC", INTCTRL, 0x08);
+        port.lock().unwrap().drive(1, false);
+        vm.step().unwrap();
+        vm.step().unwrap();
+        assert_eq!(vm.core.pc, vector_address(vm, "PORTC_INT1"));
+        vm.step().unwrap();
+        assert_eq!(read_ioreg(vm, "PORTC", INTFLAGS), 0x02);
+    }
+
+    #[test]
+    fn classic_registers() {
+        let mut emulator = AvrEmulator::from_name("atmega16");
+        let port = Arc::clone(&emulator.ports["B"]);
+        let vm = &mut emulator.vm;
+
+        write_ioreg(vm, "DDRB", 0, 0x0F);
+        write_ioreg(vm, "PORTB", 0, 0x35);
+        assert_eq!(read_ioreg(vm, "DDRB", 0), 0x0F);
+        assert_eq!(read_ioreg(vm, "PORTB", 0), 0x35);
+
+        // pull-ups on pins 4 and 5
+        assert_eq!(read_ioreg(vm, "PINB", 0), 0x35);
+        port.lock().unwrap().drive(4, false);
+        port.lock().unwrap().drive(7, true);
+        assert_eq!(read_ioreg(vm, "PINB", 0), 0xA5);
+
+        // writing PINx toggles PORTx
+        write_ioreg(vm, "PINB", 0, 0x03);
+        assert_eq!(read_ioreg(vm, "PORTB", 0), 0x36);
+        assert_eq!(read_ioreg(vm, "PINB", 0), 0xA6);
+    }
+
+    #[test]
+    fn pin_changes() {
+        let mut emulator = AvrEmulator::from_name("atmega8");
+        let port = Arc::clone(&emulator.ports["D"]);
+        let changes = port.lock().unwrap().connect_to_pin_changes();
+        let vm = &mut emulator.vm;
+
+        write_ioreg(vm, "DDRD", 0, 0x01);
+        write_ioreg(vm, "PORTD", 0, 0x01);
+        port.lock().unwrap().drive(5, true);
+        // no change
+        port.lock().unwrap().drive(0, false);
+        write_ioreg(vm, "PORTD", 0, 0x00);
+        drop(emulator);
+        drop(port);
+
+        assert_eq!(changes.collect().wait().unwrap(), vec![
+            PinChange { pin: 0, level: true },
+            PinChange { pin: 5, level: true },
+            PinChange { pin: 0, level: false },
+        ]);
+    }
+
+    #[test]
+    fn reset_ports() {
+        let mut emulator = AvrEmulator::from_name("atmega8");
+        let port = Arc::clone(&emulator.ports["C"]);
+        let vm = &mut emulator.vm;
+
+        write_ioreg(vm, "DDRC", 0, 0x01);
+        write_ioreg(vm, "PORTC", 0, 0x03);
+        port.lock().unwrap().drive(2, true);
+        assert_eq!(port.lock().unwrap().pins(), 0x07);
+
+        vm.reset(::core::ResetSource::External);
+        assert_eq!(read_ioreg(vm, "DDRC", 0), 0);
+        assert_eq!(port.lock().unwrap().pins(), 0x04);
+    }
+}
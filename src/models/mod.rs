@@ -4,7 +4,17 @@ use emulator::AvrEmulator;
 pub mod xmega_au;
 pub mod register_service;
 pub mod usart;
+pub mod pmic;
+pub mod sleep;
+pub mod nvm;
+pub mod reset;
+pub mod timer;
+pub mod classic_timer;
+pub mod gpio;
+pub mod exint;
 pub mod envmodel;
+#[cfg(test)]
+pub mod test_util;
 
 
 /// model of avr controller type
@@ -80,4 +90,12 @@ pub fn register_gpio(vm: &mut AvrVm) {
             Box::new(|core, _, value| core.rampz = value)
         )
     );
-}
\ No newline at end of file
+
+    ioregs.get("EIND").map(|&eind|
+        vm.register_io(
+            eind,
+            Box::new(|core, _, _| core.eind),
+            Box::new(|core, _, value| core.eind = value)
+        )
+    );
+}
@@ -0,0 +1,617 @@
+use core::AvrVm;
+use core::AvrCoreState;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::ops::Range;
+use byte_convert::bit_at;
+
+
+/// cycles of a flash page erase or write (about 4 ms at 1 MHz)
+const CLASSIC_PAGE_CYCLES: u64 = 4_000;
+
+/// cycles of a flash page erase or write (about 4 ms at 2 MHz)
+const XMEGA_PAGE_CYCLES: u64 = 8_000;
+
+/// cycles SPM can be executed after setting SPMEN
+const SPMEN_TIMEOUT: u64 = 4;
+
+// SPMCR bits
+const SPMEN: u8 = 1 << 0;
+const PGERS: u8 = 1 << 1;
+const PGWRT: u8 = 1 << 2;
+const BLBSET: u8 = 1 << 3;
+const RWWSRE: u8 = 1 << 4;
+const RWWSB: u8 = 1 << 6;
+const SPMIE: u8 = 1 << 7;
+
+// NVM_CTRLB bits
+const EEMAPEN: u8 = 1 << 3;
+
+// NVM_CMD values executed by SPM
+const CMD_ERASE_APP_PAGE: u8 = 0x22;
+const CMD_LOAD_FLASH_BUFFER: u8 = 0x23;
+const CMD_WRITE_APP_PAGE: u8 = 0x24;
+const CMD_ERASE_WRITE_APP_PAGE: u8 = 0x25;
+const CMD_ERASE_FLASH_BUFFER: u8 = 0x26;
+const CMD_ERASE_BOOT_PAGE: u8 = 0x2A;
+const CMD_ERASE_FLASH_PAGE: u8 = 0x2B;
+const CMD_WRITE_BOOT_PAGE: u8 = 0x2C;
+const CMD_ERASE_WRITE_BOOT_PAGE: u8 = 0x2D;
+const CMD_WRITE_FLASH_PAGE: u8 = 0x2E;
+const CMD_ERASE_WRITE_FLASH_PAGE: u8 = 0x2F;
+
+/// flash section a XMEGA page command is restricted to
+#[derive(Copy, Clone, PartialEq)]
+enum Section { App, Boot, Any }
+
+
+/// NVM controller for self-programming with SPM
+///
+/// Classic devices are controlled by SPMCR (SPMCSR), XMEGA devices by the NVM
+/// command register. CCP protection of XMEGA devices is not modeled.
+pub struct Nvm {
+    xmega: bool,
+
+    /// temporary page buffer
+    page_buffer: Vec<u16>,
+
+    /// page buffer was loaded since last erase (FLOAD)
+    buffer_loaded: bool,
+
+    /// start of no-read-while-write section in bytes
+    nrww_section: usize,
+
+    /// control bits of SPMCR (classic)
+    spmcr: u8,
+
+    /// cycle count until SPMEN and the command bits are valid (classic)
+    spmen_until: u64,
+
+    /// NVM_CMD (XMEGA)
+    cmd: u8,
+
+    /// NVM_CTRLB (XMEGA)
+    ctrlb: u8,
+
+    /// read-while-write section is written
+    rww_busy: bool,
+
+    /// cycle count when current page erase or write is finished
+    busy_until: u64,
+}
+
+impl Nvm {
+    pub fn new(xmega: bool, page_size: usize, nrww_section: usize) -> Nvm {
+        Nvm {
+            xmega,
+            page_buffer: vec![0xFFFF; page_size / 2],
+            buffer_loaded: false,
+            nrww_section,
+            spmcr: 0,
+            spmen_until: 0,
+            cmd: 0,
+            ctrlb: 0,
+            rww_busy: false,
+            busy_until: 0,
+        }
+    }
+
+    pub fn page_size(&self) -> usize { self.page_buffer.len() * 2 }
+
+    /// page erase or write in progress
+    pub fn is_busy(&self, core: &AvrCoreState) -> bool {
+        core.cycles < self.busy_until
+    }
+
+    /// read-while-write section can not be read
+    ///
+    /// Classic devices need RWWSRE to enable the section again.
+    pub fn is_rww_busy(&self, core: &AvrCoreState) -> bool {
+        self.rww_busy && (!self.xmega || self.is_busy(core))
+    }
+
+    /// flash byte `addr` can be read by LPM
+    pub fn is_readable(&self, core: &AvrCoreState, addr: usize) -> bool {
+        addr >= self.nrww_section || !self.is_rww_busy(core)
+    }
+
+    /// EEPROM is mapped into data space
+    ///
+    /// XMEGA devices need EEMAPEN, classic devices do not map EEPROM.
+    pub fn is_eeprom_mapped(&self) -> bool {
+        !self.xmega || self.ctrlb & EEMAPEN != 0
+    }
+
+    /// abort operations and restore reset values of the registers
+    fn reset(&mut self) {
+        self.erase_buffer();
+        self.spmcr = 0;
+        self.spmen_until = 0;
+        self.cmd = 0;
+        self.ctrlb = 0;
+        self.rww_busy = false;
+        self.busy_until = 0;
+    }
+
+    fn erase_buffer(&mut self) {
+        for word in self.page_buffer.iter_mut() {
+            *word = 0xFFFF;
+        }
+        self.buffer_loaded = false;
+    }
+
+    fn load_buffer(&mut self, core: &AvrCoreState, z: usize) {
+        let index = (z % self.page_size()) / 2;
+        self.page_buffer[index] = core.read_reg16(0);
+        self.buffer_loaded = true;
+    }
+
+    /// erase and/or write page containing `z`
+    fn page_operation(
+        &mut self, core: &mut AvrCoreState, z: usize, erase: bool, write: bool
+    ) -> Option<Range<usize>> {
+        let start = z - z % self.page_size();
+        let end = start + self.page_size();
+        if end > core.flash.len() {
+            return None;
+        }
+
+        if erase {
+            for byte in core.flash[start..end].iter_mut() {
+                *byte = 0xFF;
+            }
+        }
+        if write {
+            // programming can only clear bits
+            for (i, word) in self.page_buffer.iter().enumerate() {
+                core.flash[start + 2 * i] &= *word as u8;
+                core.flash[start + 2 * i + 1] &= (*word >> 8) as u8;
+            }
+            self.erase_buffer();
+        }
+
+        let cycles = if self.xmega { XMEGA_PAGE_CYCLES } else { CLASSIC_PAGE_CYCLES }
+            * (erase as u64 + write as u64);
+        if start < self.nrww_section {
+            // code in no-read-while-write section continues
+            self.rww_busy = true;
+            self.busy_until = core.cycles + cycles;
+        } else {
+            // cpu halted until operation is finished
+            core.cycles += cycles;
+            self.busy_until = core.cycles;
+        }
+
+        Some(start..end)
+    }
+
+    /// execute SPM with byte address `z`
+    ///
+    /// page erase and write are ignored if the page is not `writable` because
+    /// of lock bits. returns the flash bytes changed.
+    pub fn spm(
+        &mut self, core: &mut AvrCoreState, z: usize, writable: bool
+    ) -> Option<Range<usize>> {
+        if self.xmega {
+            self.spm_xmega(core, z, writable)
+        } else {
+            self.spm_classic(core, z, writable)
+        }
+    }
+
+    fn spm_classic(
+        &mut self, core: &mut AvrCoreState, z: usize, writable: bool
+    ) -> Option<Range<usize>> {
+        if self.spmcr & SPMEN == 0 || core.cycles > self.spmen_until || self.is_busy(core) {
+            return None;
+        }
+
+        let command = self.spmcr & (PGERS | PGWRT | BLBSET | RWWSRE);
+        self.spmen_until = 0;
+        match command {
+            0 => self.load_buffer(core, z),
+            PGERS | PGWRT if writable => {
+                return self.page_operation(core, z, command == PGERS, command == PGWRT);
+            }
+            RWWSRE => {
+                self.rww_busy = false;
+                self.erase_buffer();
+            }
+            // lock bits are not modeled
+            _ => {}
+        }
+
+        None
+    }
+
+    fn spm_xmega(
+        &mut self, core: &mut AvrCoreState, z: usize, writable: bool
+    ) -> Option<Range<usize>> {
+        if self.is_busy(core) {
+            return None;
+        }
+
+        let (section, erase, write) = match self.cmd {
+            CMD_LOAD_FLASH_BUFFER => {
+                self.load_buffer(core, z);
+                return None;
+            }
+            CMD_ERASE_APP_PAGE => (Section::App, true, false),
+            CMD_WRITE_APP_PAGE => (Section::App, false, true),
+            CMD_ERASE_WRITE_APP_PAGE => (Section::App, true, true),
+            CMD_ERASE_BOOT_PAGE => (Section::Boot, true, false),
+            CMD_WRITE_BOOT_PAGE => (Section::Boot, false, true),
+            CMD_ERASE_WRITE_BOOT_PAGE => (Section::Boot, true, true),
+            CMD_ERASE_FLASH_PAGE => (Section::Any, true, false),
+            CMD_WRITE_FLASH_PAGE => (Section::Any, false, true),
+            CMD_ERASE_WRITE_FLASH_PAGE => (Section::Any, true, true),
+            _ => return None,
+        };
+
+        let in_section = match section {
+            Section::App => z < self.nrww_section,
+            Section::Boot => z >= self.nrww_section,
+            Section::Any => true,
+        };
+        if !in_section || !writable {
+            return None;
+        }
+
+        self.page_operation(core, z, erase, write)
+    }
+
+    fn spmcr_read(&mut self, core: &AvrCoreState, _view: bool) -> u8 {
+        let mut value = self.spmcr & SPMIE;
+        // SPMEN is cleared when page erase or write is finished
+        if core.cycles <= self.spmen_until || self.is_busy(core) {
+            value |= self.spmcr & !SPMIE;
+        }
+        if self.rww_busy {
+            value |= RWWSB;
+        }
+        value
+    }
+
+    fn spmcr_write(&mut self, core: &mut AvrCoreState, value: u8) {
+        if self.is_busy(core) {
+            return;
+        }
+
+        self.spmcr = value & !RWWSB;
+        if value & SPMEN != 0 {
+            self.spmen_until = core.cycles + SPMEN_TIMEOUT;
+        }
+    }
+
+    fn cmd_read(&mut self, _core: &AvrCoreState, _view: bool) -> u8 {
+        self.cmd
+    }
+
+    fn cmd_write(&mut self, _core: &mut AvrCoreState, value: u8) {
+        self.cmd = value & 0x7F;
+    }
+
+    fn ctrla_write(&mut self, _core: &mut AvrCoreState, value: u8) {
+        // CMDEX
+        if bit_at(value, 0) && self.cmd == CMD_ERASE_FLASH_BUFFER {
+            self.erase_buffer();
+        }
+    }
+
+    fn ctrlb_read(&mut self, _core: &AvrCoreState, _view: bool) -> u8 {
+        self.ctrlb
+    }
+
+    fn ctrlb_write(&mut self, _core: &mut AvrCoreState, value: u8) {
+        // EPRM, FPRM, EEMAPEN and EESAVE
+        self.ctrlb = value & 0x0F;
+    }
+
+    fn status_read(&mut self, core: &AvrCoreState, _view: bool) -> u8 {
+        let busy = self.is_busy(core) as u8;
+        (busy << 7) | (busy << 6) | self.buffer_loaded as u8
+    }
+}
+
+
+pub fn register_nvm(vm: &mut AvrVm) -> Option<Arc<Mutex<Nvm>>> {
+    let ioregs = vm.info.io_regs.clone();
+    let page_size = vm.info.spm_pagesize;
+    let nrww_section = vm.info.nrww_section.unwrap_or(0);
+
+    let nvm = if let Some(&spmcr) = ioregs.get("SPMCR").or_else(|| ioregs.get("SPMCSR")) {
+        let nvm = Arc::new(Mutex::new(Nvm::new(false, page_size, nrww_section)));
+        let nvm1 = Arc::clone(&nvm);
+        let nvm2 = Arc::clone(&nvm);
+
+        vm.register_io(
+            spmcr,
+            Box::new(move |core, _, view| nvm1.lock().unwrap().spmcr_read(core, view)),
+            Box::new(move |core, _, value| nvm2.lock().unwrap().spmcr_write(core, value))
+        );
+        nvm
+    } else if ioregs.contains_key("NVM_CMD") {
+        let nvm = Arc::new(Mutex::new(Nvm::new(true, page_size, nrww_section)));
+        let nvm1 = Arc::clone(&nvm);
+        let nvm2 = Arc::clone(&nvm);
+        let nvm3 = Arc::clone(&nvm);
+        let nvm4 = Arc::clone(&nvm);
+        let nvm5 = Arc::clone(&nvm);
+        let nvm6 = Arc::clone(&nvm);
+
+        vm.register_io(
+            ioregs["NVM_CMD"],
+            Box::new(move |core, _, view| nvm1.lock().unwrap().cmd_read(core, view)),
+            Box::new(move |core, _, value| nvm2.lock().unwrap().cmd_write(core, value))
+        );
+        vm.register_io(
+            ioregs["NVM_CTRLA"],
+            Box::new(|_, _, _| 0),
+            Box::new(move |core, _, value| nvm3.lock().unwrap().ctrla_write(core, value))
+        );
+        vm.register_io(
+            ioregs["NVM_CTRLB"],
+            Box::new(move |core, _, view| nvm5.lock().unwrap().ctrlb_read(core, view)),
+            Box::new(move |core, _, value| nvm6.lock().unwrap().ctrlb_write(core, value))
+        );
+        vm.register_io(
+            ioregs["NVM_STATUS"],
+            Box::new(move |core, _, view| nvm4.lock().unwrap().status_read(core, view)),
+            Box::new(|_, _, _| ())
+        );
+        nvm
+    } else {
+        return None
+    };
+
+    let nvm_reset = Arc::clone(&nvm);
+    vm.register_reset(Box::new(move |_, _| nvm_reset.lock().unwrap().reset()));
+
+    vm.nvm = Some(Arc::clone(&nvm));
+    Some(nvm)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use decoder::Decoder;
+    use instruction_set::Instruction;
+    use instruction_set::Instruction::*;
+    use core::CpuSignal;
+    use models::test_util::create_emulator;
+    use core::ResetSource;
+    use fuses::SectionLock;
+
+    fn create_vm(mcu: &str, pc: usize) -> AvrVm {
+        let mut vm = create_emulator(mcu).vm;
+        vm.core.pc = pc;
+        vm
+    }
+
+    fn spmcr(vm: &mut AvrVm, value: u8) {
+        let addr = vm.info.io_regs["SPMCR"];
+        vm.write_io(addr, value);
+    }
+
+    fn nvm_cmd(vm: &mut AvrVm, value: u8) {
+        let addr = vm.info.io_regs["NVM_CMD"];
+        vm.write_io(addr, value);
+    }
+
+    fn load_classic(vm: &mut AvrVm, addr: u16, word: u16) {
+        vm.core.write_reg16(0, word);
+        vm.core.write_z(addr);
+        spmcr(vm, SPMEN);
+        Spm.execute(vm).unwrap();
+    }
+
+    fn execute_at(vm: &mut AvrVm, pc: usize, instr: Instruction) {
+        vm.core.pc = pc;
+        instr.execute(vm).unwrap();
+    }
+
+    #[test]
+    fn classic_read_while_write() {
+        // bootloader in NRWW section
+        let mut vm = create_vm("atmega16", 0x1C00);
+        let page = 0x0100u16;
+
+        load_classic(&mut vm, page, 0xE28A); // ldi r24, 0x2A
+        load_classic(&mut vm, page + 2, 0x9508); // ret
+
+        vm.core.write_z(page);
+        spmcr(&mut vm, PGERS | SPMEN);
+        Spm.execute(&mut vm).unwrap();
+
+        // cpu continues while RWW section is busy
+        assert!(vm.core.cycles < CLASSIC_PAGE_CYCLES);
+        assert_eq!(vm.read_io(0x57, true), RWWSB | PGERS | SPMEN);
+
+        // wait for SPMEN
+        vm.core.cycles += CLASSIC_PAGE_CYCLES;
+        assert_eq!(vm.read_io(0x57, true), RWWSB);
+        spmcr(&mut vm, PGWRT | SPMEN);
+        Spm.execute(&mut vm).unwrap();
+
+        assert_eq!(vm.read_io(0x57, true) & RWWSB, RWWSB);
+        execute_at(&mut vm, 0x1C00, Lpm { d: 16 });
+        assert_eq!(vm.core.read_reg(16), 0xFF);
+
+        // RWW section stays disabled until RWWSRE
+        vm.core.cycles += 2 * CLASSIC_PAGE_CYCLES;
+        assert_eq!(vm.read_io(0x57, true), RWWSB);
+        spmcr(&mut vm, RWWSRE | SPMEN);
+        Spm.execute(&mut vm).unwrap();
+        assert_eq!(vm.read_io(0x57, true), 0);
+
+        execute_at(&mut vm, 0x1C00, Lpm { d: 16 });
+        assert_eq!(vm.core.read_reg(16), 0x8A);
+        assert_eq!(&vm.core.flash[0x100..0x104], &[0x8A, 0xE2, 0x08, 0x95]);
+        assert_eq!(&vm.core.flash[0x104..0x180], &[0xFF; 0x7C][..]);
+
+        let flash = vm.core.flash.clone();
+        assert_eq!(vm.decoder.decode(&flash, 0x100), Ldi { d: 24, k: 0x2A });
+        assert_eq!(vm.decoder.decode(&flash, 0x102), Ret);
+    }
+
+    #[test]
+    fn classic_halts_in_nrww_section() {
+        let mut vm = create_vm("atmega16", 0x1C00);
+        vm.core.flash[0x3C00] = 0x42;
+
+        vm.core.write_z(0x3C00);
+        spmcr(&mut vm, PGERS | SPMEN);
+        Spm.execute(&mut vm).unwrap();
+
+        assert_eq!(vm.core.cycles, 1 + CLASSIC_PAGE_CYCLES);
+        assert_eq!(vm.core.flash[0x3C00], 0xFF);
+        assert_eq!(vm.read_io(0x57, true) & RWWSB, 0);
+    }
+
+    #[test]
+    fn classic_spm_in_application_section() {
+        let mut vm = create_vm("atmega16", 0x0400);
+        vm.core.flash[0x100] = 0x42;
+
+        vm.core.write_z(0x0100);
+        spmcr(&mut vm, PGERS | SPMEN);
+        Spm.execute(&mut vm).unwrap();
+
+        assert_eq!(vm.core.cycles, 1);
+        assert_eq!(vm.core.flash[0x100], 0x42);
+    }
+
+    #[test]
+    fn classic_spmen_timeout() {
+        let mut vm = create_vm("atmega16", 0x1C00);
+
+        vm.core.write_z(0x0100);
+        spmcr(&mut vm, PGERS | SPMEN);
+        vm.core.cycles += SPMEN_TIMEOUT + 1;
+        assert_eq!(vm.read_io(0x57, true), 0);
+        Spm.execute(&mut vm).unwrap();
+
+        assert_eq!(vm.core.flash[0x100], 0x00);
+    }
+
+    #[test]
+    fn classic_lock_bits() {
+        let mut vm = create_vm("atmega16", 0x1C00);
+        vm.info.fuses.app_lock = SectionLock::WriteLock;
+        vm.info.fuses.boot_lock = SectionLock::ReadLock;
+        vm.core.flash[0x3900] = 0x42;
+
+        // page erase of application section is ignored
+        vm.core.write_z(0x0100);
+        spmcr(&mut vm, PGERS | SPMEN);
+        Spm.execute(&mut vm).unwrap();
+        assert_eq!(vm.core.flash[0x100], 0x00);
+
+        // boot section can not be read from application section
+        vm.core.write_z(0x3900);
+        execute_at(&mut vm, 0x1C00, Lpm { d: 16 });
+        assert_eq!(vm.core.read_reg(16), 0x42);
+        execute_at(&mut vm, 0x0100, Lpm { d: 16 });
+        assert_eq!(vm.core.read_reg(16), 0xFF);
+    }
+
+    #[test]
+    fn classic_spm_outside_boot_size() {
+        // boot section of 256 bytes at 0x3F00
+        let mut vm = create_vm("atmega16", 0x1C00);
+        vm.info.fuses.boot_size = 3;
+
+        vm.core.write_z(0x0100);
+        spmcr(&mut vm, PGERS | SPMEN);
+        Spm.execute(&mut vm).unwrap();
+        assert_eq!(vm.core.flash[0x100], 0x00);
+
+        execute_at(&mut vm, 0x1F80, Spm);
+        assert_eq!(vm.core.flash[0x100], 0xFF);
+    }
+
+    #[test]
+    fn boot_reset_vector() {
+        let mut vm = create_vm("atmega16", 0);
+        vm.info.fuses.boot_reset = true;
+        vm.info.fuses.boot_size = 3;
+
+        vm.reset(ResetSource::External);
+        assert_eq!(vm.core.pc, 0x1F80);
+    }
+
+    #[test]
+    fn classic_spm_inc_invalid() {
+        let mut vm = create_vm("atmega16", 0x1C00);
+        assert_eq!(
+            SpmInc.execute(&mut vm),
+            Err(CpuSignal::InvaildOpcode { opcode: 0x95F8 }));
+    }
+
+    #[test]
+    fn xmega_erase_write_app_page() {
+        let mut vm = create_vm("atxmega128a4u", 0x10000);
+        let status = vm.info.io_regs["NVM_STATUS"];
+        let page = 0x0200u16;
+
+        vm.core.write_z(page);
+        nvm_cmd(&mut vm, CMD_LOAD_FLASH_BUFFER);
+        for &word in [0xE28Au16, 0x9508].iter() {
+            vm.core.write_reg16(0, word);
+            SpmInc.execute(&mut vm).unwrap();
+        }
+        assert_eq!(vm.core.read_z(), page + 4);
+        assert_eq!(vm.read_io(status, true), 0b0000_0001);
+
+        vm.core.write_z(page);
+        nvm_cmd(&mut vm, CMD_ERASE_WRITE_APP_PAGE);
+        Spm.execute(&mut vm).unwrap();
+        assert_eq!(vm.read_io(status, true), 0b1100_0000);
+        execute_at(&mut vm, 0x10000, Lpm { d: 16 });
+        assert_eq!(vm.core.read_reg(16), 0xFF);
+
+        // busy flags cleared automatically
+        vm.core.cycles += 2 * XMEGA_PAGE_CYCLES;
+        assert_eq!(vm.read_io(status, true), 0);
+        execute_at(&mut vm, 0x10000, Lpm { d: 16 });
+        assert_eq!(vm.core.read_reg(16), 0x8A);
+
+        let flash = vm.core.flash.clone();
+        assert_eq!(vm.decoder.decode(&flash, 0x200), Ldi { d: 24, k: 0x2A });
+    }
+
+    #[test]
+    fn xmega_app_command_in_boot_section() {
+        let mut vm = create_vm("atxmega128a4u", 0x10000);
+
+        vm.core.write_extended_z(0x20000);
+        nvm_cmd(&mut vm, CMD_ERASE_APP_PAGE);
+        Spm.execute(&mut vm).unwrap();
+        assert_eq!(vm.core.flash[0x20000], 0x00);
+
+        // boot section can not be read while written
+        nvm_cmd(&mut vm, CMD_ERASE_BOOT_PAGE);
+        Spm.execute(&mut vm).unwrap();
+        assert_eq!(vm.core.flash[0x20000], 0xFF);
+        assert_eq!(vm.core.cycles, 2 + XMEGA_PAGE_CYCLES);
+    }
+
+    #[test]
+    fn xmega_eeprom_mapping() {
+        let mut vm = create_vm("atxmega128a4u", 0x100);
+        let ctrlb = vm.info.io_regs["NVM_CTRLB"];
+        let addr = vm.info.eeprom.start + 5;
+        vm.core.eeprom[5] = 0x42;
+
+        Lds16 { d: 16, k: addr as u16 }.execute(&mut vm).unwrap();
+        assert_eq!(vm.core.regs[16], 0x00);
+
+        vm.write_io(ctrlb, EEMAPEN);
+        vm.core.cycles = 0;
+        Lds16 { d: 16, k: addr as u16 }.execute(&mut vm).unwrap();
+        assert_eq!(vm.core.regs[16], 0x42);
+        // one wait state
+        assert_eq!(vm.core.cycles, 3);
+    }
+}
@@ -0,0 +1,341 @@
+use core::AvrVm;
+use core::AvrCoreState;
+use std::sync::Arc;
+use std::sync::Mutex;
+use byte_convert::bit_at;
+use interrupts::InterruptController;
+use interrupts::InterruptLevel;
+
+
+/// XMEGA programmable multilevel interrupt controller
+pub struct Pmic {
+    /// levels of the interrupts currently executed (STATUS)
+    status: u8,
+
+    /// low level interrupt with the lowest priority (INTPRI)
+    intpri: u8,
+
+    /// level enables, round-robin and vector location (CTRL)
+    ctrl: u8,
+
+    /// start of boot section in words
+    boot_section: usize
+}
+
+fn level_bit(level: InterruptLevel) -> u8 {
+    match level {
+        InterruptLevel::Off => 0,
+        InterruptLevel::Low => 1 << 0,
+        InterruptLevel::Medium => 1 << 1,
+        InterruptLevel::High => 1 << 2,
+    }
+}
+
+impl Pmic {
+    pub fn new(boot_section: usize) -> Pmic {
+        Pmic {
+            status: 0,
+            intpri: 0,
+            ctrl: 0,
+            boot_section
+        }
+    }
+
+    pub fn is_level_enabled(&self, level: InterruptLevel) -> bool {
+        self.ctrl & level_bit(level) != 0
+    }
+
+    pub fn is_round_robin(&self) -> bool { bit_at(self.ctrl, 7) }
+
+    /// highest level currently executed
+    pub fn executing_level(&self) -> InterruptLevel {
+        if bit_at(self.status, 2) {
+            InterruptLevel::High
+        } else if bit_at(self.status, 1) {
+            InterruptLevel::Medium
+        } else if bit_at(self.status, 0) {
+            InterruptLevel::Low
+        } else {
+            InterruptLevel::Off
+        }
+    }
+
+    fn select(&self, core: &AvrCoreState) -> Option<usize> {
+        let executing = self.executing_level();
+        let candidates = core.irq.iter()
+            .filter(|&(_, level)| level > executing && self.is_level_enabled(level));
+
+        let level = candidates.clone().map(|(_, level)| level).max()?;
+        let mut vectors = candidates
+            .filter(|&(_, l)| l == level)
+            .map(|(vector, _)| vector);
+
+        if level == InterruptLevel::Low && self.is_round_robin() {
+            // vector after the last acknowledged one has the highest priority
+            let intpri = self.intpri as usize;
+            let first = vectors.clone().next();
+            vectors.find(|&vector| vector > intpri).or(first)
+        } else {
+            vectors.next()
+        }
+    }
+
+    fn acknowledge(&mut self, core: &AvrCoreState, vector: usize) {
+        let level = core.irq.level(vector).unwrap_or(InterruptLevel::Off);
+        self.status |= level_bit(level);
+        if level == InterruptLevel::Low && self.is_round_robin() {
+            self.intpri = vector as u8;
+        }
+    }
+
+    fn reset(&mut self) {
+        self.status = 0;
+        self.intpri = 0;
+        self.ctrl = 0;
+    }
+
+    fn reti(&mut self) {
+        let level = self.executing_level();
+        self.status &= !level_bit(level);
+    }
+
+    fn vector_table(&self) -> usize {
+        if bit_at(self.ctrl, 6) { self.boot_section } else { 0 }
+    }
+
+    fn status_read(&mut self, _core: &AvrCoreState, _view: bool) -> u8 {
+        self.status
+    }
+
+    fn intpri_read(&mut self, _core: &AvrCoreState, _view: bool) -> u8 {
+        self.intpri
+    }
+
+    fn intpri_write(&mut self, _core: &mut AvrCoreState, value: u8) {
+        self.intpri = value;
+    }
+
+    fn ctrl_read(&mut self, _core: &AvrCoreState, _view: bool) -> u8 {
+        self.ctrl
+    }
+
+    fn ctrl_write(&mut self, _core: &mut AvrCoreState, value: u8) {
+        self.ctrl = value & 0b1100_0111;
+        info!(
+            target: "avrvc::pmic",
+            "PMIC Control: RREN={} IVSEL={} HILVLEN={} MEDLVLEN={} LOLVLEN={}",
+            bit_at(value, 7) as u8, bit_at(value, 6) as u8,
+            bit_at(value, 2) as u8, bit_at(value, 1) as u8, bit_at(value, 0) as u8);
+    }
+}
+
+
+/// `InterruptController` of XMEGA devices
+///
+/// The I flag is not touched when entering or leaving an interrupt, the
+/// levels in execution are tracked in the PMIC STATUS register instead.
+pub struct PmicController {
+    pmic: Arc<Mutex<Pmic>>
+}
+
+impl InterruptController for PmicController {
+    fn select(&mut self, core: &AvrCoreState) -> Option<usize> {
+        self.pmic.lock().unwrap().select(core)
+    }
+
+    fn acknowledge(&mut self, core: &mut AvrCoreState, vector: usize) {
+        self.pmic.lock().unwrap().acknowledge(core, vector);
+    }
+
+    fn reti(&mut self, _core: &mut AvrCoreState) {
+        self.pmic.lock().unwrap().reti();
+    }
+
+    fn vector_table(&self) -> usize {
+        self.pmic.lock().unwrap().vector_table()
+    }
+}
+
+
+pub fn register_pmic(vm: &mut AvrVm) -> Option<Arc<Mutex<Pmic>>> {
+    if !vm.info.io_regs.contains_key("PMIC_CTRL") {
+        return None
+    }
+
+    let ioregs = vm.info.io_regs.clone();
+    let boot_section = vm.info.boot_section.map(|start| start / 2).unwrap_or(0);
+
+    let pmic = Arc::new(Mutex::new(Pmic::new(boot_section)));
+    let pmic1 = Arc::clone(&pmic);
+    let pmic2 = Arc::clone(&pmic);
+    let pmic3 = Arc::clone(&pmic);
+    let pmic4 = Arc::clone(&pmic);
+    let pmic5 = Arc::clone(&pmic);
+    let pmic6 = Arc::clone(&pmic);
+
+    vm.register_io(
+        ioregs["PMIC_STATUS"],
+        Box::new(move |core, _, view| pmic1.lock().unwrap().status_read(core, view)),
+        Box::new(|_, _, _| ())
+    );
+    vm.register_io(
+        ioregs["PMIC_INTPRI"],
+        Box::new(move |core, _, view| pmic2.lock().unwrap().intpri_read(core, view)),
+        Box::new(move |core, _, value| pmic3.lock().unwrap().intpri_write(core, value))
+    );
+    vm.register_io(
+        ioregs["PMIC_CTRL"],
+        Box::new(move |core, _, view| pmic4.lock().unwrap().ctrl_read(core, view)),
+        Box::new(move |core, _, value| pmic5.lock().unwrap().ctrl_write(core, value))
+    );
+
+    vm.register_reset(Box::new(move |_, _| pmic6.lock().unwrap().reset()));
+
+    vm.set_interrupt_controller(Box::new(PmicController { pmic: Arc::clone(&pmic) }));
+
+    Some(pmic)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use models::test_util::create_emulator;
+    use models::test_util::write_ioreg;
+    use models::test_util::read_ioreg;
+    use models::test_util::TEST_PC;
+
+    fn create_vm() -> AvrVm {
+        let mut vm = create_emulator("atxmega128a4u").vm;
+        vm.core.interrupt = true;
+        vm
+    }
+
+    #[test]
+    fn dispatch() {
+        let mut vm = create_vm();
+        write_ioreg(&mut vm, "PMIC_CTRL", 0b111);
+        let old_sp = vm.core.sp;
+
+        vm.core.irq.raise_level(25, InterruptLevel::Medium);
+        vm.step().unwrap();
+
+        assert_eq!(vm.core.pc, 25 * 2);
+        assert_eq!(vm.core.sp, old_sp - 3);
+        assert_eq!(vm.read_unchecked(old_sp - 0, true), 0x00);
+        assert_eq!(vm.read_unchecked(old_sp - 1, true), 0x01);
+        assert_eq!(vm.read_unchecked(old_sp - 2, true), 0x00);
+        assert_eq!(vm.core.cycles, 5);
+        // XMEGA leaves I flag untouched
+        assert_eq!(vm.core.interrupt, true);
+        assert_eq!(read_ioreg(&vm, "PMIC_STATUS"), 0b010);
+    }
+
+    #[test]
+    fn disabled_level() {
+        let mut vm = create_vm();
+        write_ioreg(&mut vm, "PMIC_CTRL", 0b110);
+
+        vm.core.irq.raise_level(25, InterruptLevel::Low);
+        vm.step().unwrap();
+
+        assert_eq!(vm.core.pc, 0x101);
+        assert!(vm.core.irq.is_pending(25));
+    }
+
+    #[test]
+    fn level_priority() {
+        let mut vm = create_vm();
+        write_ioreg(&mut vm, "PMIC_CTRL", 0b111);
+
+        vm.core.irq.raise_level(14, InterruptLevel::Low);
+        vm.core.irq.raise_level(25, InterruptLevel::High);
+        vm.core.irq.raise_level(20, InterruptLevel::Medium);
+        vm.step().unwrap();
+
+        assert_eq!(vm.core.pc, 25 * 2);
+    }
+
+    #[test]
+    fn static_priority_within_level() {
+        let mut vm = create_vm();
+        write_ioreg(&mut vm, "PMIC_CTRL", 0b111);
+
+        vm.core.irq.raise_level(25, InterruptLevel::Medium);
+        vm.core.irq.raise_level(20, InterruptLevel::Medium);
+        vm.step().unwrap();
+
+        assert_eq!(vm.core.pc, 20 * 2);
+    }
+
+    #[test]
+    fn nested_levels() {
+        let mut vm = create_vm();
+        write_ioreg(&mut vm, "PMIC_CTRL", 0b111);
+
+        vm.core.irq.raise_level(14, InterruptLevel::Low);
+        vm.step().unwrap();
+        assert_eq!(vm.core.pc, 14 * 2);
+
+        // same level can not interrupt
+        vm.core.irq.raise_level(20, InterruptLevel::Low);
+        vm.step().unwrap();
+        assert_eq!(vm.core.pc, 14 * 2 + 1);
+
+        // higher level can
+        vm.core.irq.raise_level(25, InterruptLevel::High);
+        vm.step().unwrap();
+        assert_eq!(vm.core.pc, 25 * 2);
+        assert_eq!(read_ioreg(&vm, "PMIC_STATUS"), 0b101);
+
+        // RETI clears highest level first
+        vm.return_from_interrupt();
+        assert_eq!(vm.core.pc, 14 * 2 + 1);
+        assert_eq!(read_ioreg(&vm, "PMIC_STATUS"), 0b001);
+        assert_eq!(vm.core.interrupt, true);
+
+        vm.step().unwrap();
+        vm.return_from_interrupt();
+        assert_eq!(vm.core.pc, TEST_PC);
+        assert_eq!(read_ioreg(&vm, "PMIC_STATUS"), 0b000);
+
+        // pending low level is served after one instruction
+        vm.step().unwrap();
+        assert_eq!(vm.core.pc, 0x101);
+        vm.step().unwrap();
+        assert_eq!(vm.core.pc, 20 * 2);
+    }
+
+    #[test]
+    fn round_robin() {
+        let mut vm = create_vm();
+        write_ioreg(&mut vm, "PMIC_CTRL", 0b1000_0001);
+
+        vm.core.irq.raise_level(14, InterruptLevel::Low);
+        vm.core.irq.raise_level(20, InterruptLevel::Low);
+        vm.core.irq.raise_level(25, InterruptLevel::Low);
+        write_ioreg(&mut vm, "PMIC_INTPRI", 20);
+
+        vm.step().unwrap();
+        assert_eq!(vm.core.pc, 25 * 2);
+        assert_eq!(read_ioreg(&vm, "PMIC_INTPRI"), 25);
+
+        vm.return_from_interrupt();
+        vm.step().unwrap();
+        vm.core.irq.raise_level(25, InterruptLevel::Low);
+        vm.step().unwrap();
+        assert_eq!(vm.core.pc, 14 * 2);
+        assert_eq!(read_ioreg(&vm, "PMIC_INTPRI"), 14);
+    }
+
+    #[test]
+    fn boot_section_vectors() {
+        let mut vm = create_vm();
+        write_ioreg(&mut vm, "PMIC_CTRL", 0b0100_0111);
+
+        vm.core.irq.raise_level(25, InterruptLevel::High);
+        vm.step().unwrap();
+
+        assert_eq!(vm.core.pc, 0x10000 + 25 * 2);
+    }
+}
@@ -26,12 +26,51 @@ impl McuIoRegistersService {
         mcu_atmega8.insert("DDRD", 0x31);
         mcu_atmega8.insert("PIND", 0x30);
         mcu_atmega8.insert("PORTB", 0x38);
+        mcu_atmega8.insert("MCUCR", 0x55);
+        mcu_atmega8.insert("SPMCR", 0x57);
+        mcu_atmega8.insert("MCUCSR", 0x54);
+        mcu_atmega8.insert("TCCR0", 0x53);
+        mcu_atmega8.insert("TCNT0", 0x52);
+        mcu_atmega8.insert("TCCR1A", 0x4f);
+        mcu_atmega8.insert("TCCR1B", 0x4e);
+        mcu_atmega8.insert("TCNT1L", 0x4c);
+        mcu_atmega8.insert("TCNT1H", 0x4d);
+        mcu_atmega8.insert("OCR1AL", 0x4a);
+        mcu_atmega8.insert("OCR1AH", 0x4b);
+        mcu_atmega8.insert("OCR1BL", 0x48);
+        mcu_atmega8.insert("OCR1BH", 0x49);
+        mcu_atmega8.insert("ICR1L", 0x46);
+        mcu_atmega8.insert("ICR1H", 0x47);
+        mcu_atmega8.insert("TCCR2", 0x45);
+        mcu_atmega8.insert("TCNT2", 0x44);
+        mcu_atmega8.insert("OCR2", 0x43);
+        mcu_atmega8.insert("ASSR", 0x42);
+        mcu_atmega8.insert("TIMSK", 0x59);
+        mcu_atmega8.insert("TIFR", 0x58);
+        mcu_atmega8.insert("SFIOR", 0x50);
+        mcu_atmega8.insert("GICR", 0x5b);
+        mcu_atmega8.insert("GIFR", 0x5a);
         mcu_atmega8.insert("#FLASHEND", 0x1fff);
         mcu_atmega8.insert("#__AVR_2_BYTE_PC__", 0x1);
         mcu_atmega8.insert("#RAMEND", 0x45f);
         mcu_atmega8.insert("#SPM_PAGESIZE", 0x40);
         mcu_atmega8.insert("#RAMSTART", 0x60);
         mcu_atmega8.insert("#__AVR_ARCH__", 0x4);
+        mcu_atmega8.insert("#SE", 0x7);
+        mcu_atmega8.insert("#SM0", 0x4);
+        mcu_atmega8.insert("#SM1", 0x5);
+        mcu_atmega8.insert("#SM2", 0x6);
+        mcu_atmega8.insert("#NRWW_START_ADDR", 0x1800);
+        mcu_atmega8.insert("#FUSE_WDTON", 0xbf);
+        mcu_atmega8.insert("#TIMER2_COMP_vect_num", 0x3);
+        mcu_atmega8.insert("#TIMER2_OVF_vect_num", 0x4);
+        mcu_atmega8.insert("#TIMER1_CAPT_vect_num", 0x5);
+        mcu_atmega8.insert("#TIMER1_COMPA_vect_num", 0x6);
+        mcu_atmega8.insert("#TIMER1_COMPB_vect_num", 0x7);
+        mcu_atmega8.insert("#TIMER1_OVF_vect_num", 0x8);
+        mcu_atmega8.insert("#TIMER0_OVF_vect_num", 0x9);
+        mcu_atmega8.insert("#INT0_vect_num", 0x1);
+        mcu_atmega8.insert("#INT1_vect_num", 0x2);
         service.mcus.insert("atmega8", mcu_atmega8);
         
         let mut mcu_atmega16: IoRegAddrs = HashMap::new();
@@ -50,6 +89,31 @@ impl McuIoRegistersService {
         mcu_atmega16.insert("DDRD", 0x31);
         mcu_atmega16.insert("PIND", 0x30);
         mcu_atmega16.insert("PORTB", 0x38);
+        mcu_atmega16.insert("MCUCR", 0x55);
+        mcu_atmega16.insert("SPMCR", 0x57);
+        mcu_atmega16.insert("MCUCSR", 0x54);
+        mcu_atmega16.insert("TCCR0", 0x53);
+        mcu_atmega16.insert("TCNT0", 0x52);
+        mcu_atmega16.insert("TCCR1A", 0x4f);
+        mcu_atmega16.insert("TCCR1B", 0x4e);
+        mcu_atmega16.insert("TCNT1L", 0x4c);
+        mcu_atmega16.insert("TCNT1H", 0x4d);
+        mcu_atmega16.insert("OCR1AL", 0x4a);
+        mcu_atmega16.insert("OCR1AH", 0x4b);
+        mcu_atmega16.insert("OCR1BL", 0x48);
+        mcu_atmega16.insert("OCR1BH", 0x49);
+        mcu_atmega16.insert("ICR1L", 0x46);
+        mcu_atmega16.insert("ICR1H", 0x47);
+        mcu_atmega16.insert("TCCR2", 0x45);
+        mcu_atmega16.insert("TCNT2", 0x44);
+        mcu_atmega16.insert("OCR2", 0x43);
+        mcu_atmega16.insert("ASSR", 0x42);
+        mcu_atmega16.insert("TIMSK", 0x59);
+        mcu_atmega16.insert("TIFR", 0x58);
+        mcu_atmega16.insert("SFIOR", 0x50);
+        mcu_atmega16.insert("OCR0", 0x5c);
+        mcu_atmega16.insert("GICR", 0x5b);
+        mcu_atmega16.insert("GIFR", 0x5a);
         mcu_atmega16.insert("#FLASHEND", 0x3fff);
         mcu_atmega16.insert("#__AVR_MEGA__", 0x1);
         mcu_atmega16.insert("#__AVR_2_BYTE_PC__", 0x1);
@@ -57,8 +121,110 @@ impl McuIoRegistersService {
         mcu_atmega16.insert("#SPM_PAGESIZE", 0x80);
         mcu_atmega16.insert("#RAMSTART", 0x60);
         mcu_atmega16.insert("#__AVR_ARCH__", 0x5);
+        mcu_atmega16.insert("#SE", 0x6);
+        mcu_atmega16.insert("#SM0", 0x4);
+        mcu_atmega16.insert("#SM1", 0x5);
+        mcu_atmega16.insert("#SM2", 0x7);
+        mcu_atmega16.insert("#NRWW_START_ADDR", 0x3800);
+        mcu_atmega16.insert("#TIMER2_COMP_vect_num", 0x3);
+        mcu_atmega16.insert("#TIMER2_OVF_vect_num", 0x4);
+        mcu_atmega16.insert("#TIMER1_CAPT_vect_num", 0x5);
+        mcu_atmega16.insert("#TIMER1_COMPA_vect_num", 0x6);
+        mcu_atmega16.insert("#TIMER1_COMPB_vect_num", 0x7);
+        mcu_atmega16.insert("#TIMER1_OVF_vect_num", 0x8);
+        mcu_atmega16.insert("#TIMER0_OVF_vect_num", 0x9);
+        mcu_atmega16.insert("#TIMER0_COMP_vect_num", 0x13);
+        mcu_atmega16.insert("#INT0_vect_num", 0x1);
+        mcu_atmega16.insert("#INT1_vect_num", 0x2);
+        mcu_atmega16.insert("#INT2_vect_num", 0x12);
         service.mcus.insert("atmega16", mcu_atmega16);
         
+        let mut mcu_attiny10: IoRegAddrs = HashMap::new();
+        mcu_attiny10.insert("SPL", 0x3d);
+        mcu_attiny10.insert("SREG", 0x3f);
+        mcu_attiny10.insert("SPH", 0x3e);
+        mcu_attiny10.insert("PINB", 0x0);
+        mcu_attiny10.insert("DDRB", 0x1);
+        mcu_attiny10.insert("PORTB", 0x2);
+        mcu_attiny10.insert("SMCR", 0x3a);
+        mcu_attiny10.insert("RSTFLR", 0x3b);
+        mcu_attiny10.insert("EICRA", 0x15);
+        mcu_attiny10.insert("EIFR", 0x14);
+        mcu_attiny10.insert("EIMSK", 0x13);
+        mcu_attiny10.insert("PCICR", 0x12);
+        mcu_attiny10.insert("PCIFR", 0x11);
+        mcu_attiny10.insert("PCMSK", 0x10);
+        mcu_attiny10.insert("#FLASHEND", 0x3ff);
+        mcu_attiny10.insert("#__AVR_TINY__", 0x1);
+        mcu_attiny10.insert("#__AVR_2_BYTE_PC__", 0x1);
+        mcu_attiny10.insert("#RAMEND", 0x5f);
+        mcu_attiny10.insert("#RAMSTART", 0x40);
+        mcu_attiny10.insert("#__AVR_ARCH__", 0x64);
+        mcu_attiny10.insert("#__AVR_TINY_PM_BASE_ADDRESS__", 0x4000);
+        mcu_attiny10.insert("#SE", 0x0);
+        mcu_attiny10.insert("#SM0", 0x1);
+        mcu_attiny10.insert("#SM1", 0x2);
+        mcu_attiny10.insert("#SM2", 0x3);
+        mcu_attiny10.insert("#FUSE_WDTON", 0xfd);
+        mcu_attiny10.insert("#INT0_vect_num", 0x1);
+        mcu_attiny10.insert("#PCINT0_vect_num", 0x2);
+        service.mcus.insert("attiny10", mcu_attiny10);
+        
+        let mut mcu_attiny20: IoRegAddrs = HashMap::new();
+        mcu_attiny20.insert("SPL", 0x3d);
+        mcu_attiny20.insert("SREG", 0x3f);
+        mcu_attiny20.insert("SPH", 0x3e);
+        mcu_attiny20.insert("PINA", 0x0);
+        mcu_attiny20.insert("DDRA", 0x1);
+        mcu_attiny20.insert("PORTA", 0x2);
+        mcu_attiny20.insert("PINB", 0x4);
+        mcu_attiny20.insert("DDRB", 0x5);
+        mcu_attiny20.insert("PORTB", 0x6);
+        mcu_attiny20.insert("SMCR", 0x3a);
+        mcu_attiny20.insert("RSTFLR", 0x3b);
+        mcu_attiny20.insert("#FLASHEND", 0x7ff);
+        mcu_attiny20.insert("#__AVR_TINY__", 0x1);
+        mcu_attiny20.insert("#__AVR_2_BYTE_PC__", 0x1);
+        mcu_attiny20.insert("#RAMEND", 0xbf);
+        mcu_attiny20.insert("#RAMSTART", 0x40);
+        mcu_attiny20.insert("#__AVR_ARCH__", 0x64);
+        mcu_attiny20.insert("#__AVR_TINY_PM_BASE_ADDRESS__", 0x4000);
+        mcu_attiny20.insert("#SE", 0x0);
+        mcu_attiny20.insert("#SM0", 0x1);
+        mcu_attiny20.insert("#SM1", 0x2);
+        mcu_attiny20.insert("#SM2", 0x3);
+        mcu_attiny20.insert("#FUSE_WDTON", 0xfd);
+        service.mcus.insert("attiny20", mcu_attiny20);
+        
+        let mut mcu_attiny40: IoRegAddrs = HashMap::new();
+        mcu_attiny40.insert("SPL", 0x3d);
+        mcu_attiny40.insert("SREG", 0x3f);
+        mcu_attiny40.insert("SPH", 0x3e);
+        mcu_attiny40.insert("PINA", 0x0);
+        mcu_attiny40.insert("DDRA", 0x1);
+        mcu_attiny40.insert("PORTA", 0x2);
+        mcu_attiny40.insert("PINB", 0x4);
+        mcu_attiny40.insert("DDRB", 0x5);
+        mcu_attiny40.insert("PORTB", 0x6);
+        mcu_attiny40.insert("PINC", 0x8);
+        mcu_attiny40.insert("DDRC", 0x9);
+        mcu_attiny40.insert("PORTC", 0xa);
+        mcu_attiny40.insert("SMCR", 0x3a);
+        mcu_attiny40.insert("RSTFLR", 0x3b);
+        mcu_attiny40.insert("#FLASHEND", 0xfff);
+        mcu_attiny40.insert("#__AVR_TINY__", 0x1);
+        mcu_attiny40.insert("#__AVR_2_BYTE_PC__", 0x1);
+        mcu_attiny40.insert("#RAMEND", 0x13f);
+        mcu_attiny40.insert("#RAMSTART", 0x40);
+        mcu_attiny40.insert("#__AVR_ARCH__", 0x64);
+        mcu_attiny40.insert("#__AVR_TINY_PM_BASE_ADDRESS__", 0x4000);
+        mcu_attiny40.insert("#SE", 0x0);
+        mcu_attiny40.insert("#SM0", 0x1);
+        mcu_attiny40.insert("#SM1", 0x2);
+        mcu_attiny40.insert("#SM2", 0x3);
+        mcu_attiny40.insert("#FUSE_WDTON", 0xfd);
+        service.mcus.insert("attiny40", mcu_attiny40);
+        
         let mut mcu_atxmega16a4u: IoRegAddrs = HashMap::new();
         mcu_atxmega16a4u.insert("USARTE0_CTRLB", 0xaa4);
         mcu_atxmega16a4u.insert("SPL", 0x3d);
@@ -106,6 +272,30 @@ impl McuIoRegistersService {
         mcu_atxmega16a4u.insert("USARTC0_CTRLB", 0x8a4);
         mcu_atxmega16a4u.insert("USARTE0_BAUDCTRLA", 0xaa6);
         mcu_atxmega16a4u.insert("USARTD0_STATUS", 0x9a1);
+        mcu_atxmega16a4u.insert("PMIC_STATUS", 0xa0);
+        mcu_atxmega16a4u.insert("PMIC_INTPRI", 0xa1);
+        mcu_atxmega16a4u.insert("PMIC_CTRL", 0xa2);
+        mcu_atxmega16a4u.insert("EIND", 0x3c);
+        mcu_atxmega16a4u.insert("SLEEP_CTRL", 0x48);
+        mcu_atxmega16a4u.insert("NVM_CMD", 0x1ca);
+        mcu_atxmega16a4u.insert("NVM_CTRLA", 0x1cb);
+        mcu_atxmega16a4u.insert("NVM_STATUS", 0x1cf);
+        mcu_atxmega16a4u.insert("NVM_CTRLB", 0x1cc);
+        mcu_atxmega16a4u.insert("RST_STATUS", 0x78);
+        mcu_atxmega16a4u.insert("RST_CTRL", 0x79);
+        mcu_atxmega16a4u.insert("TCC0", 0x800);
+        mcu_atxmega16a4u.insert("TCC1", 0x840);
+        mcu_atxmega16a4u.insert("TCD0", 0x900);
+        mcu_atxmega16a4u.insert("TCD1", 0x940);
+        mcu_atxmega16a4u.insert("TCE0", 0xa00);
+        mcu_atxmega16a4u.insert("PORTE", 0x680);
+        mcu_atxmega16a4u.insert("PORTR", 0x7e0);
+        mcu_atxmega16a4u.insert("VPORT0", 0x10);
+        mcu_atxmega16a4u.insert("VPORT1", 0x14);
+        mcu_atxmega16a4u.insert("VPORT2", 0x18);
+        mcu_atxmega16a4u.insert("VPORT3", 0x1c);
+        mcu_atxmega16a4u.insert("PORTCFG_VPCTRLA", 0xb2);
+        mcu_atxmega16a4u.insert("PORTCFG_VPCTRLB", 0xb3);
         mcu_atxmega16a4u.insert("#FLASHEND", 0x4fff);
         mcu_atxmega16a4u.insert("#__AVR_MEGA__", 0x1);
         mcu_atxmega16a4u.insert("#IO_SIZE", 0x1000);
@@ -117,6 +307,61 @@ impl McuIoRegistersService {
         mcu_atxmega16a4u.insert("#MAPPED_EEPROM_START", 0x1000);
         mcu_atxmega16a4u.insert("#RAMSTART", 0x2000);
         mcu_atxmega16a4u.insert("#__AVR_ARCH__", 0x66);
+        mcu_atxmega16a4u.insert("#BOOT_SECTION_START", 0x4000);
+        mcu_atxmega16a4u.insert("#FUSE_WDLOCK", 0xfd);
+        mcu_atxmega16a4u.insert("#TCC0_OVF_vect_num", 14);
+        mcu_atxmega16a4u.insert("#TCC0_ERR_vect_num", 15);
+        mcu_atxmega16a4u.insert("#TCC0_CCA_vect_num", 16);
+        mcu_atxmega16a4u.insert("#TCC0_CCB_vect_num", 17);
+        mcu_atxmega16a4u.insert("#TCC0_CCC_vect_num", 18);
+        mcu_atxmega16a4u.insert("#TCC0_CCD_vect_num", 19);
+        mcu_atxmega16a4u.insert("#TCC1_OVF_vect_num", 20);
+        mcu_atxmega16a4u.insert("#TCC1_ERR_vect_num", 21);
+        mcu_atxmega16a4u.insert("#TCC1_CCA_vect_num", 22);
+        mcu_atxmega16a4u.insert("#TCC1_CCB_vect_num", 23);
+        mcu_atxmega16a4u.insert("#TCE0_OVF_vect_num", 47);
+        mcu_atxmega16a4u.insert("#TCE0_ERR_vect_num", 48);
+        mcu_atxmega16a4u.insert("#TCE0_CCA_vect_num", 49);
+        mcu_atxmega16a4u.insert("#TCE0_CCB_vect_num", 50);
+        mcu_atxmega16a4u.insert("#TCE0_CCC_vect_num", 51);
+        mcu_atxmega16a4u.insert("#TCE0_CCD_vect_num", 52);
+        mcu_atxmega16a4u.insert("#TCD0_OVF_vect_num", 77);
+        mcu_atxmega16a4u.insert("#TCD0_ERR_vect_num", 78);
+        mcu_atxmega16a4u.insert("#TCD0_CCA_vect_num", 79);
+        mcu_atxmega16a4u.insert("#TCD0_CCB_vect_num", 80);
+        mcu_atxmega16a4u.insert("#TCD0_CCC_vect_num", 81);
+        mcu_atxmega16a4u.insert("#TCD0_CCD_vect_num", 82);
+        mcu_atxmega16a4u.insert("#TCD1_OVF_vect_num", 83);
+        mcu_atxmega16a4u.insert("#TCD1_ERR_vect_num", 84);
+        mcu_atxmega16a4u.insert("#TCD1_CCA_vect_num", 85);
+        mcu_atxmega16a4u.insert("#TCD1_CCB_vect_num", 86);
+        mcu_atxmega16a4u.insert("#PORTC_INT0_vect_num", 0x2);
+        mcu_atxmega16a4u.insert("#PORTC_INT1_vect_num", 0x3);
+        mcu_atxmega16a4u.insert("#PORTR_INT0_vect_num", 0x4);
+        mcu_atxmega16a4u.insert("#PORTR_INT1_vect_num", 0x5);
+        mcu_atxmega16a4u.insert("#PORTB_INT0_vect_num", 0x22);
+        mcu_atxmega16a4u.insert("#PORTB_INT1_vect_num", 0x23);
+        mcu_atxmega16a4u.insert("#PORTE_INT0_vect_num", 0x2b);
+        mcu_atxmega16a4u.insert("#PORTE_INT1_vect_num", 0x2c);
+        mcu_atxmega16a4u.insert("#PORTD_INT0_vect_num", 0x40);
+        mcu_atxmega16a4u.insert("#PORTD_INT1_vect_num", 0x41);
+        mcu_atxmega16a4u.insert("#PORTA_INT0_vect_num", 0x42);
+        mcu_atxmega16a4u.insert("#PORTA_INT1_vect_num", 0x43);
+        mcu_atxmega16a4u.insert("#USARTC0_RXC_vect_num", 25);
+        mcu_atxmega16a4u.insert("#USARTC0_DRE_vect_num", 26);
+        mcu_atxmega16a4u.insert("#USARTC0_TXC_vect_num", 27);
+        mcu_atxmega16a4u.insert("#USARTC1_RXC_vect_num", 28);
+        mcu_atxmega16a4u.insert("#USARTC1_DRE_vect_num", 29);
+        mcu_atxmega16a4u.insert("#USARTC1_TXC_vect_num", 30);
+        mcu_atxmega16a4u.insert("#USARTE0_RXC_vect_num", 58);
+        mcu_atxmega16a4u.insert("#USARTE0_DRE_vect_num", 59);
+        mcu_atxmega16a4u.insert("#USARTE0_TXC_vect_num", 60);
+        mcu_atxmega16a4u.insert("#USARTD0_RXC_vect_num", 88);
+        mcu_atxmega16a4u.insert("#USARTD0_DRE_vect_num", 89);
+        mcu_atxmega16a4u.insert("#USARTD0_TXC_vect_num", 90);
+        mcu_atxmega16a4u.insert("#USARTD1_RXC_vect_num", 91);
+        mcu_atxmega16a4u.insert("#USARTD1_DRE_vect_num", 92);
+        mcu_atxmega16a4u.insert("#USARTD1_TXC_vect_num", 93);
         service.mcus.insert("atxmega16a4u", mcu_atxmega16a4u);
         
         let mut mcu_atxmega32a4u: IoRegAddrs = HashMap::new();
@@ -166,6 +411,30 @@ impl McuIoRegistersService {
         mcu_atxmega32a4u.insert("USARTC0_CTRLB", 0x8a4);
         mcu_atxmega32a4u.insert("USARTE0_BAUDCTRLA", 0xaa6);
         mcu_atxmega32a4u.insert("USARTD0_STATUS", 0x9a1);
+        mcu_atxmega32a4u.insert("PMIC_STATUS", 0xa0);
+        mcu_atxmega32a4u.insert("PMIC_INTPRI", 0xa1);
+        mcu_atxmega32a4u.insert("PMIC_CTRL", 0xa2);
+        mcu_atxmega32a4u.insert("EIND", 0x3c);
+        mcu_atxmega32a4u.insert("SLEEP_CTRL", 0x48);
+        mcu_atxmega32a4u.insert("NVM_CMD", 0x1ca);
+        mcu_atxmega32a4u.insert("NVM_CTRLA", 0x1cb);
+        mcu_atxmega32a4u.insert("NVM_STATUS", 0x1cf);
+        mcu_atxmega32a4u.insert("NVM_CTRLB", 0x1cc);
+        mcu_atxmega32a4u.insert("RST_STATUS", 0x78);
+        mcu_atxmega32a4u.insert("RST_CTRL", 0x79);
+        mcu_atxmega32a4u.insert("TCC0", 0x800);
+        mcu_atxmega32a4u.insert("TCC1", 0x840);
+        mcu_atxmega32a4u.insert("TCD0", 0x900);
+        mcu_atxmega32a4u.insert("TCD1", 0x940);
+        mcu_atxmega32a4u.insert("TCE0", 0xa00);
+        mcu_atxmega32a4u.insert("PORTE", 0x680);
+        mcu_atxmega32a4u.insert("PORTR", 0x7e0);
+        mcu_atxmega32a4u.insert("VPORT0", 0x10);
+        mcu_atxmega32a4u.insert("VPORT1", 0x14);
+        mcu_atxmega32a4u.insert("VPORT2", 0x18);
+        mcu_atxmega32a4u.insert("VPORT3", 0x1c);
+        mcu_atxmega32a4u.insert("PORTCFG_VPCTRLA", 0xb2);
+        mcu_atxmega32a4u.insert("PORTCFG_VPCTRLB", 0xb3);
         mcu_atxmega32a4u.insert("#FLASHEND", 0x8fff);
         mcu_atxmega32a4u.insert("#__AVR_MEGA__", 0x1);
         mcu_atxmega32a4u.insert("#IO_SIZE", 0x1000);
@@ -177,6 +446,61 @@ impl McuIoRegistersService {
         mcu_atxmega32a4u.insert("#MAPPED_EEPROM_START", 0x1000);
         mcu_atxmega32a4u.insert("#RAMSTART", 0x2000);
         mcu_atxmega32a4u.insert("#__AVR_ARCH__", 0x66);
+        mcu_atxmega32a4u.insert("#BOOT_SECTION_START", 0x8000);
+        mcu_atxmega32a4u.insert("#FUSE_WDLOCK", 0xfd);
+        mcu_atxmega32a4u.insert("#TCC0_OVF_vect_num", 14);
+        mcu_atxmega32a4u.insert("#TCC0_ERR_vect_num", 15);
+        mcu_atxmega32a4u.insert("#TCC0_CCA_vect_num", 16);
+        mcu_atxmega32a4u.insert("#TCC0_CCB_vect_num", 17);
+        mcu_atxmega32a4u.insert("#TCC0_CCC_vect_num", 18);
+        mcu_atxmega32a4u.insert("#TCC0_CCD_vect_num", 19);
+        mcu_atxmega32a4u.insert("#TCC1_OVF_vect_num", 20);
+        mcu_atxmega32a4u.insert("#TCC1_ERR_vect_num", 21);
+        mcu_atxmega32a4u.insert("#TCC1_CCA_vect_num", 22);
+        mcu_atxmega32a4u.insert("#TCC1_CCB_vect_num", 23);
+        mcu_atxmega32a4u.insert("#TCE0_OVF_vect_num", 47);
+        mcu_atxmega32a4u.insert("#TCE0_ERR_vect_num", 48);
+        mcu_atxmega32a4u.insert("#TCE0_CCA_vect_num", 49);
+        mcu_atxmega32a4u.insert("#TCE0_CCB_vect_num", 50);
+        mcu_atxmega32a4u.insert("#TCE0_CCC_vect_num", 51);
+        mcu_atxmega32a4u.insert("#TCE0_CCD_vect_num", 52);
+        mcu_atxmega32a4u.insert("#TCD0_OVF_vect_num", 77);
+        mcu_atxmega32a4u.insert("#TCD0_ERR_vect_num", 78);
+        mcu_atxmega32a4u.insert("#TCD0_CCA_vect_num", 79);
+        mcu_atxmega32a4u.insert("#TCD0_CCB_vect_num", 80);
+        mcu_atxmega32a4u.insert("#TCD0_CCC_vect_num", 81);
+        mcu_atxmega32a4u.insert("#TCD0_CCD_vect_num", 82);
+        mcu_atxmega32a4u.insert("#TCD1_OVF_vect_num", 83);
+        mcu_atxmega32a4u.insert("#TCD1_ERR_vect_num", 84);
+        mcu_atxmega32a4u.insert("#TCD1_CCA_vect_num", 85);
+        mcu_atxmega32a4u.insert("#TCD1_CCB_vect_num", 86);
+        mcu_atxmega32a4u.insert("#PORTC_INT0_vect_num", 0x2);
+        mcu_atxmega32a4u.insert("#PORTC_INT1_vect_num", 0x3);
+        mcu_atxmega32a4u.insert("#PORTR_INT0_vect_num", 0x4);
+        mcu_atxmega32a4u.insert("#PORTR_INT1_vect_num", 0x5);
+        mcu_atxmega32a4u.insert("#PORTB_INT0_vect_num", 0x22);
+        mcu_atxmega32a4u.insert("#PORTB_INT1_vect_num", 0x23);
+        mcu_atxmega32a4u.insert("#PORTE_INT0_vect_num", 0x2b);
+        mcu_atxmega32a4u.insert("#PORTE_INT1_vect_num", 0x2c);
+        mcu_atxmega32a4u.insert("#PORTD_INT0_vect_num", 0x40);
+        mcu_atxmega32a4u.insert("#PORTD_INT1_vect_num", 0x41);
+        mcu_atxmega32a4u.insert("#PORTA_INT0_vect_num", 0x42);
+        mcu_atxmega32a4u.insert("#PORTA_INT1_vect_num", 0x43);
+        mcu_atxmega32a4u.insert("#USARTC0_RXC_vect_num", 25);
+        mcu_atxmega32a4u.insert("#USARTC0_DRE_vect_num", 26);
+        mcu_atxmega32a4u.insert("#USARTC0_TXC_vect_num", 27);
+        mcu_atxmega32a4u.insert("#USARTC1_RXC_vect_num", 28);
+        mcu_atxmega32a4u.insert("#USARTC1_DRE_vect_num", 29);
+        mcu_atxmega32a4u.insert("#USARTC1_TXC_vect_num", 30);
+        mcu_atxmega32a4u.insert("#USARTE0_RXC_vect_num", 58);
+        mcu_atxmega32a4u.insert("#USARTE0_DRE_vect_num", 59);
+        mcu_atxmega32a4u.insert("#USARTE0_TXC_vect_num", 60);
+        mcu_atxmega32a4u.insert("#USARTD0_RXC_vect_num", 88);
+        mcu_atxmega32a4u.insert("#USARTD0_DRE_vect_num", 89);
+        mcu_atxmega32a4u.insert("#USARTD0_TXC_vect_num", 90);
+        mcu_atxmega32a4u.insert("#USARTD1_RXC_vect_num", 91);
+        mcu_atxmega32a4u.insert("#USARTD1_DRE_vect_num", 92);
+        mcu_atxmega32a4u.insert("#USARTD1_TXC_vect_num", 93);
         service.mcus.insert("atxmega32a4u", mcu_atxmega32a4u);
         
         let mut mcu_atxmega64a4u: IoRegAddrs = HashMap::new();
@@ -226,6 +550,30 @@ impl McuIoRegistersService {
         mcu_atxmega64a4u.insert("USARTC0_CTRLB", 0x8a4);
         mcu_atxmega64a4u.insert("USARTE0_BAUDCTRLA", 0xaa6);
         mcu_atxmega64a4u.insert("USARTD0_STATUS", 0x9a1);
+        mcu_atxmega64a4u.insert("PMIC_STATUS", 0xa0);
+        mcu_atxmega64a4u.insert("PMIC_INTPRI", 0xa1);
+        mcu_atxmega64a4u.insert("PMIC_CTRL", 0xa2);
+        mcu_atxmega64a4u.insert("EIND", 0x3c);
+        mcu_atxmega64a4u.insert("SLEEP_CTRL", 0x48);
+        mcu_atxmega64a4u.insert("NVM_CMD", 0x1ca);
+        mcu_atxmega64a4u.insert("NVM_CTRLA", 0x1cb);
+        mcu_atxmega64a4u.insert("NVM_STATUS", 0x1cf);
+        mcu_atxmega64a4u.insert("NVM_CTRLB", 0x1cc);
+        mcu_atxmega64a4u.insert("RST_STATUS", 0x78);
+        mcu_atxmega64a4u.insert("RST_CTRL", 0x79);
+        mcu_atxmega64a4u.insert("TCC0", 0x800);
+        mcu_atxmega64a4u.insert("TCC1", 0x840);
+        mcu_atxmega64a4u.insert("TCD0", 0x900);
+        mcu_atxmega64a4u.insert("TCD1", 0x940);
+        mcu_atxmega64a4u.insert("TCE0", 0xa00);
+        mcu_atxmega64a4u.insert("PORTE", 0x680);
+        mcu_atxmega64a4u.insert("PORTR", 0x7e0);
+        mcu_atxmega64a4u.insert("VPORT0", 0x10);
+        mcu_atxmega64a4u.insert("VPORT1", 0x14);
+        mcu_atxmega64a4u.insert("VPORT2", 0x18);
+        mcu_atxmega64a4u.insert("VPORT3", 0x1c);
+        mcu_atxmega64a4u.insert("PORTCFG_VPCTRLA", 0xb2);
+        mcu_atxmega64a4u.insert("PORTCFG_VPCTRLB", 0xb3);
         mcu_atxmega64a4u.insert("#FLASHEND", 0x10fff);
         mcu_atxmega64a4u.insert("#__AVR_MEGA__", 0x1);
         mcu_atxmega64a4u.insert("#IO_SIZE", 0x1000);
@@ -237,6 +585,61 @@ impl McuIoRegistersService {
         mcu_atxmega64a4u.insert("#MAPPED_EEPROM_START", 0x1000);
         mcu_atxmega64a4u.insert("#RAMSTART", 0x2000);
         mcu_atxmega64a4u.insert("#__AVR_ARCH__", 0x68);
+        mcu_atxmega64a4u.insert("#BOOT_SECTION_START", 0x10000);
+        mcu_atxmega64a4u.insert("#FUSE_WDLOCK", 0xfd);
+        mcu_atxmega64a4u.insert("#TCC0_OVF_vect_num", 14);
+        mcu_atxmega64a4u.insert("#TCC0_ERR_vect_num", 15);
+        mcu_atxmega64a4u.insert("#TCC0_CCA_vect_num", 16);
+        mcu_atxmega64a4u.insert("#TCC0_CCB_vect_num", 17);
+        mcu_atxmega64a4u.insert("#TCC0_CCC_vect_num", 18);
+        mcu_atxmega64a4u.insert("#TCC0_CCD_vect_num", 19);
+        mcu_atxmega64a4u.insert("#TCC1_OVF_vect_num", 20);
+        mcu_atxmega64a4u.insert("#TCC1_ERR_vect_num", 21);
+        mcu_atxmega64a4u.insert("#TCC1_CCA_vect_num", 22);
+        mcu_atxmega64a4u.insert("#TCC1_CCB_vect_num", 23);
+        mcu_atxmega64a4u.insert("#TCE0_OVF_vect_num", 47);
+        mcu_atxmega64a4u.insert("#TCE0_ERR_vect_num", 48);
+        mcu_atxmega64a4u.insert("#TCE0_CCA_vect_num", 49);
+        mcu_atxmega64a4u.insert("#TCE0_CCB_vect_num", 50);
+        mcu_atxmega64a4u.insert("#TCE0_CCC_vect_num", 51);
+        mcu_atxmega64a4u.insert("#TCE0_CCD_vect_num", 52);
+        mcu_atxmega64a4u.insert("#TCD0_OVF_vect_num", 77);
+        mcu_atxmega64a4u.insert("#TCD0_ERR_vect_num", 78);
+        mcu_atxmega64a4u.insert("#TCD0_CCA_vect_num", 79);
+        mcu_atxmega64a4u.insert("#TCD0_CCB_vect_num", 80);
+        mcu_atxmega64a4u.insert("#TCD0_CCC_vect_num", 81);
+        mcu_atxmega64a4u.insert("#TCD0_CCD_vect_num", 82);
+        mcu_atxmega64a4u.insert("#TCD1_OVF_vect_num", 83);
+        mcu_atxmega64a4u.insert("#TCD1_ERR_vect_num", 84);
+        mcu_atxmega64a4u.insert("#TCD1_CCA_vect_num", 85);
+        mcu_atxmega64a4u.insert("#TCD1_CCB_vect_num", 86);
+        mcu_atxmega64a4u.insert("#PORTC_INT0_vect_num", 0x2);
+        mcu_atxmega64a4u.insert("#PORTC_INT1_vect_num", 0x3);
+        mcu_atxmega64a4u.insert("#PORTR_INT0_vect_num", 0x4);
+        mcu_atxmega64a4u.insert("#PORTR_INT1_vect_num", 0x5);
+        mcu_atxmega64a4u.insert("#PORTB_INT0_vect_num", 0x22);
+        mcu_atxmega64a4u.insert("#PORTB_INT1_vect_num", 0x23);
+        mcu_atxmega64a4u.insert("#PORTE_INT0_vect_num", 0x2b);
+        mcu_atxmega64a4u.insert("#PORTE_INT1_vect_num", 0x2c);
+        mcu_atxmega64a4u.insert("#PORTD_INT0_vect_num", 0x40);
+        mcu_atxmega64a4u.insert("#PORTD_INT1_vect_num", 0x41);
+        mcu_atxmega64a4u.insert("#PORTA_INT0_vect_num", 0x42);
+        mcu_atxmega64a4u.insert("#PORTA_INT1_vect_num", 0x43);
+        mcu_atxmega64a4u.insert("#USARTC0_RXC_vect_num", 25);
+        mcu_atxmega64a4u.insert("#USARTC0_DRE_vect_num", 26);
+        mcu_atxmega64a4u.insert("#USARTC0_TXC_vect_num", 27);
+        mcu_atxmega64a4u.insert("#USARTC1_RXC_vect_num", 28);
+        mcu_atxmega64a4u.insert("#USARTC1_DRE_vect_num", 29);
+        mcu_atxmega64a4u.insert("#USARTC1_TXC_vect_num", 30);
+        mcu_atxmega64a4u.insert("#USARTE0_RXC_vect_num", 58);
+        mcu_atxmega64a4u.insert("#USARTE0_DRE_vect_num", 59);
+        mcu_atxmega64a4u.insert("#USARTE0_TXC_vect_num", 60);
+        mcu_atxmega64a4u.insert("#USARTD0_RXC_vect_num", 88);
+        mcu_atxmega64a4u.insert("#USARTD0_DRE_vect_num", 89);
+        mcu_atxmega64a4u.insert("#USARTD0_TXC_vect_num", 90);
+        mcu_atxmega64a4u.insert("#USARTD1_RXC_vect_num", 91);
+        mcu_atxmega64a4u.insert("#USARTD1_DRE_vect_num", 92);
+        mcu_atxmega64a4u.insert("#USARTD1_TXC_vect_num", 93);
         service.mcus.insert("atxmega64a4u", mcu_atxmega64a4u);
         
         let mut mcu_atxmega128a4u: IoRegAddrs = HashMap::new();
@@ -286,6 +689,30 @@ impl McuIoRegistersService {
         mcu_atxmega128a4u.insert("USARTC0_CTRLB", 0x8a4);
         mcu_atxmega128a4u.insert("USARTE0_BAUDCTRLA", 0xaa6);
         mcu_atxmega128a4u.insert("USARTD0_STATUS", 0x9a1);
+        mcu_atxmega128a4u.insert("PMIC_STATUS", 0xa0);
+        mcu_atxmega128a4u.insert("PMIC_INTPRI", 0xa1);
+        mcu_atxmega128a4u.insert("PMIC_CTRL", 0xa2);
+        mcu_atxmega128a4u.insert("EIND", 0x3c);
+        mcu_atxmega128a4u.insert("SLEEP_CTRL", 0x48);
+        mcu_atxmega128a4u.insert("NVM_CMD", 0x1ca);
+        mcu_atxmega128a4u.insert("NVM_CTRLA", 0x1cb);
+        mcu_atxmega128a4u.insert("NVM_STATUS", 0x1cf);
+        mcu_atxmega128a4u.insert("NVM_CTRLB", 0x1cc);
+        mcu_atxmega128a4u.insert("RST_STATUS", 0x78);
+        mcu_atxmega128a4u.insert("RST_CTRL", 0x79);
+        mcu_atxmega128a4u.insert("TCC0", 0x800);
+        mcu_atxmega128a4u.insert("TCC1", 0x840);
+        mcu_atxmega128a4u.insert("TCD0", 0x900);
+        mcu_atxmega128a4u.insert("TCD1", 0x940);
+        mcu_atxmega128a4u.insert("TCE0", 0xa00);
+        mcu_atxmega128a4u.insert("PORTE", 0x680);
+        mcu_atxmega128a4u.insert("PORTR", 0x7e0);
+        mcu_atxmega128a4u.insert("VPORT0", 0x10);
+        mcu_atxmega128a4u.insert("VPORT1", 0x14);
+        mcu_atxmega128a4u.insert("VPORT2", 0x18);
+        mcu_atxmega128a4u.insert("VPORT3", 0x1c);
+        mcu_atxmega128a4u.insert("PORTCFG_VPCTRLA", 0xb2);
+        mcu_atxmega128a4u.insert("PORTCFG_VPCTRLB", 0xb3);
         mcu_atxmega128a4u.insert("#FLASHEND", 0x21fff);
         mcu_atxmega128a4u.insert("#__AVR_3_BYTE_PC__", 0x1);
         mcu_atxmega128a4u.insert("#__AVR_MEGA__", 0x1);
@@ -297,6 +724,61 @@ impl McuIoRegistersService {
         mcu_atxmega128a4u.insert("#MAPPED_EEPROM_START", 0x1000);
         mcu_atxmega128a4u.insert("#RAMSTART", 0x2000);
         mcu_atxmega128a4u.insert("#__AVR_ARCH__", 0x6b);
+        mcu_atxmega128a4u.insert("#BOOT_SECTION_START", 0x20000);
+        mcu_atxmega128a4u.insert("#FUSE_WDLOCK", 0xfd);
+        mcu_atxmega128a4u.insert("#TCC0_OVF_vect_num", 14);
+        mcu_atxmega128a4u.insert("#TCC0_ERR_vect_num", 15);
+        mcu_atxmega128a4u.insert("#TCC0_CCA_vect_num", 16);
+        mcu_atxmega128a4u.insert("#TCC0_CCB_vect_num", 17);
+        mcu_atxmega128a4u.insert("#TCC0_CCC_vect_num", 18);
+        mcu_atxmega128a4u.insert("#TCC0_CCD_vect_num", 19);
+        mcu_atxmega128a4u.insert("#TCC1_OVF_vect_num", 20);
+        mcu_atxmega128a4u.insert("#TCC1_ERR_vect_num", 21);
+        mcu_atxmega128a4u.insert("#TCC1_CCA_vect_num", 22);
+        mcu_atxmega128a4u.insert("#TCC1_CCB_vect_num", 23);
+        mcu_atxmega128a4u.insert("#TCE0_OVF_vect_num", 47);
+        mcu_atxmega128a4u.insert("#TCE0_ERR_vect_num", 48);
+        mcu_atxmega128a4u.insert("#TCE0_CCA_vect_num", 49);
+        mcu_atxmega128a4u.insert("#TCE0_CCB_vect_num", 50);
+        mcu_atxmega128a4u.insert("#TCE0_CCC_vect_num", 51);
+        mcu_atxmega128a4u.insert("#TCE0_CCD_vect_num", 52);
+        mcu_atxmega128a4u.insert("#TCD0_OVF_vect_num", 77);
+        mcu_atxmega128a4u.insert("#TCD0_ERR_vect_num", 78);
+        mcu_atxmega128a4u.insert("#TCD0_CCA_vect_num", 79);
+        mcu_atxmega128a4u.insert("#TCD0_CCB_vect_num", 80);
+        mcu_atxmega128a4u.insert("#TCD0_CCC_vect_num", 81);
+        mcu_atxmega128a4u.insert("#TCD0_CCD_vect_num", 82);
+        mcu_atxmega128a4u.insert("#TCD1_OVF_vect_num", 83);
+        mcu_atxmega128a4u.insert("#TCD1_ERR_vect_num", 84);
+        mcu_atxmega128a4u.insert("#TCD1_CCA_vect_num", 85);
+        mcu_atxmega128a4u.insert("#TCD1_CCB_vect_num", 86);
+        mcu_atxmega128a4u.insert("#PORTC_INT0_vect_num", 0x2);
+        mcu_atxmega128a4u.insert("#PORTC_INT1_vect_num", 0x3);
+        mcu_atxmega128a4u.insert("#PORTR_INT0_vect_num", 0x4);
+        mcu_atxmega128a4u.insert("#PORTR_INT1_vect_num", 0x5);
+        mcu_atxmega128a4u.insert("#PORTB_INT0_vect_num", 0x22);
+        mcu_atxmega128a4u.insert("#PORTB_INT1_vect_num", 0x23);
+        mcu_atxmega128a4u.insert("#PORTE_INT0_vect_num", 0x2b);
+        mcu_atxmega128a4u.insert("#PORTE_INT1_vect_num", 0x2c);
+        mcu_atxmega128a4u.insert("#PORTD_INT0_vect_num", 0x40);
+        mcu_atxmega128a4u.insert("#PORTD_INT1_vect_num", 0x41);
+        mcu_atxmega128a4u.insert("#PORTA_INT0_vect_num", 0x42);
+        mcu_atxmega128a4u.insert("#PORTA_INT1_vect_num", 0x43);
+        mcu_atxmega128a4u.insert("#USARTC0_RXC_vect_num", 25);
+        mcu_atxmega128a4u.insert("#USARTC0_DRE_vect_num", 26);
+        mcu_atxmega128a4u.insert("#USARTC0_TXC_vect_num", 27);
+        mcu_atxmega128a4u.insert("#USARTC1_RXC_vect_num", 28);
+        mcu_atxmega128a4u.insert("#USARTC1_DRE_vect_num", 29);
+        mcu_atxmega128a4u.insert("#USARTC1_TXC_vect_num", 30);
+        mcu_atxmega128a4u.insert("#USARTE0_RXC_vect_num", 58);
+        mcu_atxmega128a4u.insert("#USARTE0_DRE_vect_num", 59);
+        mcu_atxmega128a4u.insert("#USARTE0_TXC_vect_num", 60);
+        mcu_atxmega128a4u.insert("#USARTD0_RXC_vect_num", 88);
+        mcu_atxmega128a4u.insert("#USARTD0_DRE_vect_num", 89);
+        mcu_atxmega128a4u.insert("#USARTD0_TXC_vect_num", 90);
+        mcu_atxmega128a4u.insert("#USARTD1_RXC_vect_num", 91);
+        mcu_atxmega128a4u.insert("#USARTD1_DRE_vect_num", 92);
+        mcu_atxmega128a4u.insert("#USARTD1_TXC_vect_num", 93);
         service.mcus.insert("atxmega128a4u", mcu_atxmega128a4u);
         
 
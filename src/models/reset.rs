@@ -0,0 +1,223 @@
+use core::AvrVm;
+use core::AvrCoreState;
+use core::ResetSource;
+use std::sync::Arc;
+use std::sync::Mutex;
+use byte_convert::bit_at;
+
+
+// reset flags of MCUCSR, RSTFLR and RST_STATUS
+const PORF: u8 = 1 << 0;
+const EXTRF: u8 = 1 << 1;
+const BORF: u8 = 1 << 2;
+const WDRF: u8 = 1 << 3;
+const SRF: u8 = 1 << 5;
+
+
+/// reset flags recording the source of the last resets
+///
+/// Classic and tinyAVR devices clear flags by writing zero to them, XMEGA
+/// devices by writing one. CCP protection of RST_CTRL is not modeled.
+pub struct ResetController {
+    xmega: bool,
+
+    /// flags of the supported reset sources
+    mask: u8,
+
+    /// MCUCSR, RSTFLR or RST_STATUS
+    status: u8,
+
+    /// writable bits of MCUCSR other than reset flags (JTD, ISC2)
+    control_mask: u8,
+
+    control: u8,
+}
+
+impl ResetController {
+    pub fn new(xmega: bool, mask: u8) -> ResetController {
+        ResetController { xmega, mask, status: PORF, control_mask: 0, control: 0 }
+    }
+
+    /// current reset flags
+    pub fn status(&self) -> u8 { self.status }
+
+    /// bits of the status register not used for reset flags
+    pub fn control(&self) -> u8 { self.control }
+
+    fn reset(&mut self, source: ResetSource) {
+        let flag = match source {
+            ResetSource::PowerOn => PORF,
+            ResetSource::External => EXTRF,
+            ResetSource::BrownOut => BORF,
+            ResetSource::Watchdog => WDRF,
+            ResetSource::Software => SRF,
+        } & self.mask;
+
+        if source == ResetSource::PowerOn {
+            self.status = flag;
+        } else {
+            self.status |= flag;
+        }
+        self.control = 0;
+    }
+
+    fn status_read(&mut self, _core: &AvrCoreState, _view: bool) -> u8 {
+        self.status | self.control
+    }
+
+    fn status_write(&mut self, _core: &mut AvrCoreState, value: u8) {
+        self.control = value & self.control_mask;
+        if self.xmega {
+            self.status &= !value;
+        } else {
+            self.status &= value;
+        }
+    }
+
+    fn ctrl_write(&mut self, core: &mut AvrCoreState, value: u8) {
+        if bit_at(value, 0) {
+            core.reset_request = Some(ResetSource::Software);
+        }
+    }
+}
+
+
+pub fn register_reset_controller(vm: &mut AvrVm) -> Option<Arc<Mutex<ResetController>>> {
+    let ioregs = vm.info.io_regs.clone();
+
+    let (status, mask, control_mask) = if let Some(&status) = ioregs.get("RST_STATUS") {
+        (status, 0b0111_1111, 0)
+    } else if let Some(&status) = ioregs.get("MCUCSR").or_else(|| ioregs.get("MCUSR")) {
+        // JTD and ISC2 of devices with INT2
+        let control_mask = if vm.info.vectors.contains_key("INT2") { 0b1100_0000 } else { 0 };
+        (status, PORF | EXTRF | BORF | WDRF, control_mask)
+    } else if let Some(&status) = ioregs.get("RSTFLR") {
+        (status, PORF | EXTRF | WDRF, 0)
+    } else {
+        return None
+    };
+
+    let mut controller = ResetController::new(vm.info.xmega, mask);
+    controller.control_mask = control_mask;
+    let reset = Arc::new(Mutex::new(controller));
+    let reset1 = Arc::clone(&reset);
+    let reset2 = Arc::clone(&reset);
+    let reset3 = Arc::clone(&reset);
+
+    vm.register_io(
+        status,
+        Box::new(move |core, _, view| reset1.lock().unwrap().status_read(core, view)),
+        Box::new(move |core, _, value| reset2.lock().unwrap().status_write(core, value))
+    );
+    if let Some(&ctrl) = ioregs.get("RST_CTRL") {
+        let reset4 = Arc::clone(&reset);
+        vm.register_io(
+            ctrl,
+            Box::new(|_, _, _| 0),
+            Box::new(move |core, _, value| reset4.lock().unwrap().ctrl_write(core, value))
+        );
+    }
+    vm.register_reset(Box::new(move |_, source| reset3.lock().unwrap().reset(source)));
+
+    Some(reset)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use emulator::AvrEmulator;
+    use core::DataMemoryType;
+    use interrupts::InterruptLevel;
+
+    #[test]
+    fn reset_values() {
+        let mut emulator = AvrEmulator::from_name("atxmega128a4u");
+        let ram = emulator.vm.info.ram.clone();
+        let pmic_ctrl = emulator.vm.info.io_regs["PMIC_CTRL"];
+        let portc = emulator.vm.info.io_regs["PORTC"];
+
+        {
+            let vm = &mut emulator.vm;
+            vm.core.pc = 0x100;
+            vm.core.sp = 0x2100;
+            vm.core.regs[31] = 0x42;
+            vm.core.write_sreg(0xFF);
+            vm.core.rampz = 0x01;
+            vm.core.irq.raise(10);
+            vm.write_io(pmic_ctrl, 0x07);
+            vm.write_io(portc, 0x55);
+            vm.write(ram.start, 0xAA).unwrap();
+
+            vm.reset(ResetSource::External);
+            assert_eq!(vm.core.pc, 0);
+            assert_eq!(vm.core.sp, ram.end - 1);
+            assert_eq!(vm.core.regs[31], 0);
+            assert_eq!(vm.core.read_sreg(), 0);
+            assert_eq!(vm.core.rampz, 0);
+            assert!(!vm.core.irq.has_pending());
+            assert_eq!(vm.read_io(pmic_ctrl, true), 0);
+            assert_eq!(vm.read_io(portc, true), 0);
+
+            // SRAM is kept except for power-on reset
+            assert_eq!(vm.read(ram.start, true), Ok((0xAA, DataMemoryType::SRam)));
+            vm.reset(ResetSource::PowerOn);
+            assert_eq!(vm.read(ram.start, true), Ok((0x00, DataMemoryType::SRam)));
+        }
+
+        assert!(!emulator.pmic.unwrap().lock().unwrap().is_level_enabled(InterruptLevel::Low));
+    }
+
+    #[test]
+    fn reset_flags_classic() {
+        let mut emulator = AvrEmulator::from_name("atmega16");
+        let mcucsr = emulator.vm.info.io_regs["MCUCSR"];
+        assert_eq!(emulator.vm.read_io(mcucsr, true), PORF);
+
+        emulator.vm.reset(ResetSource::External);
+        emulator.vm.reset(ResetSource::Watchdog);
+        assert_eq!(emulator.vm.read_io(mcucsr, true), PORF | EXTRF | WDRF);
+
+        // flags are cleared by writing zero
+        emulator.vm.write_io(mcucsr, !PORF & 0x3F);
+        assert_eq!(emulator.vm.read_io(mcucsr, true), EXTRF | WDRF);
+
+        // ISC2 and JTD are kept
+        emulator.vm.write_io(mcucsr, 0xFF);
+        assert_eq!(emulator.vm.read_io(mcucsr, true), 0xC0 | EXTRF | WDRF);
+        assert_eq!(emulator.reset.as_ref().unwrap().lock().unwrap().control(), 0xC0);
+
+        // no software reset flag
+        emulator.vm.reset(ResetSource::Software);
+        emulator.vm.reset(ResetSource::BrownOut);
+        assert_eq!(emulator.vm.read_io(mcucsr, true), EXTRF | BORF | WDRF);
+
+        emulator.vm.reset(ResetSource::PowerOn);
+        assert_eq!(emulator.vm.read_io(mcucsr, true), PORF);
+    }
+
+    #[test]
+    fn reset_flags_xmega() {
+        let mut emulator = AvrEmulator::from_name("atxmega128a4u");
+        let status = emulator.vm.info.io_regs["RST_STATUS"];
+        let ctrl = emulator.vm.info.io_regs["RST_CTRL"];
+        // NOP, NOP
+        emulator.vm.write_flash(0, &[0x00, 0x00, 0x00, 0x00]);
+
+        emulator.vm.reset(ResetSource::BrownOut);
+        assert_eq!(emulator.vm.read_io(status, true), PORF | BORF);
+
+        // flags are cleared by writing one
+        emulator.vm.write_io(status, PORF | BORF);
+        assert_eq!(emulator.vm.read_io(status, true), 0);
+
+        // software reset after the current instruction
+        emulator.vm.core.pc = 1;
+        emulator.vm.core.regs[16] = 0x42;
+        emulator.vm.write_io(ctrl, 0x01);
+        assert_eq!(emulator.vm.step(), Ok(()));
+        assert_eq!(emulator.vm.core.pc, 0);
+        assert_eq!(emulator.vm.core.regs[16], 0);
+        assert_eq!(emulator.vm.read_io(status, true), SRF);
+    }
+}
@@ -0,0 +1,242 @@
+use core::AvrVm;
+use byte_convert::bit_at;
+
+
+/// sleep modes entered by the SLEEP instruction
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SleepMode {
+    Idle,
+    AdcNoiseReduction,
+    PowerDown,
+    PowerSave,
+    Standby,
+    ExtendedStandby,
+}
+
+/// clock driving a peripheral, stopped depending on the sleep mode
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ClockDomain {
+    /// I/O or peripheral clock, only running in idle mode
+    Io,
+    /// oscillator of the asynchronous timer, stopped in power-down and standby
+    Asynchronous,
+    /// never stopped, e.g. asynchronous pin sensing
+    Always,
+}
+
+impl ClockDomain {
+    /// is the clock running while the cpu is in `sleep`
+    pub fn is_running(self, sleep: Option<SleepMode>) -> bool {
+        match (self, sleep) {
+            (_, None) | (_, Some(SleepMode::Idle)) | (ClockDomain::Always, _) => true,
+            (ClockDomain::Io, _) => false,
+            (ClockDomain::Asynchronous, Some(mode)) =>
+                mode != SleepMode::PowerDown && mode != SleepMode::Standby,
+        }
+    }
+}
+
+/// positions of SEN and SMODE in the XMEGA SLEEP_CTRL register
+const XMEGA_SLEEP_BITS: [u8; 4] = [0, 1, 2, 3];
+
+/// sleep mode selected by MCUCR or SMCR (classic and tinyAVR) or SLEEP_CTRL
+/// (XMEGA)
+///
+/// The positions of the sleep enable and mode bits differ between devices and
+/// are taken from `AvrVmInfo::sleep_bits`. `None` is returned if sleep is not
+/// enabled. Reserved modes enter idle mode, as do devices without a sleep
+/// control register.
+pub fn selected_sleep_mode(vm: &AvrVm) -> Option<SleepMode> {
+    let ioregs = &vm.info.io_regs;
+    let (addr, bits) = match vm.info.sleep_bits {
+        Some(bits) => (ioregs.get("SMCR").or_else(|| ioregs.get("MCUCR")), bits),
+        None => (ioregs.get("SLEEP_CTRL"), XMEGA_SLEEP_BITS),
+    };
+    let ctrl = match addr {
+        Some(&addr) => vm.read_io(addr, true),
+        None => return Some(SleepMode::Idle),
+    };
+
+    if !bit_at(ctrl, bits[0]) {
+        return None;
+    }
+
+    let mode = (1..4).filter(|&i| bit_at(ctrl, bits[i]))
+        .fold(0, |mode, i| mode | (1 << (i - 1)));
+    if vm.info.tiny {
+        return match mode {
+            1 => Some(SleepMode::AdcNoiseReduction),
+            2 => Some(SleepMode::PowerDown),
+            4 => Some(SleepMode::Standby),
+            _ => Some(SleepMode::Idle),
+        };
+    }
+
+    match mode {
+        1 if !vm.info.xmega => Some(SleepMode::AdcNoiseReduction),
+        2 => Some(SleepMode::PowerDown),
+        3 => Some(SleepMode::PowerSave),
+        6 => Some(SleepMode::Standby),
+        7 => Some(SleepMode::ExtendedStandby),
+        _ => Some(SleepMode::Idle),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::CpuSignal;
+    use models::xmega_au::XmegaA4U::ATxmega128A4U;
+    use models::AvrModel;
+    use models::test_util::create_vm;
+    use models::test_util::write_ioreg;
+    use models::test_util::TEST_PC;
+    use instruction_set::Instruction::*;
+
+    const SE_ATMEGA16: u8 = 1 << 6;
+
+    #[test]
+    fn sleep_ctrl() {
+        let mut vm = ATxmega128A4U.create_vm();
+        let addr = vm.info.io_regs["SLEEP_CTRL"];
+
+        assert_eq!(selected_sleep_mode(&vm), None);
+
+        vm.write_io(addr, 0b0000_0001);
+        assert_eq!(selected_sleep_mode(&vm), Some(SleepMode::Idle));
+
+        vm.write_io(addr, 0b0000_0101);
+        assert_eq!(selected_sleep_mode(&vm), Some(SleepMode::PowerDown));
+
+        vm.write_io(addr, 0b0000_1111);
+        assert_eq!(selected_sleep_mode(&vm), Some(SleepMode::ExtendedStandby));
+
+        vm.write_io(addr, 0b0000_1110);
+        assert_eq!(selected_sleep_mode(&vm), None);
+    }
+
+    #[test]
+    fn classic_mcucr() {
+        // SE is bit 7 on the ATmega8
+        let mut vm = create_vm("atmega8");
+        assert_eq!(selected_sleep_mode(&vm), None);
+        write_ioreg(&mut vm, "MCUCR", 0b1000_0000);
+        assert_eq!(selected_sleep_mode(&vm), Some(SleepMode::Idle));
+        write_ioreg(&mut vm, "MCUCR", 0b1010_0000);
+        assert_eq!(selected_sleep_mode(&vm), Some(SleepMode::PowerDown));
+        write_ioreg(&mut vm, "MCUCR", 0b0011_0000);
+        assert_eq!(selected_sleep_mode(&vm), None);
+
+        // SE is bit 6 and SM2 bit 7 on the ATmega16
+        let mut vm = create_vm("atmega16");
+        write_ioreg(&mut vm, "MCUCR", 0b1000_0000);
+        assert_eq!(selected_sleep_mode(&vm), None);
+        write_ioreg(&mut vm, "MCUCR", 0b0111_0000);
+        assert_eq!(selected_sleep_mode(&vm), Some(SleepMode::PowerSave));
+        write_ioreg(&mut vm, "MCUCR", 0b1110_0000);
+        assert_eq!(selected_sleep_mode(&vm), Some(SleepMode::Standby));
+        write_ioreg(&mut vm, "MCUCR", 0b1111_0000);
+        assert_eq!(selected_sleep_mode(&vm), Some(SleepMode::ExtendedStandby));
+    }
+
+    #[test]
+    fn tiny_smcr() {
+        let mut vm = create_vm("attiny10");
+        assert_eq!(selected_sleep_mode(&vm), None);
+        write_ioreg(&mut vm, "SMCR", 0b0000_0011);
+        assert_eq!(selected_sleep_mode(&vm), Some(SleepMode::AdcNoiseReduction));
+        write_ioreg(&mut vm, "SMCR", 0b0000_0101);
+        assert_eq!(selected_sleep_mode(&vm), Some(SleepMode::PowerDown));
+        write_ioreg(&mut vm, "SMCR", 0b0000_1001);
+        assert_eq!(selected_sleep_mode(&vm), Some(SleepMode::Standby));
+        write_ioreg(&mut vm, "SMCR", 0b0000_1101);
+        assert_eq!(selected_sleep_mode(&vm), Some(SleepMode::Idle));
+    }
+
+    #[test]
+    fn classic_sleep_disabled() {
+        let mut vm = create_vm("atmega16");
+
+        Sleep.execute(&mut vm).unwrap();
+        assert_eq!(vm.core.sleep, None);
+        assert_eq!(vm.step(), Ok(()));
+    }
+
+    #[test]
+    fn sleep_without_wake_up() {
+        let mut vm = create_vm("atmega16");
+        write_ioreg(&mut vm, "MCUCR", SE_ATMEGA16);
+        vm.core.interrupt = true;
+
+        Sleep.execute(&mut vm).unwrap();
+        assert_eq!(vm.core.sleep, Some(SleepMode::Idle));
+
+        assert_eq!(vm.step(), Err(CpuSignal::Sleeping));
+        assert_eq!(vm.core.pc, 0x101);
+        assert_eq!(vm.core.cycles, 1);
+
+        // disabled interrupts can not wake up
+        vm.core.interrupt = false;
+        vm.raise_interrupt(3);
+        assert_eq!(vm.step(), Err(CpuSignal::Sleeping));
+    }
+
+    #[test]
+    fn wake_up_by_interrupt() {
+        let mut vm = create_vm("atmega16");
+        write_ioreg(&mut vm, "MCUCR", SE_ATMEGA16);
+        vm.core.interrupt = true;
+
+        Sleep.execute(&mut vm).unwrap();
+        vm.raise_interrupt(3);
+        vm.step().unwrap();
+
+        assert_eq!(vm.core.sleep, None);
+        assert_eq!(vm.core.pc, 3 * 2);
+        assert_eq!(vm.core.cycles, 1 + 4 + 4);
+
+        vm.return_from_interrupt();
+        assert_eq!(vm.core.pc, 0x101);
+    }
+
+    #[test]
+    fn fast_forward_to_clock_event() {
+        let mut vm = create_vm("atmega16");
+        write_ioreg(&mut vm, "MCUCR", SE_ATMEGA16);
+        vm.core.interrupt = true;
+        vm.register_clock(ClockDomain::Always, Box::new(|core| {
+            if core.cycles >= 1000 {
+                core.irq.raise(3);
+                None
+            } else {
+                Some(1000)
+            }
+        }));
+
+        Sleep.execute(&mut vm).unwrap();
+        vm.step().unwrap();
+
+        assert_eq!(vm.core.pc, 3 * 2);
+        assert_eq!(vm.core.cycles, 1000 + 4 + 4);
+    }
+
+    #[test]
+    fn clock_domains() {
+        assert!(ClockDomain::Io.is_running(None));
+        assert!(ClockDomain::Io.is_running(Some(SleepMode::Idle)));
+        assert!(!ClockDomain::Io.is_running(Some(SleepMode::PowerSave)));
+        assert!(ClockDomain::Asynchronous.is_running(Some(SleepMode::PowerSave)));
+        assert!(!ClockDomain::Asynchronous.is_running(Some(SleepMode::PowerDown)));
+        assert!(ClockDomain::Always.is_running(Some(SleepMode::Standby)));
+    }
+
+    #[test]
+    fn sleep_disabled() {
+        let mut vm = ATxmega128A4U.create_vm();
+        vm.core.pc = TEST_PC;
+
+        Sleep.execute(&mut vm).unwrap();
+        assert_eq!(vm.core.sleep, None);
+    }
+}
@@ -0,0 +1,59 @@
+//! fixtures shared by the instruction and peripheral tests
+
+use core::AvrVm;
+use core::AvrVmInfo;
+use emulator::AvrEmulator;
+
+
+/// program counter of the test code in words
+pub const TEST_PC: usize = 0x100;
+
+/// fill flash with NOPs, start at `TEST_PC` with SP at the end of RAM
+pub fn prepare_vm(vm: &mut AvrVm) {
+    let flash = vec![0u8; vm.info.flash_bytes];
+    vm.write_flash(0, &flash);
+    vm.core.sp = vm.info.ram.end - 1;
+    vm.core.pc = TEST_PC;
+}
+
+/// prepared vm of `mcu` without peripherals
+pub fn create_vm(mcu: &str) -> AvrVm {
+    let mut vm = AvrVm::new(&AvrVmInfo::from_name(mcu));
+    prepare_vm(&mut vm);
+    vm
+}
+
+/// prepared emulator of `mcu` with all peripherals
+pub fn create_emulator(mcu: &str) -> AvrEmulator {
+    let mut emulator = AvrEmulator::from_name(mcu);
+    prepare_vm(&mut emulator.vm);
+    emulator
+}
+
+pub fn write_ioreg(vm: &mut AvrVm, name: &str, value: u8) {
+    let addr = vm.info.io_regs[name];
+    vm.write_io(addr, value);
+}
+
+/// read register `name` without side effects
+pub fn read_ioreg(vm: &AvrVm, name: &str) -> u8 {
+    vm.read_io(vm.info.io_regs[name], true)
+}
+
+/// read register `name` as the cpu does, including side effects
+pub fn load_ioreg(vm: &AvrVm, name: &str) -> u8 {
+    vm.read_io(vm.info.io_regs[name], false)
+}
+
+/// program address of interrupt vector `name` in words
+pub fn vector_address(vm: &AvrVm, name: &str) -> usize {
+    vm.info.vectors[name] * vm.info.vector_size
+}
+
+/// execute instructions for at least `cycles` cpu cycles
+pub fn run(vm: &mut AvrVm, cycles: u64) {
+    let end = vm.core.cycles + cycles;
+    while vm.core.cycles < end {
+        vm.step().unwrap();
+    }
+}
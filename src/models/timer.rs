@@ -0,0 +1,1063 @@
+use core::AvrVm;
+use core::AvrCoreState;
+use std::cmp;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::Mutex;
+use interrupts::InterruptLevel;
+use models::sleep::ClockDomain;
+
+
+static TIMER_NAMES: [&str; 8] = [
+    "TCC0", "TCC1", "TCD0", "TCD1", "TCE0", "TCE1", "TCF0", "TCF1"
+];
+
+pub type Timers = HashMap<&'static str, Arc<Mutex<Timer>>>;
+
+// register offsets
+const CTRLA: usize = 0x00;
+const CTRLB: usize = 0x01;
+const CTRLC: usize = 0x02;
+const CTRLD: usize = 0x03;
+const CTRLE: usize = 0x04;
+const INTCTRLA: usize = 0x06;
+const INTCTRLB: usize = 0x07;
+const CTRLFCLR: usize = 0x08;
+const CTRLFSET: usize = 0x09;
+const CTRLGCLR: usize = 0x0A;
+const CTRLGSET: usize = 0x0B;
+const INTFLAGS: usize = 0x0C;
+const TEMP: usize = 0x0F;
+const CNT: usize = 0x20;
+const PER: usize = 0x26;
+const CCA: usize = 0x28;
+const PERBUF: usize = 0x36;
+const CCABUF: usize = 0x38;
+
+// INTFLAGS bits, CCxIF are bits 4-7
+const OVFIF: u8 = 1 << 0;
+const ERRIF: u8 = 1 << 1;
+const CCIF_ALL: u8 = 0xF0;
+
+// CTRLF bits
+const DIR: u8 = 1 << 0;
+const LUPD: u8 = 1 << 1;
+
+// CTRLF commands
+const CMD_UPDATE: u8 = 1;
+const CMD_RESTART: u8 = 2;
+const CMD_RESET: u8 = 3;
+
+// CTRLG bits, CCxBV are bits 1-4
+const PERBV: u8 = 1 << 0;
+
+/// input capture event action of CTRLD
+const EVACT_CAPT: u8 = 1;
+
+/// interrupt sources: OVF, ERR and the compare/capture channels
+const MAX_SOURCES: usize = 6;
+
+
+/// waveform generation mode (WGMODE)
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum WaveformMode {
+    Normal,
+
+    /// CCA is top and toggles output A
+    Frequency,
+
+    SingleSlope,
+
+    /// dual slope with overflow at top
+    DualSlopeTop,
+
+    /// dual slope with overflow at top and bottom
+    DualSlopeBoth,
+
+    /// dual slope with overflow at bottom
+    DualSlopeBottom,
+}
+
+impl WaveformMode {
+    fn from_bits(bits: u8) -> WaveformMode {
+        match bits & 0b111 {
+            1 => WaveformMode::Frequency,
+            3 => WaveformMode::SingleSlope,
+            5 => WaveformMode::DualSlopeTop,
+            6 => WaveformMode::DualSlopeBoth,
+            7 => WaveformMode::DualSlopeBottom,
+            // normal and reserved modes
+            _ => WaveformMode::Normal,
+        }
+    }
+
+    fn is_dual_slope(self) -> bool {
+        self == WaveformMode::DualSlopeTop
+            || self == WaveformMode::DualSlopeBoth
+            || self == WaveformMode::DualSlopeBottom
+    }
+}
+
+/// INTFLAGS bit of interrupt source `source`
+fn source_flag(source: usize) -> u8 {
+    match source {
+        0 => OVFIF,
+        1 => ERRIF,
+        channel => 1 << (channel + 2),
+    }
+}
+
+
+/// XMEGA 16-bit timer/counter type 0 (4 channels) or type 1 (2 channels)
+///
+/// The counter is clocked by the prescaled cpu clock, event channels as clock
+/// source and the byte mode (BYTEM) are not modeled. Input captures are
+/// triggered by the host with `capture` instead of the event system.
+#[derive(Clone)]
+pub struct Timer {
+    name: &'static str,
+
+    /// number of compare/capture channels
+    channels: usize,
+
+    ctrla: u8,
+    ctrlb: u8,
+
+    /// compare output values
+    ctrlc: u8,
+
+    ctrld: u8,
+    ctrle: u8,
+    intctrla: u8,
+    intctrlb: u8,
+
+    /// direction, lock update and command
+    ctrlf: u8,
+
+    /// buffer valid flags
+    ctrlg: u8,
+
+    intflags: u8,
+
+    /// high byte of 16-bit accesses
+    temp: u8,
+
+    cnt: u16,
+    per: u16,
+    cc: [u16; 4],
+    perbuf: u16,
+    ccbuf: [u16; 4],
+
+    /// peripheral clock cycle count the counter is updated to
+    cycles: u64,
+
+    /// interrupt vectors of OVF, ERR and the channels
+    vectors: [Option<usize>; MAX_SOURCES],
+
+    /// flags of requested interrupts
+    raised: u8,
+
+    /// peripheral clock cycle count of the next interrupt request
+    next_event: Option<u64>,
+
+    /// `next_event` has to be recalculated
+    dirty: bool,
+}
+
+impl Timer {
+    pub fn new(name: &'static str, channels: usize) -> Timer {
+        Timer {
+            name,
+            channels,
+            ctrla: 0,
+            ctrlb: 0,
+            ctrlc: 0,
+            ctrld: 0,
+            ctrle: 0,
+            intctrla: 0,
+            intctrlb: 0,
+            ctrlf: 0,
+            ctrlg: 0,
+            intflags: 0,
+            temp: 0,
+            cnt: 0,
+            per: 0xFFFF,
+            cc: [0; 4],
+            perbuf: 0xFFFF,
+            ccbuf: [0; 4],
+            cycles: 0,
+            vectors: [None; MAX_SOURCES],
+            raised: 0,
+            next_event: None,
+            dirty: true,
+        }
+    }
+
+    pub fn get_name(&self) -> &str { self.name }
+
+    /// cpu cycles per count or `None` if the timer is stopped
+    pub fn prescaler(&self) -> Option<u64> {
+        match self.ctrla & 0x0F {
+            1 => Some(1),
+            2 => Some(2),
+            3 => Some(4),
+            4 => Some(8),
+            5 => Some(64),
+            6 => Some(256),
+            7 => Some(1024),
+            // off and event channels
+            _ => None,
+        }
+    }
+
+    pub fn mode(&self) -> WaveformMode {
+        WaveformMode::from_bits(self.ctrlb)
+    }
+
+    /// level of waveform output of `channel` (CMPx)
+    pub fn output(&self, channel: usize) -> bool {
+        self.ctrlc & (1 << channel) != 0
+    }
+
+    /// capture the counter into `channel` as done by a capture event
+    ///
+    /// Ignored if the channel is not enabled or the event action is not
+    /// input capture. A capture into a channel with unread data sets ERRIF.
+    pub fn capture(&mut self, core: &mut AvrCoreState, channel: usize) {
+        self.advance(core.clock_cycles(ClockDomain::Io));
+        if !self.is_capture(channel) {
+            return;
+        }
+
+        let flag = source_flag(channel + 2);
+        if self.intflags & flag != 0 {
+            self.intflags |= ERRIF;
+        }
+        self.cc[channel] = self.cnt;
+        self.intflags |= flag;
+        self.dirty = true;
+        self.sync_irq(core);
+    }
+
+    fn is_capture(&self, channel: usize) -> bool {
+        channel < self.channels
+            && self.ctrld >> 5 == EVACT_CAPT
+            && self.ctrlb & (1 << (channel + 4)) != 0
+    }
+
+    fn top(&self) -> u16 {
+        if self.mode() == WaveformMode::Frequency { self.cc[0] } else { self.per }
+    }
+
+    /// bring counter up to cycle count `cycles`
+    ///
+    /// Counting up to the next compare match, turn or wrap around is skipped.
+    fn advance(&mut self, cycles: u64) {
+        if let Some(prescaler) = self.prescaler() {
+            let mut ticks = (cycles / prescaler).saturating_sub(self.cycles / prescaler);
+            while ticks > 0 {
+                let run = self.run_length();
+                let next = self.ticks_to_compare(CCIF_ALL, run).unwrap_or(run);
+                let skipped = cmp::min(next - 1, ticks);
+                self.skip(skipped);
+                ticks -= skipped;
+                if ticks > 0 {
+                    self.tick();
+                    ticks -= 1;
+                }
+            }
+        }
+        self.cycles = cycles.max(self.cycles);
+    }
+
+    /// count `ticks` times without reaching the end of the slope
+    fn skip(&mut self, ticks: u64) {
+        if self.ctrlf & DIR != 0 {
+            self.cnt -= ticks as u16;
+        } else {
+            self.cnt += ticks as u16;
+        }
+    }
+
+    /// count once
+    fn tick(&mut self) {
+        let mode = self.mode();
+        let top = self.top();
+        let down = self.ctrlf & DIR != 0;
+
+        if mode.is_dual_slope() {
+            if down {
+                self.cnt = self.cnt.saturating_sub(1);
+                if self.cnt == 0 {
+                    self.ctrlf &= !DIR;
+                    self.update();
+                    if mode != WaveformMode::DualSlopeTop {
+                        self.intflags |= OVFIF;
+                    }
+                }
+            } else {
+                self.cnt = self.cnt.saturating_add(1);
+                if self.cnt >= top {
+                    self.cnt = top;
+                    self.ctrlf |= DIR;
+                    if mode != WaveformMode::DualSlopeBottom {
+                        self.intflags |= OVFIF;
+                    }
+                }
+            }
+        } else if down {
+            if self.cnt == 0 {
+                self.cnt = top;
+                self.overflow();
+            } else {
+                self.cnt -= 1;
+            }
+        } else if self.cnt >= top {
+            self.cnt = 0;
+            self.overflow();
+        } else {
+            self.cnt += 1;
+        }
+
+        self.compare(mode, down);
+    }
+
+    /// overflow of single slope modes
+    fn overflow(&mut self) {
+        self.intflags |= OVFIF;
+        self.update();
+        if self.mode() == WaveformMode::SingleSlope {
+            // outputs are set at bottom
+            self.ctrlc |= (self.ctrlb >> 4) & self.channel_mask();
+        }
+    }
+
+    /// compare matches of all channels
+    fn compare(&mut self, mode: WaveformMode, down: bool) {
+        for channel in 0..self.channels {
+            if self.cnt != self.cc[channel] || self.is_capture(channel) {
+                continue;
+            }
+
+            self.intflags |= source_flag(channel + 2);
+            if self.ctrlb & (1 << (channel + 4)) == 0 {
+                continue;
+            }
+
+            let output = 1 << channel;
+            match mode {
+                WaveformMode::Frequency if channel == 0 => self.ctrlc ^= output,
+                WaveformMode::SingleSlope => self.ctrlc &= !output,
+                _ if mode.is_dual_slope() && down => self.ctrlc |= output,
+                _ if mode.is_dual_slope() => self.ctrlc &= !output,
+                _ => (),
+            }
+        }
+    }
+
+    /// copy valid buffers to PER and CCx unless locked (UPDATE condition)
+    fn update(&mut self) {
+        if self.ctrlf & LUPD != 0 {
+            return;
+        }
+        self.force_update();
+    }
+
+    fn force_update(&mut self) {
+        if self.ctrlg & PERBV != 0 {
+            self.per = self.perbuf;
+        }
+        for channel in 0..self.channels {
+            if self.ctrlg & (1 << (channel + 1)) != 0 {
+                self.cc[channel] = self.ccbuf[channel];
+            }
+        }
+        self.ctrlg = 0;
+    }
+
+    fn channel_mask(&self) -> u8 {
+        (1 << self.channels) - 1
+    }
+
+    fn level(&self, source: usize) -> InterruptLevel {
+        InterruptLevel::from_bits(match source {
+            0 => self.intctrla,
+            1 => self.intctrla >> 2,
+            channel => self.intctrlb >> (2 * (channel - 2)),
+        })
+    }
+
+    fn sources(&self) -> usize {
+        2 + self.channels
+    }
+
+    /// raise interrupts of set flags and withdraw them for cleared flags
+    fn sync_irq(&mut self, core: &mut AvrCoreState) {
+        for source in 0..self.sources() {
+            if let Some(vector) = self.vectors[source] {
+                let flag = source_flag(source);
+                let level = self.level(source);
+                if self.intflags & flag != 0 && level != InterruptLevel::Off {
+                    core.irq.raise_level(vector, level);
+                    self.raised |= flag;
+                } else if self.raised & flag != 0 {
+                    core.irq.clear(vector);
+                    self.raised &= !flag;
+                }
+            }
+        }
+    }
+
+    /// number of ticks until the counter turns or wraps around, including
+    /// that tick
+    fn run_length(&self) -> u64 {
+        let top = self.top() as u64;
+        let cnt = self.cnt as u64;
+        let down = self.ctrlf & DIR != 0;
+        match (self.mode().is_dual_slope(), down) {
+            (true, false) if cnt < top => top - cnt,
+            (true, true) => cnt.max(1),
+            (false, false) if cnt < top => top - cnt + 1,
+            (false, true) => cnt + 1,
+            _ => 1,
+        }
+    }
+
+    /// ticks until the first compare match in `enabled` before the counter
+    /// turns or wraps around after `run` ticks
+    fn ticks_to_compare(&self, enabled: u8, run: u64) -> Option<u64> {
+        let down = self.ctrlf & DIR != 0;
+        (0..self.channels)
+            .filter(|&channel| enabled & source_flag(channel + 2) != 0)
+            .filter(|&channel| !self.is_capture(channel))
+            .filter_map(|channel| {
+                let cc = self.cc[channel];
+                if down { self.cnt.checked_sub(cc) } else { cc.checked_sub(self.cnt) }
+            })
+            .map(|ticks| ticks as u64)
+            .filter(|&ticks| ticks > 0 && ticks < run)
+            .min()
+    }
+
+    /// cycle count of the next tick raising an enabled interrupt
+    ///
+    /// Counting between two turns or wrap arounds is skipped, only the ticks
+    /// at the ends of the slopes are simulated.
+    fn find_next_event(&self) -> Option<u64> {
+        let prescaler = self.prescaler()?;
+        let enabled = (0..self.sources())
+            .filter(|&source| {
+                self.vectors[source].is_some() && self.level(source) != InterruptLevel::Off
+            })
+            .fold(0, |flags, source| flags | source_flag(source))
+            & !self.intflags;
+        if enabled == 0 {
+            return None;
+        }
+
+        // buffers are copied within the first two slopes, every event happens
+        // within two periods after that
+        let mut timer = self.clone();
+        let start = self.cycles / prescaler;
+        let mut ticks = 0;
+        for _ in 0..6 {
+            let run = timer.run_length();
+            if let Some(compare) = timer.ticks_to_compare(enabled, run) {
+                return Some((start + ticks + compare) * prescaler);
+            }
+
+            timer.skip(run - 1);
+            timer.tick();
+            ticks += run;
+            if timer.intflags & enabled != 0 {
+                return Some((start + ticks) * prescaler);
+            }
+        }
+        None
+    }
+
+    fn clock(&mut self, core: &mut AvrCoreState) -> Option<u64> {
+        let cycles = core.clock_cycles(ClockDomain::Io);
+        self.advance(cycles);
+        self.sync_irq(core);
+
+        let passed = match self.next_event {
+            Some(next) => next <= cycles,
+            None => false,
+        };
+        if self.dirty || passed {
+            self.next_event = self.find_next_event();
+            self.dirty = false;
+        }
+        self.next_event.map(|next| core.cpu_cycles(ClockDomain::Io, next))
+    }
+
+    fn acknowledge(&mut self, vector: usize) {
+        for source in 0..self.sources() {
+            if self.vectors[source] == Some(vector) {
+                self.intflags &= !source_flag(source);
+                self.raised &= !source_flag(source);
+            }
+        }
+        self.dirty = true;
+    }
+
+    fn reset(&mut self) {
+        let mut timer = Timer::new(self.name, self.channels);
+        timer.cycles = self.cycles;
+        timer.vectors = self.vectors;
+        *self = timer;
+    }
+
+    fn command(&mut self, command: u8) {
+        match command {
+            CMD_UPDATE => self.force_update(),
+            CMD_RESTART => {
+                self.cnt = 0;
+                self.ctrlf &= !DIR;
+                self.ctrlc = 0;
+            }
+            CMD_RESET if self.prescaler().is_none() => self.reset(),
+            _ => (),
+        }
+    }
+
+    /// 16-bit register at `offset`
+    fn register16(&mut self, offset: usize) -> Option<&mut u16> {
+        let channels = self.channels;
+        match offset & !1 {
+            CNT => Some(&mut self.cnt),
+            PER => Some(&mut self.per),
+            PERBUF => Some(&mut self.perbuf),
+            o if o >= CCA && o < CCA + 2 * channels => Some(&mut self.cc[(o - CCA) / 2]),
+            o if o >= CCABUF && o < CCABUF + 2 * channels =>
+                Some(&mut self.ccbuf[(o - CCABUF) / 2]),
+            _ => None,
+        }
+    }
+
+    fn read(&mut self, core: &AvrCoreState, offset: usize, view: bool) -> u8 {
+        self.advance(core.clock_cycles(ClockDomain::Io));
+
+        match offset {
+            CTRLA => self.ctrla,
+            CTRLB => self.ctrlb,
+            CTRLC => self.ctrlc,
+            CTRLD => self.ctrld,
+            CTRLE => self.ctrle,
+            INTCTRLA => self.intctrla,
+            INTCTRLB => self.intctrlb,
+            CTRLFCLR | CTRLFSET => self.ctrlf,
+            CTRLGCLR | CTRLGSET => self.ctrlg,
+            INTFLAGS => self.intflags,
+            TEMP => self.temp,
+            _ => self.read16(offset, view),
+        }
+    }
+
+    /// reading the low byte stores the high byte in TEMP
+    fn read16(&mut self, offset: usize, view: bool) -> u8 {
+        let value = match self.register16(offset) {
+            Some(register) => *register,
+            None => return 0,
+        };
+
+        if offset & 1 != 0 {
+            return self.temp;
+        }
+        if !view {
+            self.temp = (value >> 8) as u8;
+
+            // reading a captured value clears the flag
+            if offset >= CCA && offset < CCA + 2 * self.channels
+                    && self.is_capture((offset - CCA) / 2) {
+                self.intflags &= !source_flag((offset - CCA) / 2 + 2);
+                self.dirty = true;
+            }
+        }
+        value as u8
+    }
+
+    fn write(&mut self, core: &mut AvrCoreState, offset: usize, value: u8) {
+        self.advance(core.clock_cycles(ClockDomain::Io));
+
+        let channels = self.channel_mask();
+        match offset {
+            CTRLA => {
+                self.ctrla = value & 0x0F;
+                info!(
+                    target: "avrvc::timer",
+                    "{} Control A: CLKSEL={}", self.name, self.ctrla);
+            }
+            CTRLB => self.ctrlb = value & ((channels << 4) | 0x07),
+            CTRLC => self.ctrlc = value & channels,
+            CTRLD => self.ctrld = value,
+            CTRLE => self.ctrle = value & 0x03,
+            INTCTRLA => self.intctrla = value & 0x0F,
+            INTCTRLB => self.intctrlb = value & if self.channels == 4 { 0xFF } else { 0x0F },
+            CTRLFCLR => self.ctrlf &= !(value & (DIR | LUPD)),
+            CTRLFSET => {
+                self.ctrlf |= value & (DIR | LUPD);
+                self.command((value >> 2) & 0b11);
+            }
+            CTRLGCLR => self.ctrlg &= !(value & ((channels << 1) | PERBV)),
+            CTRLGSET => self.ctrlg |= value & ((channels << 1) | PERBV),
+            INTFLAGS => self.intflags &= !value,
+            TEMP => self.temp = value,
+            _ => self.write16(offset, value),
+        }
+
+        // output levels, TEMP and low bytes do not change the next event
+        self.dirty |= match offset {
+            CTRLC | CTRLE | TEMP => false,
+            _ => offset < CNT || offset & 1 != 0,
+        };
+        self.sync_irq(core);
+    }
+
+    /// writing the low byte stores it in TEMP, the high byte writes all 16 bits
+    fn write16(&mut self, offset: usize, value: u8) {
+        if offset & 1 == 0 {
+            self.temp = value;
+            return;
+        }
+
+        let word = (value as u16) << 8 | self.temp as u16;
+        let offset = offset & !1;
+        if let Some(register) = self.register16(offset) {
+            *register = word;
+        } else {
+            return;
+        }
+
+        // buffer is valid until the next UPDATE condition
+        if offset == PERBUF {
+            self.ctrlg |= PERBV;
+        } else if offset >= CCABUF {
+            self.ctrlg |= 1 << ((offset - CCABUF) / 2 + 1);
+        }
+    }
+}
+
+
+pub fn register_timers(vm: &mut AvrVm) -> Timers {
+    TIMER_NAMES.iter().filter_map(
+        |&name| register_one_timer(vm, name).map(|timer| (name, timer))
+    ).collect()
+}
+
+fn register_one_timer(vm: &mut AvrVm, name: &'static str) -> Option<Arc<Mutex<Timer>>> {
+    let base = *vm.info.io_regs.get(name)?;
+    let channels = if name.ends_with('0') { 4 } else { 2 };
+
+    let mut timer = Timer::new(name, channels);
+    for (source, vector) in ["OVF", "ERR", "CCA", "CCB", "CCC", "CCD"].iter()
+            .take(2 + channels).enumerate() {
+        timer.vectors[source] = vm.info.vectors.get(&*format!("{}_{}", name, vector)).cloned();
+    }
+    let vectors = timer.vectors;
+    let timer = Arc::new(Mutex::new(timer));
+
+    let mut offsets = vec![
+        CTRLA, CTRLB, CTRLC, CTRLD, CTRLE, INTCTRLA, INTCTRLB,
+        CTRLFCLR, CTRLFSET, CTRLGCLR, CTRLGSET, INTFLAGS, TEMP,
+        CNT, CNT + 1, PER, PER + 1, PERBUF, PERBUF + 1
+    ];
+    for channel in 0..channels {
+        offsets.extend_from_slice(&[
+            CCA + 2 * channel, CCA + 2 * channel + 1,
+            CCABUF + 2 * channel, CCABUF + 2 * channel + 1
+        ]);
+    }
+
+    for offset in offsets {
+        let timer1 = Arc::clone(&timer);
+        let timer2 = Arc::clone(&timer);
+        vm.register_io(
+            base + offset,
+            Box::new(move |core, _, view| timer1.lock().unwrap().read(core, offset, view)),
+            Box::new(move |core, _, value| timer2.lock().unwrap().write(core, offset, value))
+        );
+    }
+
+    for &vector in vectors.iter().filter_map(|vector| vector.as_ref()) {
+        let timer3 = Arc::clone(&timer);
+        vm.register_interrupt_ack(
+            vector,
+            Box::new(move |_, vector| timer3.lock().unwrap().acknowledge(vector))
+        );
+    }
+
+    let timer4 = Arc::clone(&timer);
+    let timer5 = Arc::clone(&timer);
+    vm.register_clock(ClockDomain::Io, Box::new(move |core| timer4.lock().unwrap().clock(core)));
+    vm.register_reset(Box::new(move |_, _| timer5.lock().unwrap().reset()));
+
+    Some(timer)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use emulator::AvrEmulator;
+    use models::test_util;
+    use models::test_util::run;
+    use core::CpuSignal;
+
+    const TCC0: usize = 0x800;
+
+    fn create_emulator() -> AvrEmulator {
+        test_util::create_emulator("atxmega128a4u")
+    }
+
+    fn write16(vm: &mut AvrVm, addr: usize, value: u16) {
+        vm.write_io(addr, value as u8);
+        vm.write_io(addr + 1, (value >> 8) as u8);
+    }
+
+    fn read16(vm: &mut AvrVm, addr: usize) -> u16 {
+        let low = vm.read_io(addr, false) as u16;
+        low | (vm.read_io(addr + 1, false) as u16) << 8
+    }
+
+    #[test]
+    fn temp_register() {
+        let mut emulator = create_emulator();
+        let vm = &mut emulator.vm;
+
+        write16(vm, TCC0 + PER, 0x1234);
+        assert_eq!(emulator.timers["TCC0"].lock().unwrap().per, 0x1234);
+
+        // high byte is latched when reading the low byte
+        vm.write_io(TCC0 + TEMP, 0x00);
+        assert_eq!(vm.read_io(TCC0 + PER, false), 0x34);
+        vm.write_io(TCC0 + TEMP, 0x00);
+        assert_eq!(vm.read_io(TCC0 + PER + 1, false), 0x00);
+        assert_eq!(read16(vm, TCC0 + PER), 0x1234);
+
+        // low byte write is only stored in TEMP
+        vm.write_io(TCC0 + PER, 0x78);
+        assert_eq!(emulator.timers["TCC0"].lock().unwrap().per, 0x1234);
+        vm.write_io(TCC0 + PER + 1, 0x56);
+        assert_eq!(emulator.timers["TCC0"].lock().unwrap().per, 0x5678);
+    }
+
+    #[test]
+    fn normal_mode() {
+        let mut emulator = create_emulator();
+        let vm = &mut emulator.vm;
+
+        write16(vm, TCC0 + PER, 9);
+        write16(vm, TCC0 + CCA, 4);
+        for channel in 1..4 {
+            write16(vm, TCC0 + CCA + 2 * channel, 0xFFFF);
+        }
+        vm.write_io(TCC0 + CTRLA, 1); // DIV1
+        let start = vm.core.cycles;
+
+        run(vm, 5);
+        assert_eq!(read16(vm, TCC0 + CNT), (vm.core.cycles - start) as u16);
+        assert_eq!(vm.read_io(TCC0 + INTFLAGS, true), 1 << 4);
+
+        run(vm, 6);
+        assert_eq!(read16(vm, TCC0 + CNT), (vm.core.cycles - start - 10) as u16);
+        assert_eq!(vm.read_io(TCC0 + INTFLAGS, true), (1 << 4) | OVFIF);
+
+        // flags are cleared by writing one
+        vm.write_io(TCC0 + INTFLAGS, OVFIF);
+        assert_eq!(vm.read_io(TCC0 + INTFLAGS, true), 1 << 4);
+
+        // counting down
+        vm.write_io(TCC0 + CTRLFSET, DIR);
+        write16(vm, TCC0 + CNT, 1);
+        run(vm, 2);
+        assert_eq!(vm.read_io(TCC0 + INTFLAGS, true) & OVFIF, OVFIF);
+    }
+
+    #[test]
+    fn prescaler() {
+        let mut emulator = create_emulator();
+        let vm = &mut emulator.vm;
+
+        vm.core.cycles = 0;
+        vm.write_io(TCC0 + CTRLA, 5); // DIV64
+        vm.core.cycles = 64 * 10 - 1;
+        assert_eq!(read16(vm, TCC0 + CNT), 9);
+        vm.core.cycles = 64 * 10;
+        assert_eq!(read16(vm, TCC0 + CNT), 10);
+
+        // stopped
+        vm.write_io(TCC0 + CTRLA, 0);
+        vm.core.cycles = 64 * 100;
+        assert_eq!(read16(vm, TCC0 + CNT), 10);
+    }
+
+    #[test]
+    fn single_slope_pwm() {
+        let mut emulator = create_emulator();
+        let vm = &mut emulator.vm;
+        let timer = Arc::clone(&emulator.timers["TCC1"]);
+        let tcc1 = vm.info.io_regs["TCC1"];
+
+        vm.core.cycles = 0;
+        write16(vm, tcc1 + PER, 9);
+        write16(vm, tcc1 + CCA, 3);
+        vm.write_io(tcc1 + CTRLB, 0x10 | 3); // CCAEN, single slope
+        vm.write_io(tcc1 + CTRLA, 1);
+
+        // output is set at bottom and cleared at compare match
+        let mut high = 0;
+        for cycles in 1..30 {
+            vm.core.cycles = cycles;
+            timer.lock().unwrap().advance(cycles);
+            if timer.lock().unwrap().output(0) {
+                high += 1;
+            }
+        }
+        assert_eq!(high, 6);
+
+        // buffered period is used after the next overflow
+        write16(vm, tcc1 + PERBUF, 4);
+        assert_eq!(vm.read_io(tcc1 + CTRLGSET, true), PERBV);
+        vm.core.cycles = 29;
+        assert_eq!(read16(vm, tcc1 + PER), 9);
+        vm.core.cycles = 30;
+        assert_eq!(read16(vm, tcc1 + PER), 4);
+        assert_eq!(vm.read_io(tcc1 + CTRLGSET, true), 0);
+    }
+
+    #[test]
+    fn dual_slope() {
+        let mut emulator = create_emulator();
+        let vm = &mut emulator.vm;
+
+        vm.core.cycles = 0;
+        write16(vm, TCC0 + PER, 4);
+        vm.write_io(TCC0 + CTRLB, 5); // dual slope, overflow at top
+        vm.write_io(TCC0 + CTRLA, 1);
+
+        vm.core.cycles = 4;
+        assert_eq!(read16(vm, TCC0 + CNT), 4);
+        assert_eq!(vm.read_io(TCC0 + INTFLAGS, true) & OVFIF, OVFIF);
+        assert_eq!(vm.read_io(TCC0 + CTRLFSET, true) & DIR, DIR);
+
+        vm.write_io(TCC0 + INTFLAGS, OVFIF);
+        vm.core.cycles = 8;
+        assert_eq!(read16(vm, TCC0 + CNT), 0);
+        assert_eq!(vm.read_io(TCC0 + INTFLAGS, true) & OVFIF, 0);
+        assert_eq!(vm.read_io(TCC0 + CTRLFSET, true) & DIR, 0);
+
+        vm.core.cycles = 10;
+        assert_eq!(read16(vm, TCC0 + CNT), 2);
+    }
+
+    #[test]
+    fn frequency_mode() {
+        let mut emulator = create_emulator();
+        let vm = &mut emulator.vm;
+        let timer = Arc::clone(&emulator.timers["TCC0"]);
+
+        vm.core.cycles = 0;
+        write16(vm, TCC0 + CCA, 2);
+        vm.write_io(TCC0 + CTRLB, 0x10 | 1); // CCAEN, frequency
+        vm.write_io(TCC0 + CTRLA, 1);
+
+        let outputs: Vec<bool> = (1..=6).map(|cycles| {
+            let mut timer = timer.lock().unwrap();
+            timer.advance(cycles);
+            timer.output(0)
+        }).collect();
+        assert_eq!(outputs, vec![false, true, true, true, false, false]);
+    }
+
+    #[test]
+    fn input_capture() {
+        let mut emulator = create_emulator();
+        let vm = &mut emulator.vm;
+        let timer = Arc::clone(&emulator.timers["TCC0"]);
+
+        vm.core.cycles = 0;
+        vm.write_io(TCC0 + CTRLD, EVACT_CAPT << 5);
+        vm.write_io(TCC0 + CTRLB, 0x20); // CCBEN
+        vm.write_io(TCC0 + CTRLA, 1);
+
+        vm.core.cycles = 42;
+        timer.lock().unwrap().capture(&mut vm.core, 1);
+        assert_eq!(vm.read_io(TCC0 + INTFLAGS, true), 1 << 5);
+        timer.lock().unwrap().capture(&mut vm.core, 1);
+        assert_eq!(vm.read_io(TCC0 + INTFLAGS, true), (1 << 5) | ERRIF);
+
+        // reading the captured value clears the flag
+        assert_eq!(read16(vm, TCC0 + CCA + 2), 42);
+        assert_eq!(vm.read_io(TCC0 + INTFLAGS, true), ERRIF);
+
+        // channel A is not enabled
+        timer.lock().unwrap().capture(&mut vm.core, 0);
+        assert_eq!(read16(vm, TCC0 + CCA), 0);
+    }
+
+    #[test]
+    fn overflow_interrupt() {
+        let mut emulator = create_emulator();
+        let vm = &mut emulator.vm;
+        let vector = vm.info.vectors["TCC0_OVF"];
+        let pmic_ctrl = vm.info.io_regs["PMIC_CTRL"];
+
+        vm.core.interrupt = true;
+        vm.write_io(pmic_ctrl, 0x07);
+        write16(vm, TCC0 + PER, 99);
+        vm.write_io(TCC0 + INTCTRLA, 0x02); // medium level
+        vm.write_io(TCC0 + CTRLA, 1);
+
+        let mut dispatched = false;
+        for _ in 0..200 {
+            vm.step().unwrap();
+            if vm.core.pc == vector * vm.info.vector_size {
+                dispatched = true;
+                break;
+            }
+        }
+        assert!(dispatched);
+
+        // flag is cleared when the vector is executed
+        assert_eq!(vm.read_io(TCC0 + INTFLAGS, true) & OVFIF, 0);
+        assert!(!vm.core.irq.is_pending(vector));
+    }
+
+    #[test]
+    fn wake_up_from_sleep() {
+        let mut emulator = create_emulator();
+        let vm = &mut emulator.vm;
+        let vector = vm.info.vectors["TCC0_CCA"];
+        let pmic_ctrl = vm.info.io_regs["PMIC_CTRL"];
+
+        vm.core.interrupt = true;
+        vm.write_io(pmic_ctrl, 0x07);
+        write16(vm, TCC0 + CCA, 1000);
+        vm.write_io(TCC0 + CTRLA, 4); // DIV8
+        let start = vm.core.cycles;
+        vm.core.sleep = Some(::models::sleep::SleepMode::Idle);
+
+        // no interrupt enabled
+        assert_eq!(vm.step(), Err(CpuSignal::Sleeping));
+
+        vm.write_io(TCC0 + INTCTRLB, 0x01);
+        assert_eq!(vm.step(), Ok(()));
+        assert_eq!(vm.core.pc, vector * vm.info.vector_size);
+        assert!(vm.core.cycles >= start + 8 * 1000);
+        assert!(vm.core.cycles < start + 8 * 1000 + 20);
+    }
+
+    /// ticks until an interrupt in `enabled` by counting every tick
+    fn count_to_next_event(timer: &Timer, enabled: u8) -> Option<u64> {
+        let mut timer = timer.clone();
+        (1..(2 * 0x1_0000 + 2)).find(|_| {
+            timer.tick();
+            timer.intflags & enabled != 0
+        })
+    }
+
+    #[test]
+    fn next_event() {
+        // CNT, PER, CCA, CCB and PERBUF if valid
+        let counters = [
+            (0x0000, 0xFFFF, 0x1234, 0x0000, None),
+            (0x0100, 0x0200, 0x0080, 0x0300, None),
+            (0x01FF, 0x0200, 0x0200, 0x01FF, Some(0x0100)),
+            (0x0300, 0x0200, 0x0010, 0x0005, Some(0x0400)),
+            (0x0005, 0x0000, 0x0000, 0x0003, None),
+        ];
+        // INTCTRLA and INTCTRLB
+        let interrupts = [(0x01, 0x00), (0x00, 0x01), (0x00, 0x04), (0x01, 0x05)];
+
+        for &mode in [0, 1, 3, 5, 6, 7].iter() {
+            for &dir in [0, DIR].iter() {
+                for &(cnt, per, cca, ccb, perbuf) in counters.iter() {
+                    for &(intctrla, intctrlb) in interrupts.iter() {
+                        let mut timer = Timer::new("TCC0", 4);
+                        timer.vectors = [Some(1), Some(2), Some(3), Some(4), Some(5), Some(6)];
+                        timer.ctrla = 1;
+                        timer.ctrlb = mode;
+                        timer.ctrlf = dir;
+                        timer.cnt = cnt;
+                        timer.per = per;
+                        timer.cc = [cca, ccb, 0, 0];
+                        if let Some(perbuf) = perbuf {
+                            timer.perbuf = perbuf;
+                            timer.ctrlg = PERBV;
+                        }
+                        timer.intctrla = intctrla;
+                        timer.intctrlb = intctrlb;
+
+                        let enabled = OVFIF * intctrla
+                            | if intctrlb & 0x01 != 0 { source_flag(2) } else { 0 }
+                            | if intctrlb & 0x04 != 0 { source_flag(3) } else { 0 };
+                        assert_eq!(
+                            timer.find_next_event(), count_to_next_event(&timer, enabled),
+                            "WGMODE={} DIR={} CNT={:04x} PER={:04x} CC={:04x},{:04x}",
+                            mode, dir, cnt, per, cca, ccb);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn advance() {
+        // CNT, PER, CCA and CCB
+        let counters = [
+            (0x0000, 0xFFFF, 0x1234, 0x0000),
+            (0x0100, 0x0200, 0x0080, 0x0300),
+            (0x01FF, 0x0200, 0x0200, 0x01FF),
+            (0x0005, 0x0000, 0x0000, 0x0003),
+        ];
+
+        for &mode in [0, 1, 3, 5, 6, 7].iter() {
+            for &dir in [0, DIR].iter() {
+                for &(cnt, per, cca, ccb) in counters.iter() {
+                    for &ticks in [1, 0x80, 0x201, 0x1_2345].iter() {
+                        let mut timer = Timer::new("TCC0", 4);
+                        timer.ctrla = 1;
+                        // compare outputs A and B enabled
+                        timer.ctrlb = mode | 0x30;
+                        timer.ctrlf = dir;
+                        timer.cnt = cnt;
+                        timer.per = per;
+                        timer.cc = [cca, ccb, 0, 0];
+
+                        let mut counted = timer.clone();
+                        for _ in 0..ticks {
+                            counted.tick();
+                        }
+                        timer.advance(ticks);
+                        assert_eq!(
+                            (timer.cnt, timer.ctrlf, timer.ctrlc, timer.intflags),
+                            (counted.cnt, counted.ctrlf, counted.ctrlc, counted.intflags),
+                            "WGMODE={} DIR={} CNT={:04x} PER={:04x} CC={:04x},{:04x} ticks={}",
+                            mode, dir, cnt, per, cca, ccb, ticks);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn write_without_event_change() {
+        let mut emulator = create_emulator();
+        let vm = &mut emulator.vm;
+        vm.write_io(TCC0 + CTRLA, 1);
+        vm.write_io(TCC0 + INTCTRLA, 0x01);
+        vm.step().unwrap();
+        assert!(!emulator.timers["TCC0"].lock().unwrap().dirty);
+
+        // TEMP, low bytes and output levels do not affect the next event
+        vm.write_io(TCC0 + TEMP, 0x12);
+        vm.write_io(TCC0 + PERBUF, 0x34);
+        vm.write_io(TCC0 + CTRLC, 0x01);
+        assert!(!emulator.timers["TCC0"].lock().unwrap().dirty);
+
+        vm.write_io(TCC0 + PERBUF + 1, 0x12);
+        assert!(emulator.timers["TCC0"].lock().unwrap().dirty);
+    }
+}
@@ -1,10 +1,13 @@
 use core::AvrVm;
 use std::collections::HashMap;
+use std::collections::VecDeque;
 use std::sync::Arc;
 use core::AvrCoreState;
 use std::sync::Mutex;
 use byte_convert::u8bits;
 use byte_convert::bit_at;
+use interrupts::InterruptLevel;
+use models::sleep::ClockDomain;
 use ::internals::signals::{Broadcast, BroadcastListener};
 
 
@@ -14,13 +17,73 @@ pub type Usarts = HashMap<&'static str, Arc<Mutex<Usart>>>;
 pub type UsartTxSignal = Broadcast<u8>;
 pub type UsartTxConnection = BroadcastListener<u8>;
 
+/// levels of the receive buffer
+const RX_BUFFER_SIZE: usize = 2;
+
+/// TXC in the interrupt sources RXC, DRE and TXC
+const TXC: usize = 2;
+
+/// positions of RXCINTLVL, DREINTLVL and TXCINTLVL in CTRLA
+const LEVEL_SHIFTS: [u8; 3] = [4, 0, 2];
+
+// CTRLB bits
+const RXEN: u8 = 4;
+const TXEN: u8 = 3;
+const CLK2X: u8 = 2;
+const TXB8: u8 = 0;
+
+/// CHSIZE of CTRLC for 9-bit frames
+const CHSIZE_9BIT: u8 = 7;
+
+
+/// frame in the receive buffer
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct Received {
+    data: u16,
+
+    /// a following frame was lost (BUFOVF)
+    overflow: bool,
+}
+
+/// asynchronous XMEGA USART
+///
+/// Transmitted frames are sent to the tx listeners when they are moved to the
+/// shift register, TXCIF is set one frame time later. Frames pushed by the
+/// host are received one after another at the configured baud rate while the
+/// receiver is enabled. A frame completed while the receive buffer is full is
+/// lost. The peripheral clock equals the cpu clock, synchronous modes,
+/// multi-processor mode and frame or parity errors are not modeled.
 pub struct Usart {
-//    baudrate: u16,
-//    baudrate_scale: u8,
-    rx_enable: bool,
-    tx_enable: bool,
-    rx_buffer: Vec<u8>,
-    data_empty: bool,
+    ctrla: u8,
+    ctrlb: u8,
+    ctrlc: u8,
+    baudctrla: u8,
+    baudctrlb: u8,
+
+    /// frames pushed by the host, not yet received
+    rx_input: VecDeque<u16>,
+
+    /// peripheral clock cycle count when the frame on the line is completely received
+    rx_done: Option<u64>,
+
+    /// receive buffer (DATA)
+    rx_buffer: VecDeque<Received>,
+
+    /// transmit buffer (DATA) or `None` if empty (DREIF)
+    tx_buffer: Option<u16>,
+
+    /// peripheral clock cycle count when the frame in the shift register is sent
+    tx_done: Option<u64>,
+
+    /// transmit complete (TXCIF)
+    tx_complete: bool,
+
+    /// RXC, DRE and TXC interrupt vectors
+    vectors: [Option<usize>; 3],
+
+    /// sources of requested interrupts
+    raised: [bool; 3],
+
     tx_signal: UsartTxSignal,
 
     index: &'static str
@@ -29,12 +92,19 @@ pub struct Usart {
 impl Usart {
     pub fn new(index: &'static str) -> Usart {
         Usart {
-//            baudrate: 0,
-//            baudrate_scale: 0,
-            rx_enable: false,
-            tx_enable: false,
-            rx_buffer: vec![],
-            data_empty: true,
+            ctrla: 0,
+            ctrlb: 0,
+            ctrlc: 0x06,
+            baudctrla: 0,
+            baudctrlb: 0,
+            rx_input: VecDeque::new(),
+            rx_done: None,
+            rx_buffer: VecDeque::new(),
+            tx_buffer: None,
+            tx_done: None,
+            tx_complete: false,
+            vectors: [None; 3],
+            raised: [false; 3],
             tx_signal: Broadcast::new(),
             index
         }
@@ -47,69 +117,306 @@ impl Usart {
     }
 
     pub fn push(&mut self, input: &[u8]) {
-        self.rx_buffer.extend_from_slice(input);
+        self.rx_input.extend(input.iter().map(|&byte| byte as u16));
     }
 
-    fn data_read(&mut self, _core: &AvrCoreState, view: bool) -> u8 {
-        if self.rx_enable {
-            if let Some(&byte) = self.rx_buffer.iter().next() {
-                if !view {
-                    self.rx_buffer.remove(0);
+    /// push 9-bit frames, bit 8 is received in RXB8
+    pub fn push_frames(&mut self, input: &[u16]) {
+        self.rx_input.extend(input.iter().map(|&frame| frame & 0x1FF));
+    }
+
+    fn rx_enable(&self) -> bool { bit_at(self.ctrlb, RXEN) }
+
+    fn tx_enable(&self) -> bool { bit_at(self.ctrlb, TXEN) }
+
+    /// number of data bits (CHSIZE)
+    fn data_bits(&self) -> u64 {
+        match self.ctrlc & 0b111 {
+            size @ 0..=3 => 5 + size as u64,
+            CHSIZE_9BIT => 9,
+            _ => 8,
+        }
+    }
+
+    fn data_mask(&self) -> u16 {
+        (1 << self.data_bits()) - 1
+    }
+
+    /// bits of a frame including start, parity and stop bits
+    fn frame_bits(&self) -> u64 {
+        let parity = if (self.ctrlc >> 4) & 0b11 >= 2 { 1 } else { 0 };
+        let stop = if bit_at(self.ctrlc, 3) { 2 } else { 1 };
+        1 + self.data_bits() + parity + stop
+    }
+
+    /// cpu cycles of a frame at the baud rate of BSEL and BSCALE
+    pub fn frame_cycles(&self) -> u64 {
+        let bsel = ((self.baudctrlb as u64 & 0x0F) << 8) | self.baudctrla as u64;
+        let bscale = (self.baudctrlb as i8) >> 4;
+        let samples = if bit_at(self.ctrlb, CLK2X) { 8 } else { 16 };
+        let cycles = self.frame_bits() * samples;
+
+        if bscale >= 0 {
+            cycles * ((bsel + 1) << bscale)
+        } else {
+            // fractional baud rate generation
+            let scale = -bscale as u64;
+            (cycles * (bsel + (1 << scale))) >> scale
+        }
+    }
+
+    /// receive frames completed until cycle count `cycles`
+    fn receive(&mut self, cycles: u64) {
+        if !self.rx_enable() {
+            self.rx_done = None;
+            return;
+        }
+
+        loop {
+            let done = match self.rx_done {
+                Some(done) if done <= cycles => done,
+                Some(_) => return,
+                None if !self.rx_input.is_empty() => {
+                    self.rx_done = Some(cycles + self.frame_cycles());
+                    continue;
                 }
+                None => return,
+            };
+
+            let data = self.rx_input.pop_front().unwrap_or(0) & self.data_mask();
+            if self.rx_buffer.len() < RX_BUFFER_SIZE {
+                self.rx_buffer.push_back(Received { data, overflow: false });
+            } else {
                 info!(
                     target: "avrvc::usart",
-                    "USART {} Rx: 0x{:02x} {}",
-                    self.index,
-                    byte,
-                    display_ascii_char(byte));
-                return byte
+                    "USART {} Rx buffer overflow: 0x{:02x} lost", self.index, data);
+                if let Some(last) = self.rx_buffer.back_mut() {
+                    last.overflow = true;
+                }
             }
-        }
 
-        0
+            self.rx_done = if self.rx_input.is_empty() {
+                None
+            } else {
+                Some(done + self.frame_cycles())
+            };
+        }
     }
 
-    fn data_write(&mut self, _core: &mut AvrCoreState, value: u8) {
-        if self.tx_enable && self.data_empty {
+    /// move the transmit buffer into the idle shift register at cycle count `cycles`
+    fn start_transmit(&mut self, cycles: u64) {
+        if self.tx_done.is_some() {
+            return;
+        }
+        if let Some(data) = self.tx_buffer.take() {
             info!(
                 target: "avrvc::usart",
                 "USART {} Tx: 0x{:02x} {}",
                 self.index,
-                value,
-                display_ascii_char(value));
-            self.tx_signal.send(value);
+                data,
+                display_ascii_char(data as u8));
+            self.tx_signal.send(data as u8);
+            self.tx_done = Some(cycles + self.frame_cycles());
+        }
+    }
+
+    /// send frames completed until cycle count `cycles`
+    fn transmit(&mut self, cycles: u64) {
+        while let Some(done) = self.tx_done {
+            if done > cycles {
+                return;
+            }
+
+            self.tx_done = None;
+            if self.tx_buffer.is_some() {
+                self.start_transmit(done);
+            } else {
+                self.tx_complete = true;
+            }
+        }
+    }
+
+    /// flags of the interrupt sources
+    fn flags(&self) -> [bool; 3] {
+        [!self.rx_buffer.is_empty(), self.tx_buffer.is_none(), self.tx_complete]
+    }
+
+    /// request interrupts of set flags at the CTRLA levels and withdraw others
+    fn sync_irq(&mut self, core: &mut AvrCoreState) {
+        let flags = self.flags();
+        for source in 0..3 {
+            if let Some(vector) = self.vectors[source] {
+                let level = InterruptLevel::from_bits(self.ctrla >> LEVEL_SHIFTS[source]);
+                if flags[source] && level != InterruptLevel::Off {
+                    core.irq.raise_level(vector, level);
+                    self.raised[source] = true;
+                } else if self.raised[source] {
+                    core.irq.clear(vector);
+                    self.raised[source] = false;
+                }
+            }
+        }
+    }
+
+    fn clock(&mut self, core: &mut AvrCoreState) -> Option<u64> {
+        let cycles = core.clock_cycles(ClockDomain::Io);
+        self.receive(cycles);
+        self.transmit(cycles);
+        self.sync_irq(core);
+
+        let next = match (self.rx_done, self.tx_done) {
+            (Some(rx), Some(tx)) => Some(rx.min(tx)),
+            (rx, tx) => rx.or(tx),
+        };
+        next.map(|next| core.cpu_cycles(ClockDomain::Io, next))
+    }
+
+    /// TXCIF is cleared when the interrupt is executed
+    fn acknowledge(&mut self, vector: usize) {
+        for source in 0..3 {
+            if self.vectors[source] == Some(vector) {
+                if source == TXC {
+                    self.tx_complete = false;
+                }
+                self.raised[source] = false;
+            }
+        }
+    }
+
+    /// disable receiver and transmitter and flush received data
+    fn reset(&mut self) {
+        self.ctrla = 0;
+        self.ctrlb = 0;
+        self.ctrlc = 0x06;
+        self.baudctrla = 0;
+        self.baudctrlb = 0;
+        self.rx_input.clear();
+        self.rx_done = None;
+        self.rx_buffer.clear();
+        self.tx_buffer = None;
+        self.tx_done = None;
+        self.tx_complete = false;
+        self.raised = [false; 3];
+    }
+
+    fn data_read(&mut self, _core: &AvrCoreState, view: bool) -> u8 {
+        let received = if view {
+            self.rx_buffer.front().cloned()
+        } else {
+            self.rx_buffer.pop_front()
+        };
+
+        match received {
+            Some(received) => {
+                let byte = received.data as u8;
+                if !view {
+                    info!(
+                        target: "avrvc::usart",
+                        "USART {} Rx: 0x{:02x} {}",
+                        self.index,
+                        received.data,
+                        display_ascii_char(byte));
+                }
+                byte
+            },
+            None => 0
+        }
+    }
+
+    fn data_write(&mut self, core: &mut AvrCoreState, value: u8) {
+        if self.tx_enable() && self.tx_buffer.is_none() {
+            let bit8 = if bit_at(self.ctrlb, TXB8) { 0x100 } else { 0 };
+            self.tx_buffer = Some((bit8 | value as u16) & self.data_mask());
+            self.start_transmit(core.clock_cycles(ClockDomain::Io));
+            self.sync_irq(core);
         }
     }
 
     fn status_read(&mut self, _core: &AvrCoreState, _view: bool) -> u8 {
+        let front = self.rx_buffer.front();
         u8bits(
             !self.rx_buffer.is_empty(), // RXCIF: Receive Complete Interrupt Flag
-            false, // TXCIF: Transmit Complete Interrupt Flag
-            self.data_empty, // DREIF: Data Register Empty Flag
+            self.tx_complete, // TXCIF: Transmit Complete Interrupt Flag
+            self.tx_buffer.is_none(), // DREIF: Data Register Empty Flag
             false, // FERR: Frame Error
 
-            false, // BUFOVF: Buffer Overflow
+            front.map(|received| received.overflow).unwrap_or(false), // BUFOVF: Buffer Overflow
             false, // PERR: Parity Error
             false, // Reserved
-            false  // RXB8: Receive Bit 8
+            front.map(|received| received.data & 0x100 != 0).unwrap_or(false) // RXB8: Receive Bit 8
         )
     }
 
-    fn status_write(&mut self, _core: &mut AvrCoreState, _value: u8) {
+    /// TXCIF is cleared by writing one, the other flags are read-only
+    fn status_write(&mut self, core: &mut AvrCoreState, value: u8) {
+        if bit_at(value, 6) {
+            self.tx_complete = false;
+            self.sync_irq(core);
+        }
+    }
+
+    fn control_a_read(&mut self, _core: &AvrCoreState, _view: bool) -> u8 {
+        self.ctrla
+    }
 
+    fn control_a_write(&mut self, core: &mut AvrCoreState, value: u8) {
+        self.ctrla = value & 0x3F;
+        info!(
+            target: "avrvc::usart",
+            "USART {} Control A: RXCINTLVL={} TXCINTLVL={} DREINTLVL={}",
+            self.index, (value >> 4) & 0b11, (value >> 2) & 0b11, value & 0b11);
+        self.sync_irq(core);
     }
 
     fn control_b_read(&mut self, _core: &AvrCoreState, _view: bool) -> u8 {
-        0
+        self.ctrlb
     }
 
-    fn control_b_write(&mut self, _core: &mut AvrCoreState, value: u8) {
-        self.rx_enable = bit_at(value, 4);
-        self.tx_enable = bit_at(value, 3);
+    fn control_b_write(&mut self, core: &mut AvrCoreState, value: u8) {
+        self.transmit(core.clock_cycles(ClockDomain::Io));
+
+        self.ctrlb = value & 0x1F;
+        if !self.rx_enable() {
+            // disabling the receiver flushes the receive buffer
+            self.rx_buffer.clear();
+        }
+        self.receive(core.clock_cycles(ClockDomain::Io));
         info!(
             target: "avrvc::usart",
             "USART {} Control B: RXEN={} TXEN={}",
-            self.index, self.rx_enable as u8, self.tx_enable as u8);
+            self.index, self.rx_enable() as u8, self.tx_enable() as u8);
+        self.sync_irq(core);
+    }
+
+    fn control_c_read(&mut self, _core: &AvrCoreState, _view: bool) -> u8 {
+        self.ctrlc
+    }
+
+    fn control_c_write(&mut self, _core: &mut AvrCoreState, value: u8) {
+        self.ctrlc = value;
+    }
+
+    fn baud_a_read(&mut self, _core: &AvrCoreState, _view: bool) -> u8 {
+        self.baudctrla
+    }
+
+    fn baud_a_write(&mut self, _core: &mut AvrCoreState, value: u8) {
+        self.baudctrla = value;
+    }
+
+    fn baud_b_read(&mut self, _core: &AvrCoreState, _view: bool) -> u8 {
+        self.baudctrlb
+    }
+
+    fn baud_b_write(&mut self, _core: &mut AvrCoreState, value: u8) {
+        self.baudctrlb = value;
+        info!(
+            target: "avrvc::usart",
+            "USART {} Baud: BSEL={} BSCALE={} ({} cycles per frame)",
+            self.index,
+            ((value as u16 & 0x0F) << 8) | self.baudctrla as u16,
+            (value as i8) >> 4,
+            self.frame_cycles());
     }
 }
 
@@ -137,13 +444,29 @@ fn register_one_usart(
 
     let ioregs = vm.info.io_regs.clone();
 
-    let usart = Arc::new(Mutex::new(Usart::new(index)));
+    let mut usart = Usart::new(index);
+    for (source, name) in ["RXC", "DRE", "TXC"].iter().enumerate() {
+        usart.vectors[source] = vm.info.vectors.get(&*format!("USART{}_{}", index, name)).cloned();
+    }
+    let vectors = usart.vectors;
+
+    let usart = Arc::new(Mutex::new(usart));
     let usart1 = Arc::clone(&usart);
     let usart2 = Arc::clone(&usart);
     let usart3 = Arc::clone(&usart);
     let usart4 = Arc::clone(&usart);
     let usart5 = Arc::clone(&usart);
     let usart6 = Arc::clone(&usart);
+    let usart7 = Arc::clone(&usart);
+    let usart8 = Arc::clone(&usart);
+    let usart9 = Arc::clone(&usart);
+    let usart10 = Arc::clone(&usart);
+    let usart11 = Arc::clone(&usart);
+    let usart12 = Arc::clone(&usart);
+    let usart13 = Arc::clone(&usart);
+    let usart14 = Arc::clone(&usart);
+    let usart15 = Arc::clone(&usart);
+    let usart16 = Arc::clone(&usart);
 
     vm.register_io(
         ioregs[&*format!("USART{}_DATA", index)],
@@ -160,6 +483,175 @@ fn register_one_usart(
         Box::new(move |core, _, view| usart5.lock().unwrap().control_b_read(core, view)),
         Box::new(move |core, _, value| usart6.lock().unwrap().control_b_write(core, value))
     );
+    vm.register_io(
+        ioregs[&*format!("USART{}_CTRLA", index)],
+        Box::new(move |core, _, view| usart8.lock().unwrap().control_a_read(core, view)),
+        Box::new(move |core, _, value| usart9.lock().unwrap().control_a_write(core, value))
+    );
+    vm.register_io(
+        ioregs[&*format!("USART{}_CTRLC", index)],
+        Box::new(move |core, _, view| usart10.lock().unwrap().control_c_read(core, view)),
+        Box::new(move |core, _, value| usart11.lock().unwrap().control_c_write(core, value))
+    );
+    vm.register_io(
+        ioregs[&*format!("USART{}_BAUDCTRLA", index)],
+        Box::new(move |core, _, view| usart12.lock().unwrap().baud_a_read(core, view)),
+        Box::new(move |core, _, value| usart13.lock().unwrap().baud_a_write(core, value))
+    );
+    vm.register_io(
+        ioregs[&*format!("USART{}_BAUDCTRLB", index)],
+        Box::new(move |core, _, view| usart14.lock().unwrap().baud_b_read(core, view)),
+        Box::new(move |core, _, value| usart15.lock().unwrap().baud_b_write(core, value))
+    );
+
+    for vector in vectors.iter().filter_map(|&vector| vector) {
+        let usart17 = Arc::clone(&usart);
+        vm.register_interrupt_ack(
+            vector,
+            Box::new(move |_, vector| usart17.lock().unwrap().acknowledge(vector))
+        );
+    }
+
+    vm.register_clock(ClockDomain::Io, Box::new(move |core| usart16.lock().unwrap().clock(core)));
+    vm.register_reset(Box::new(move |_, _| usart7.lock().unwrap().reset()));
 
     Some(usart)
-}
\ No newline at end of file
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use emulator::AvrEmulator;
+    use models::test_util;
+    use models::test_util::write_ioreg;
+    use models::test_util::read_ioreg;
+    use models::test_util::load_ioreg;
+    use models::test_util::run;
+    use models::test_util::vector_address;
+    use futures::Future;
+    use futures::Stream;
+
+    fn create_emulator() -> AvrEmulator {
+        test_util::create_emulator("atxmega128a4u")
+    }
+
+    #[test]
+    fn frame_time() {
+        let mut usart = Usart::new("C0");
+        usart.baudctrla = 132;
+        assert_eq!(usart.frame_cycles(), 10 * 16 * 133);
+
+        // BSCALE = 2
+        usart.baudctrla = 1;
+        usart.baudctrlb = 0x20;
+        assert_eq!(usart.frame_cycles(), 10 * 16 * 2 * 4);
+        usart.ctrlb = 1 << CLK2X;
+        assert_eq!(usart.frame_cycles(), 10 * 8 * 2 * 4);
+
+        // BSCALE = -3, 9 data bits, even parity and 2 stop bits
+        usart.ctrlb = 0;
+        usart.baudctrla = 12;
+        usart.baudctrlb = 0xD0;
+        usart.ctrlc = 0x2F;
+        assert_eq!(usart.frame_cycles(), 13 * 16 * (12 + 8) / 8);
+    }
+
+    #[test]
+    fn transmit() {
+        let mut emulator = create_emulator();
+        let tx = emulator.usarts["C0"].lock().unwrap().connect_to_tx();
+        {
+            let vm = &mut emulator.vm;
+            write_ioreg(vm, "USARTC0_CTRLB", 1 << TXEN);
+
+            // first frame moves to the shift register immediately
+            write_ioreg(vm, "USARTC0_DATA", b'a');
+            assert_eq!(read_ioreg(vm, "USARTC0_STATUS"), 0x20);
+            write_ioreg(vm, "USARTC0_DATA", b'b');
+            assert_eq!(read_ioreg(vm, "USARTC0_STATUS"), 0x00);
+            // ignored while the data register is full
+            write_ioreg(vm, "USARTC0_DATA", b'c');
+
+            run(vm, 160);
+            assert_eq!(read_ioreg(vm, "USARTC0_STATUS"), 0x20);
+            run(vm, 160);
+            assert_eq!(read_ioreg(vm, "USARTC0_STATUS"), 0x60);
+
+            // TXCIF is cleared by writing one
+            write_ioreg(vm, "USARTC0_STATUS", 0xFF);
+            assert_eq!(read_ioreg(vm, "USARTC0_STATUS"), 0x20);
+        }
+        drop(emulator);
+
+        assert_eq!(tx.collect().wait().unwrap(), b"ab".to_vec());
+    }
+
+    #[test]
+    fn receive_buffer_overflow() {
+        let mut emulator = create_emulator();
+        emulator.usarts["C0"].lock().unwrap().push(b"abc");
+        let vm = &mut emulator.vm;
+
+        // nothing is received while the receiver is disabled
+        run(vm, 1000);
+        assert_eq!(read_ioreg(vm, "USARTC0_STATUS") & 0x80, 0);
+
+        write_ioreg(vm, "USARTC0_CTRLB", 1 << RXEN);
+        run(vm, 159);
+        assert_eq!(read_ioreg(vm, "USARTC0_STATUS") & 0x80, 0);
+        run(vm, 2 * 160 + 1);
+        assert_eq!(read_ioreg(vm, "USARTC0_STATUS") & 0x88, 0x80);
+
+        // third frame is lost
+        assert_eq!(load_ioreg(vm, "USARTC0_DATA"), b'a');
+        assert_eq!(read_ioreg(vm, "USARTC0_STATUS") & 0x88, 0x88);
+        assert_eq!(load_ioreg(vm, "USARTC0_DATA"), b'b');
+        assert_eq!(read_ioreg(vm, "USARTC0_STATUS") & 0x88, 0x00);
+        assert_eq!(load_ioreg(vm, "USARTC0_DATA"), 0);
+    }
+
+    #[test]
+    fn nine_bit_frames() {
+        let mut emulator = create_emulator();
+        emulator.usarts["C0"].lock().unwrap().push_frames(&[0x1A5, 0x05A]);
+        let vm = &mut emulator.vm;
+
+        write_ioreg(vm, "USARTC0_CTRLC", CHSIZE_9BIT);
+        write_ioreg(vm, "USARTC0_CTRLB", 1 << RXEN);
+        run(vm, 2 * 12 * 16);
+
+        assert_eq!(read_ioreg(vm, "USARTC0_STATUS") & 0x81, 0x81);
+        assert_eq!(load_ioreg(vm, "USARTC0_DATA"), 0xA5);
+        assert_eq!(read_ioreg(vm, "USARTC0_STATUS") & 0x81, 0x80);
+        assert_eq!(load_ioreg(vm, "USARTC0_DATA"), 0x5A);
+    }
+
+    #[test]
+    fn interrupts() {
+        let mut emulator = create_emulator();
+        emulator.usarts["C0"].lock().unwrap().push(b"x");
+        let vm = &mut emulator.vm;
+        vm.core.interrupt = true;
+        write_ioreg(vm, "PMIC_CTRL", 0x07);
+
+        // RXC at high level
+        write_ioreg(vm, "USARTC0_CTRLA", 0x30);
+        write_ioreg(vm, "USARTC0_CTRLB", (1 << RXEN) | (1 << TXEN));
+        run(vm, 160);
+        vm.step().unwrap();
+        assert_eq!(vm.core.pc, vector_address(vm, "USARTC0_RXC"));
+        assert_eq!(load_ioreg(vm, "USARTC0_DATA"), b'x');
+
+        // TXC at low level, TXCIF is cleared by the interrupt
+        write_ioreg(vm, "USARTC0_CTRLA", 0x04);
+        write_ioreg(vm, "USARTC0_DATA", b'y');
+        run(vm, 160);
+        assert_eq!(read_ioreg(vm, "USARTC0_STATUS"), 0x60);
+        vm.return_from_interrupt();
+        vm.step().unwrap();
+        vm.step().unwrap();
+        assert_eq!(vm.core.pc, vector_address(vm, "USARTC0_TXC"));
+        assert_eq!(read_ioreg(vm, "USARTC0_STATUS"), 0x20);
+    }
+}
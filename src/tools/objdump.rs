@@ -4,6 +4,11 @@ use decoder::Decoder;
 use instruction_set::RegIncDec;
 
 
+/// disassembly in the format of avr-objdump
+///
+/// Like avr-objdump, the aliases lsl, rol, tst, clr, ser, sbr and cbr are
+/// printed as the instructions they are encoded with (add, adc, and, eor,
+/// ldi, ori and andi).
 pub trait ObjDumpInstr {
     fn dump(&self) -> String;
 }
@@ -57,17 +62,35 @@ impl ObjDumpInstr for Instruction {
 
             &Bst { d, b } => format!("bst\tr{}, {}", d, b),
             &Call { k } => format_calljmp("call", k),
+            &Cbi { a, b } => format!("cbi\t0x{:02x}, {}", a, b),
+            &Clc => String::from("clc"),
+            &Clh => String::from("clh"),
             &Cli => String::from("cli"),
+            &Cln => String::from("cln"),
+            &Cls => String::from("cls"),
+            &Clt => String::from("clt"),
+            &Clv => String::from("clv"),
+            &Clz => String::from("clz"),
             &Cp { d, r } => format!("cp\tr{}, r{}", d, r),
             &Cpc { d, r } => format!("cpc\tr{}, r{}", d, r),
             &Cpi { d, k } => format!("cpi\tr{}, 0x{:02X}", d, k),
+            &Cpse { d, r } => format!("cpse\tr{}, r{}", d, r),
             &Com { d } => format!("com\tr{}", d),
             &Dec { d } => format!("dec\tr{}", d),
+            &Des { k } => format!("des\t{}", k),
 
+            &Eicall => String::from("eicall"),
+            &Eijmp => String::from("eijmp"),
             &Elpm0 => String::from("elpm"),
             &Elpm { d } => format!("elpm\tr{}, Z", d),
             &ElpmInc { d } => format!("elpm\tr{}, Z+", d),
             &Eor { d, r } => format!("eor\tr{}, r{}", d, r),
+            &Fmul { d, r } => format!("fmul\tr{}, r{}", d, r),
+            &Fmuls { d, r } => format!("fmuls\tr{}, r{}", d, r),
+            &Fmulsu { d, r } => format!("fmulsu\tr{}, r{}", d, r),
+            &Lac { d } => format!("lac\tZ, r{}", d),
+            &Las { d } => format!("las\tZ, r{}", d),
+            &Lat { d } => format!("lat\tZ, r{}", d),
             &LdX { d, xop } => format!("ld\tr{}, {}", d, incdec("X", xop)),
             &LdY { d, yop } => format!("ld\tr{}, {}", d, incdec("Y", yop)),
             &LdZ { d, zop } => format!("ld\tr{}, {}", d, incdec("Z", zop)),
@@ -76,11 +99,21 @@ impl ObjDumpInstr for Instruction {
             &Ldi { d, k } => format!("ldi\tr{}, 0x{:02X}", d, k),
             &Lds { d, k } => format!("lds\tr{}, 0x{:02X}", d, k),
             &Lds16 { d, k } => format!("lds\tr{}, 0x{:04X}", d, k),
+            &Lpm0 => String::from("lpm"),
+            &Lpm { d } => format!("lpm\tr{}, Z", d),
+            &LpmInc { d } => format!("lpm\tr{}, Z+", d),
             &Lsr { d } => format!("lsr\tr{}", d),
+            &Icall => String::from("icall"),
+            &Ijmp => String::from("ijmp"),
             &In { d, a } => format!("in\tr{}, 0x{:02x}", d, a),
             &Inc { d } => format!("inc\tr{}", d),
             &Jmp { k } => format_calljmp("jmp", k),
+            &Neg { d } => format!("neg\tr{}", d),
             &Nop => String::from("nop"),
+            &Sleep => String::from("sleep"),
+            &Wdr => String::from("wdr"),
+            &Spm => String::from("spm"),
+            &SpmInc => String::from("spm\tZ+"),
             &Mov { d, r } => format!("mov\tr{}, r{}", d, r),
             &Movw { d, r } => format!("movw\tr{}, r{}", d, r),
             &Mul { d, r } => format!("mul\tr{}, r{}", d, r),
@@ -93,15 +126,25 @@ impl ObjDumpInstr for Instruction {
             &Push { r } => format!("push\tr{}", r),
             &Rcall { k } => format!("rcall\t.{:+}", k * 2),
             &Ret => String::from("ret"),
+            &Reti => String::from("reti"),
             &Ror { d } => format!("ror\tr{}", d),
             &Rjmp { k } => format!("rjmp\t.{:+}", k * 2),
             &Sbc { d, r } => format!("sbc\tr{}, r{}", d, r),
-            &Sbic { r, b } => format!("sbic\t0x{:02x}, {}", r, b),
-            &Sbis { r, b } => format!("sbis\t0x{:02x}, {}", r, b),
+            &Sbi { a, b } => format!("sbi\t0x{:02x}, {}", a, b),
+            &Sbic { a, b } => format!("sbic\t0x{:02x}, {}", a, b),
+            &Sbis { a, b } => format!("sbis\t0x{:02x}, {}", a, b),
             &Sbci { d, k } => format!("sbci\tr{}, 0x{:02X}", d, k),
             &Sbiw { d, k } => format!("sbiw\tr{}, 0x{:02x}", d, k),
             &Sbrc { r, b } => format!("sbrc\tr{}, {}", r, b),
             &Sbrs { r, b } => format!("sbrs\tr{}, {}", r, b),
+            &Sec => String::from("sec"),
+            &Seh => String::from("seh"),
+            &Sei => String::from("sei"),
+            &Sen => String::from("sen"),
+            &Ses => String::from("ses"),
+            &Set => String::from("set"),
+            &Sev => String::from("sev"),
+            &Sez => String::from("sez"),
             &StX { r, xop } => format!("st\t{}, r{}", incdec("X", xop), r),
             &StY { r, yop } => format!("st\t{}, r{}", incdec("Y", yop), r),
             &StZ { r, zop } => format!("st\t{}, r{}", incdec("Z", zop), r),
@@ -113,6 +156,7 @@ impl ObjDumpInstr for Instruction {
             &Subi { d, k } => format!("subi\tr{}, 0x{:02X}", d, k),
             &Swap { d } => format!("swap\tr{}", d),
 
+            &Xch { d } => format!("xch\tZ, r{}", d),
             &Invaild { .. } => format!("invalid"),
         }
     }
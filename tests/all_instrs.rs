@@ -5,6 +5,13 @@ extern crate pretty_assertions;
 
 use avrvc::executable::read_executable_file;
 use avrvc::tools::objdump::objdump;
+use avrvc::tools::objdump::ObjDumpInstr;
+use avrvc::decoder::AvrDecoder;
+use avrvc::decoder::Decoder;
+use avrvc::core::AvrVm;
+use avrvc::core::AvrVmInfo;
+use avrvc::models::xmega_au::XmegaA4U::ATxmega128A4U;
+use avrvc::models::AvrModel;
 use std::fs;
 use std::path::Path;
 
@@ -41,4 +48,109 @@ fn all_instrs() {
     assert_eq!(decoded_exp, 63983);
     println!("actual: decoded {} out of {}", decoded_act, 65537);
     println!("expected: decoded {} out of {}", decoded_exp, 65537);
-}
\ No newline at end of file
+}
+
+fn execute_opcode(vm: &mut AvrVm, opcode: u16) {
+    let decoder = AvrDecoder::new();
+    let instr = decoder.decode(&vec![opcode as u8, (opcode >> 8) as u8], 0);
+    instr.execute(vm).unwrap();
+}
+
+#[test]
+fn bset_bclr() {
+    let mut vm = ATxmega128A4U.create_vm();
+    let flags = ["c", "z", "n", "v", "s", "h", "t", "i"];
+
+    for s in 0..8u16 {
+        let bset = 0b_1001_0100_0000_1000_u16 | (s << 4);
+        let bclr = 0b_1001_0100_1000_1000_u16 | (s << 4);
+
+        vm.core.write_sreg(0x00);
+        execute_opcode(&mut vm, bset);
+        assert_eq!(vm.core.read_sreg(), 1 << s, "se{}", flags[s as usize]);
+
+        vm.core.write_sreg(0xFF);
+        execute_opcode(&mut vm, bclr);
+        assert_eq!(vm.core.read_sreg(), !(1 << s) as u8, "cl{}", flags[s as usize]);
+    }
+}
+
+#[test]
+fn bset_bclr_objdump() {
+    let decoder = AvrDecoder::new();
+    let flags = ["c", "z", "n", "v", "s", "h", "t", "i"];
+
+    for s in 0..8u16 {
+        let bset = 0b_1001_0100_0000_1000_u16 | (s << 4);
+        let bclr = 0b_1001_0100_1000_1000_u16 | (s << 4);
+
+        let instr = decoder.decode(&vec![bset as u8, (bset >> 8) as u8], 0);
+        assert_eq!(instr.dump(), format!("se{}", flags[s as usize]));
+
+        let instr = decoder.decode(&vec![bclr as u8, (bclr >> 8) as u8], 0);
+        assert_eq!(instr.dump(), format!("cl{}", flags[s as usize]));
+    }
+}
+
+#[test]
+fn sei_delays_interrupt() {
+    let mut vm = AvrVm::new(&AvrVmInfo::from_name("atmega16"));
+    vm.write_flash(0, &vec![0u8; 0x400]);
+    vm.core.sp = vm.info.ram.end - 1;
+    vm.core.pc = 0x100;
+    vm.core.interrupt = false;
+    vm.raise_interrupt(3);
+
+    execute_opcode(&mut vm, 0b_1001_0100_0111_1000_u16); // sei
+    assert_eq!(vm.core.interrupt, true);
+
+    // one instruction after SEI is executed before the interrupt
+    vm.step().unwrap();
+    assert_eq!(vm.core.pc, 0x102);
+    vm.step().unwrap();
+    assert_eq!(vm.core.pc, 3 * 2);
+}
+
+#[test]
+fn reti() {
+    let mut vm = AvrVm::new(&AvrVmInfo::from_name("atmega16"));
+    vm.write_flash(0, &vec![0u8; 0x400]);
+    vm.core.sp = vm.info.ram.end - 1;
+    vm.core.pc = 0x100;
+    vm.core.interrupt = true;
+    vm.raise_interrupt(3);
+    vm.raise_interrupt(4);
+
+    vm.step().unwrap();
+    assert_eq!(vm.core.pc, 3 * 2);
+    assert_eq!(vm.core.interrupt, false);
+
+    vm.core.cycles = 0;
+    execute_opcode(&mut vm, 0b_1001_0101_0001_1000_u16); // reti
+    assert_eq!(vm.core.pc, 0x100);
+    assert_eq!(vm.core.interrupt, true);
+    assert_eq!(vm.core.cycles, 4);
+
+    // one instruction of main program is executed before next interrupt
+    vm.step().unwrap();
+    assert_eq!(vm.core.pc, 0x101);
+    vm.step().unwrap();
+    assert_eq!(vm.core.pc, 4 * 2);
+}
+
+#[test]
+fn aliases_objdump() {
+    let decoder = AvrDecoder::new();
+    let dump = |opcode: u16| decoder.decode(&vec![opcode as u8, (opcode >> 8) as u8], 0).dump();
+
+    // avr-objdump prints the instructions the aliases are encoded with
+    assert_eq!(dump(0x0F88), "add\tr24, r24"); // lsl r24
+    assert_eq!(dump(0x1F88), "adc\tr24, r24"); // rol r24
+    assert_eq!(dump(0x2388), "and\tr24, r24"); // tst r24
+    assert_eq!(dump(0x2788), "eor\tr24, r24"); // clr r24
+    assert_eq!(dump(0xEF8F), "ldi\tr24, 0xFF"); // ser r24
+    assert_eq!(dump(0x6081), "ori\tr24, 0x01"); // sbr r24, 0x01
+    assert_eq!(dump(0x7F8E), "andi\tr24, 0xFE"); // cbr r24, 0x01
+    assert_eq!(dump(0x9581), "neg\tr24");
+    assert_eq!(dump(0x1389), "cpse\tr24, r25");
+}
@@ -4,6 +4,8 @@ extern crate avrvc;
 extern crate pretty_assertions;
 
 use avrvc::executable::read_executable_file;
+use avrvc::executable::read_elf_file;
+use avrvc::core::StackGuard;
 use avrvc::tools::objdump::objdump;
 use std::fs::File;
 use std::io::Read;
@@ -63,4 +65,26 @@ fn run_nothing() {
     assert_eq!(vm.core.read_y(), 0x3FFF);
     assert_eq!(vm.core.read_z(), 0x0000);
     assert_eq!(vm.core.read_sreg(), 0x00);
-}
\ No newline at end of file
+}
+
+#[test]
+fn run_nothing_stack_guard() {
+    let bytes = read_executable_file(Path::new("tests/nothing/main.bin"));
+    let elf = read_elf_file(Path::new("tests/nothing/main.elf"));
+
+    let mut vm = ATxmega128A4U.create_vm();
+    vm.write_flash(0, &bytes);
+    vm.stack_guard = StackGuard::from_elf(&elf);
+    assert_eq!(vm.stack_guard, Some(StackGuard::new(0x2000, 0x3FFF)));
+
+    vm.debugger.add_breakpoint(0x11A);
+
+    let mut signal = None;
+    for _i in 0..100 {
+        if let Err(s) = vm.step() {
+            signal = Some(s);
+            break;
+        }
+    }
+    assert_eq!(signal, Some(CpuSignal::Break));
+}
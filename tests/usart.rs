@@ -24,7 +24,7 @@ use std::path::Path;
 use std::char;
 
 
-static MAX_CYCLES: usize = 1000 * 1000;
+static MAX_CYCLES: usize = 10 * 1000 * 1000;
 
 
 fn find_all(input: &str, pat: &str) -> Vec<usize> {